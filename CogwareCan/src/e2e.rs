@@ -0,0 +1,121 @@
+use core::cell::Cell;
+use core::ops::AddAssign;
+use critical_section::Mutex;
+
+/// Bytes the E2E trailer adds after the value: rolling counter, then CRC.
+pub const E2E_TRAILER_LEN: usize = 2;
+
+/// The counter uses all of its four bits and wraps after 15. AUTOSAR E2E profile 1, whose CRC
+/// this shares, stops at 14 instead.
+pub const E2E_COUNTER_MODULO: u8 = 16;
+
+/// Largest counter jump still accepted as in sequence, i.e. one lost frame.
+pub const E2E_MAX_DELTA_COUNTER: u8 = 2;
+
+static E2E_ENABLED: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// Whether gauge frames carry the counter/CRC trailer.
+pub fn e2e_enabled() -> bool {
+    critical_section::with(|cs| E2E_ENABLED.borrow(cs).get())
+}
+
+/// Turn the counter/CRC trailer on or off, both ends of the bus have to agree.
+pub fn set_e2e(enabled: bool) {
+    critical_section::with(|cs| E2E_ENABLED.borrow(cs).set(enabled))
+}
+
+/// CRC-8 SAE J1850 (poly 0x1D, init and xor-out 0xFF), as used by E2E profile 1.
+pub fn crc8(data: &[u8]) -> u8 {
+    let crc = data.iter().fold(0xFFu8, |mut crc, byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x1D
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    });
+    crc ^ 0xFF
+}
+
+/// CRC over the gauge id (the E2E "data id") followed by the value bytes and the counter.
+pub(crate) fn e2e_crc(data_id: u16, payload: &[u8]) -> u8 {
+    let mut buf = [0u8; 8];
+    buf[..2].copy_from_slice(&data_id.to_le_bytes());
+    buf[2..2 + payload.len()].copy_from_slice(payload);
+    crc8(&buf[..2 + payload.len()])
+}
+
+/// Per-gauge receive error counters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct E2eStats {
+    /// Frames that passed the checks.
+    pub ok: u32,
+    /// Frames whose CRC didn't match.
+    pub crc_errors: u32,
+    /// Frames with the wrong length for the gauge.
+    pub length_errors: u32,
+    /// Frames repeating the previous counter.
+    pub repeated: u32,
+    /// Frames missing between two received counters.
+    pub lost: u32,
+    /// Times the gauge went invalid because no good frame came in time.
+    pub timeouts: u32,
+}
+
+impl AddAssign for E2eStats {
+    fn add_assign(&mut self, other: E2eStats) {
+        self.ok += other.ok;
+        self.crc_errors += other.crc_errors;
+        self.length_errors += other.length_errors;
+        self.repeated += other.repeated;
+        self.lost += other.lost;
+        self.timeouts += other.timeouts;
+    }
+}
+
+/// What the receiver knows about a gauge's frame sequence. The default has nothing received
+/// yet, so nothing to trust either.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct E2eState {
+    pub(crate) last_counter: Option<u8>,
+    pub(crate) valid: bool,
+    /// A frame passed the checks since the last timeout check.
+    pub(crate) fresh: bool,
+    pub(crate) stats: E2eStats,
+}
+
+/// Outcome of checking one received frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum E2eStatus {
+    /// In sequence, the value is trustworthy.
+    Ok,
+    /// Same counter as the last frame, the value is stale.
+    Repeated,
+    /// Too many frames lost in between, the value is fresh but the channel is suspect.
+    WrongSequence,
+    /// CRC or length mismatch, the value must not be used.
+    Error,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc8_matches_sae_j1850() {
+        // The catalogue check value of CRC-8/SAE-J1850.
+        assert_eq!(crc8(b"123456789"), 0x4B);
+        assert_eq!(crc8(&[]), 0x00);
+        assert_eq!(crc8(&[0x00]), 0x3B);
+    }
+
+    #[test]
+    fn crc_covers_the_data_id() {
+        let payload = [0x2A, 0x03];
+        assert_eq!(e2e_crc(0x2D, &payload), crc8(&[0x2D, 0x00, 0x2A, 0x03]));
+        assert_ne!(e2e_crc(0x2D, &payload), e2e_crc(0x2E, &payload));
+    }
+}
@@ -0,0 +1,305 @@
+use embedded_hal_0_2::can::{Frame, Id};
+use mcp2515::frame::CanFrame;
+
+use crate::IdConfig;
+
+/// One side of the gateway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    /// The bus the dash reads gauges from, usually the ECU bus.
+    Primary,
+    /// The second controller, usually the OEM vehicle bus.
+    Secondary,
+}
+
+impl Port {
+    /// The port frames from this one are forwarded to.
+    pub fn other(&self) -> Port {
+        match self {
+            Port::Primary => Port::Secondary,
+            Port::Secondary => Port::Primary,
+        }
+    }
+}
+
+/// Matches ids where `id & mask == self.id & mask`, of one id width only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdFilter {
+    pub id: u32,
+    pub mask: u32,
+    pub extended: bool,
+}
+
+impl IdFilter {
+    pub fn matches(&self, id: Id) -> bool {
+        let (raw, extended) = raw_id(id);
+        extended == self.extended && raw & self.mask == self.id & self.mask
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteAction {
+    /// Forward the frame unchanged.
+    Forward,
+    /// Forward the frame with its id replaced.
+    Remap(Id),
+    /// Don't forward the frame.
+    Drop,
+}
+
+/// A forwarding rule, the first route matching a frame decides what happens to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    pub from: Port,
+    pub filter: IdFilter,
+    pub action: RouteAction,
+}
+
+impl Route {
+    /// Parse a route from its config form:
+    ///
+    /// `<primary|secondary> [ext] <id>[/<mask>] <forward|drop|remap [ext] <id>>`
+    ///
+    /// Ids and masks are decimal or `0x` hex, `ext` selects 29-bit ids.
+    pub fn parse(line: &str) -> Option<Route> {
+        let mut tokens = line.split_whitespace();
+
+        let from = match tokens.next()? {
+            t if t.eq_ignore_ascii_case("primary") => Port::Primary,
+            t if t.eq_ignore_ascii_case("secondary") => Port::Secondary,
+            _ => return None,
+        };
+
+        let (extended, token) = ext_prefix(&mut tokens)?;
+        let (id, mask) = match token.split_once('/') {
+            Some((id, mask)) => (parse_number(id)?, parse_number(mask)?),
+            None => (parse_number(token)?, u32::MAX),
+        };
+
+        let action = match tokens.next()? {
+            t if t.eq_ignore_ascii_case("forward") => RouteAction::Forward,
+            t if t.eq_ignore_ascii_case("drop") => RouteAction::Drop,
+            t if t.eq_ignore_ascii_case("remap") => {
+                let (extended, token) = ext_prefix(&mut tokens)?;
+                RouteAction::Remap(IdConfig::new(parse_number(token)?, extended).id(0)?)
+            }
+            _ => return None,
+        };
+
+        if tokens.next().is_some() {
+            return None;
+        }
+
+        Some(Route {
+            from,
+            filter: IdFilter { id, mask, extended },
+            action,
+        })
+    }
+}
+
+/// Frame counters, per direction of travel.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GatewayStats {
+    pub forwarded: u32,
+    pub remapped: u32,
+    pub dropped: u32,
+}
+
+/// Forwards, remaps and filters frames between two CAN controllers.
+///
+/// The gateway only decides; reading and sending frames is left to the caller so it works with
+/// any pair of controllers.
+pub struct Gateway<'r> {
+    routes: &'r [Route],
+    default: RouteAction,
+    stats: [GatewayStats; 2],
+}
+
+impl<'r> Gateway<'r> {
+    /// `default` applies to frames no route matches.
+    pub fn new(routes: &'r [Route], default: RouteAction) -> Self {
+        Gateway {
+            routes,
+            default,
+            stats: [GatewayStats::default(); 2],
+        }
+    }
+
+    /// Decide what happens to `frame` received on `from`.
+    ///
+    /// Returns the frame to send on `from.other()`, or `None` if it is dropped.
+    pub fn route(&mut self, from: Port, frame: &CanFrame) -> Option<CanFrame> {
+        let action = self
+            .routes
+            .iter()
+            .find(|r| r.from == from && r.filter.matches(frame.id()))
+            .map_or(self.default, |r| r.action);
+
+        let stats = &mut self.stats[from as usize];
+        let id = match action {
+            RouteAction::Forward => {
+                stats.forwarded += 1;
+                frame.id()
+            }
+            RouteAction::Remap(id) => {
+                stats.remapped += 1;
+                id
+            }
+            RouteAction::Drop => {
+                stats.dropped += 1;
+                return None;
+            }
+        };
+
+        if frame.is_remote_frame() {
+            CanFrame::new_remote(id, frame.dlc())
+        } else {
+            CanFrame::new(id, frame.data())
+        }
+    }
+
+    /// Counters for frames received on `from`.
+    pub fn stats(&self, from: Port) -> GatewayStats {
+        self.stats[from as usize]
+    }
+}
+
+fn raw_id(id: Id) -> (u32, bool) {
+    match id {
+        Id::Standard(id) => (id.as_raw() as u32, false),
+        Id::Extended(id) => (id.as_raw(), true),
+    }
+}
+
+/// Takes an optional `ext` marker and the token after it.
+fn ext_prefix<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Option<(bool, &'a str)> {
+    match tokens.next()? {
+        t if t.eq_ignore_ascii_case("ext") => Some((true, tokens.next()?)),
+        t => Some((false, t)),
+    }
+}
+
+fn parse_number(value: &str) -> Option<u32> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_0_2::can::{ExtendedId, StandardId};
+
+    fn std_id(id: u16) -> Id {
+        Id::Standard(StandardId::new(id).unwrap())
+    }
+
+    fn ext_id(id: u32) -> Id {
+        Id::Extended(ExtendedId::new(id).unwrap())
+    }
+
+    #[test]
+    fn routes_parse_from_config_lines() {
+        assert_eq!(
+            Route::parse("primary 0x5F0/0x7F0 forward"),
+            Some(Route {
+                from: Port::Primary,
+                filter: IdFilter {
+                    id: 0x5F0,
+                    mask: 0x7F0,
+                    extended: false,
+                },
+                action: RouteAction::Forward,
+            })
+        );
+        assert_eq!(
+            Route::parse("SECONDARY ext 0x18FEF100 remap 0x123"),
+            Some(Route {
+                from: Port::Secondary,
+                filter: IdFilter {
+                    id: 0x18FE_F100,
+                    mask: u32::MAX,
+                    extended: true,
+                },
+                action: RouteAction::Remap(std_id(0x123)),
+            })
+        );
+        let route = Route::parse("secondary 1024 remap ext 0x1000").unwrap();
+        assert_eq!(route.action, RouteAction::Remap(ext_id(0x1000)));
+        assert_eq!(Route::parse("primary 0x100 drop").unwrap().action, RouteAction::Drop);
+    }
+
+    #[test]
+    fn bad_routes_are_rejected() {
+        for line in [
+            "",
+            "tertiary 0x100 forward",
+            "primary forward",
+            "primary 0x100",
+            "primary 0x100 bounce",
+            "primary 0x100/zz forward",
+            "primary 0x100 forward now",
+            // Too wide for an 11-bit id.
+            "primary 0x100 remap 0x800",
+        ] {
+            assert_eq!(Route::parse(line), None, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn first_matching_route_decides() {
+        let routes = [
+            Route::parse("primary 0x100 drop").unwrap(),
+            Route::parse("primary 0x100/0x700 remap 0x400").unwrap(),
+            Route::parse("secondary ext 0x18FEF100 remap 0x7E8").unwrap(),
+        ];
+        let mut gateway = Gateway::new(&routes, RouteAction::Forward);
+        let frame = |id| CanFrame::new(id, &[1, 2, 3]).unwrap();
+
+        assert!(gateway.route(Port::Primary, &frame(std_id(0x100))).is_none());
+        let remapped = gateway.route(Port::Primary, &frame(std_id(0x123))).unwrap();
+        assert_eq!(remapped.id(), std_id(0x400));
+        assert_eq!(remapped.data(), &[1, 2, 3]);
+        // The same id on the other port, or of the other width, takes the default.
+        let forwarded = gateway.route(Port::Secondary, &frame(std_id(0x123))).unwrap();
+        assert_eq!(forwarded.id(), std_id(0x123));
+        let forwarded = gateway.route(Port::Primary, &frame(ext_id(0x100))).unwrap();
+        assert_eq!(forwarded.id(), ext_id(0x100));
+        let remapped = gateway.route(Port::Secondary, &frame(ext_id(0x18FE_F100))).unwrap();
+        assert_eq!(remapped.id(), std_id(0x7E8));
+
+        assert_eq!(
+            gateway.stats(Port::Primary),
+            GatewayStats {
+                forwarded: 1,
+                remapped: 1,
+                dropped: 1,
+            }
+        );
+        assert_eq!(
+            gateway.stats(Port::Secondary),
+            GatewayStats {
+                forwarded: 1,
+                remapped: 1,
+                dropped: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn default_action_and_remote_frames() {
+        let mut gateway = Gateway::new(&[], RouteAction::Drop);
+        let frame = CanFrame::new(std_id(0x200), &[0; 8]).unwrap();
+        assert!(gateway.route(Port::Primary, &frame).is_none());
+
+        let routes = [Route::parse("primary 0x200 remap 0x201").unwrap()];
+        let mut gateway = Gateway::new(&routes, RouteAction::Drop);
+        let request = CanFrame::new_remote(std_id(0x200), 4).unwrap();
+        let forwarded = gateway.route(Port::Primary, &request).unwrap();
+        assert!(forwarded.is_remote_frame());
+        assert_eq!(forwarded.id(), std_id(0x201));
+        assert_eq!(forwarded.dlc(), 4);
+    }
+}
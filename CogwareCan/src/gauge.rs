@@ -1,11 +1,13 @@
 use core::cell::Cell;
 use core::ops::Deref;
 use critical_section::Mutex;
-use embedded_hal_0_2::can::{Frame, Id, StandardId};
+use embedded_hal_0_2::can::{Frame, Id};
 use mcp2515::frame::CanFrame;
-use strum_macros::FromRepr;
+use strum_macros::{EnumString, FromRepr};
 use paste::paste;
 
+use crate::e2e::*;
+
 #[non_exhaustive]
 pub enum DataWidth {
     U8,
@@ -27,6 +29,11 @@ pub struct GaugeData {
     pub id: u16,
     pub width: DataWidth,
     pub value: Mutex<Cell<u32>>,
+    /// A value was stored since power-on, the initial one is only a placeholder.
+    received: Mutex<Cell<bool>>,
+    tx_counter: Mutex<Cell<u8>>,
+    /// `None` until the first frame and after a reset, read as [`E2eState::default`].
+    e2e: Mutex<Cell<Option<E2eState>>>,
 }
 
 impl GaugeData {
@@ -35,6 +42,9 @@ impl GaugeData {
             id,
             width,
             value: Mutex::new(Cell::new(initial_value)),
+            received: Mutex::new(Cell::new(false)),
+            tx_counter: Mutex::new(Cell::new(0)),
+            e2e: Mutex::new(Cell::new(None)),
         }
     }
 
@@ -42,9 +52,31 @@ impl GaugeData {
         self.width.num_bytes()
     }
 
+    /// Build the frame carrying this gauge, using the configured id space.
+    ///
+    /// With E2E enabled the value is followed by a rolling counter and a CRC.
     pub fn to_frame(&self) -> Option<CanFrame> {
-        let data = &self.get().to_le_bytes()[..self.width()];
-        CanFrame::new(Id::Standard(StandardId::new(self.id.into())?), data)
+        let id = crate::id_config().id(self.id)?;
+        let value = self.get().to_le_bytes();
+        let len = self.width();
+        if !e2e_enabled() {
+            return CanFrame::new(id, &value[..len]);
+        }
+
+        let mut data = [0u8; 4 + E2E_TRAILER_LEN];
+        data[..len].copy_from_slice(&value[..len]);
+        data[len] = self.next_counter();
+        data[len + 1] = e2e_crc(self.id, &data[..=len]);
+        CanFrame::new(id, &data[..len + E2E_TRAILER_LEN])
+    }
+
+    fn next_counter(&self) -> u8 {
+        critical_section::with(|cs| {
+            let counter = self.tx_counter.borrow(cs);
+            let current = counter.get();
+            counter.set((current + 1) % E2E_COUNTER_MODULO);
+            current
+        })
     }
 
     pub fn get(&self) -> u32 {
@@ -57,9 +89,15 @@ impl GaugeData {
     pub fn set(&self, value: u32) {
         critical_section::with(|cs| {
             self.value.borrow(cs).set(value);
+            self.received.borrow(cs).set(true);
         })
     }
 
+    /// Whether the gauge has had a value since power-on, as opposed to its initial one.
+    pub fn received(&self) -> bool {
+        critical_section::with(|cs| self.received.borrow(cs).get())
+    }
+
 
     pub fn set_from_bytes(&self, data: &[u8]) {
         let mut bytes = [0; 4];
@@ -69,13 +107,110 @@ impl GaugeData {
     }
 
     pub fn set_from_frame(&self, frame: CanFrame) {
+        self.receive(&frame);
+    }
+
+    /// Take the value from a received frame, checking the E2E trailer if enabled.
+    ///
+    /// Frames failing the CRC or length check are discarded and mark the gauge invalid, as does
+    /// a counter jump of more than [`E2E_MAX_DELTA_COUNTER`]. The next in-sequence frame makes
+    /// it valid again, so does the first one after power-on.
+    pub fn receive(&self, frame: &CanFrame) -> E2eStatus {
         let data = &frame.data()[..frame.dlc()];
+        if !e2e_enabled() {
+            self.set_from_bytes(data);
+            return E2eStatus::Ok;
+        }
+
+        let len = self.width();
+        critical_section::with(|cs| {
+            let cell = self.e2e.borrow(cs);
+            let mut state = cell.get().unwrap_or_default();
+
+            let status = if data.len() != len + E2E_TRAILER_LEN {
+                state.stats.length_errors += 1;
+                E2eStatus::Error
+            } else if e2e_crc(self.id, &data[..=len]) != data[len + 1] {
+                state.stats.crc_errors += 1;
+                E2eStatus::Error
+            } else {
+                let counter = data[len] % E2E_COUNTER_MODULO;
+                let delta = state
+                    .last_counter
+                    .map(|last| (counter + E2E_COUNTER_MODULO - last) % E2E_COUNTER_MODULO);
+                state.last_counter = Some(counter);
+                match delta {
+                    Some(0) => {
+                        state.stats.repeated += 1;
+                        E2eStatus::Repeated
+                    }
+                    Some(d) if d > E2E_MAX_DELTA_COUNTER => {
+                        state.stats.lost += d as u32 - 1;
+                        E2eStatus::WrongSequence
+                    }
+                    d => {
+                        state.stats.lost += d.map_or(0, |d| d as u32 - 1);
+                        state.stats.ok += 1;
+                        E2eStatus::Ok
+                    }
+                }
+            };
+
+            match status {
+                E2eStatus::Ok => {
+                    state.valid = true;
+                    state.fresh = true;
+                }
+                E2eStatus::WrongSequence | E2eStatus::Error => state.valid = false,
+                E2eStatus::Repeated => {}
+            }
+            if matches!(status, E2eStatus::Ok | E2eStatus::WrongSequence) {
+                let mut bytes = [0; 4];
+                bytes[..len].copy_from_slice(&data[..len]);
+                self.value.borrow(cs).set(u32::from_le_bytes(bytes));
+                self.received.borrow(cs).set(true);
+            }
+            cell.set(Some(state));
+            status
+        })
+    }
 
-        self.set_from_bytes(&data);
+    /// Whether the last received value can be trusted: it came in a frame that passed the
+    /// checks, and no [`GaugeData::check_e2e_timeout`] found the gauge silent since. Always
+    /// `true` with E2E disabled.
+    pub fn is_valid(&self) -> bool {
+        !e2e_enabled()
+            || critical_section::with(|cs| self.e2e.borrow(cs).get().unwrap_or_default().valid)
+    }
+
+    /// Mark the gauge invalid if no frame passed the checks since the last call, so a sender
+    /// that went silent is not trusted forever. Call it once per receive timeout, see
+    /// [`check_e2e_timeouts`]. Returns whether the gauge is still valid.
+    pub fn check_e2e_timeout(&self) -> bool {
+        critical_section::with(|cs| {
+            let cell = self.e2e.borrow(cs);
+            let mut state = cell.get().unwrap_or_default();
+            if state.valid && !state.fresh {
+                state.valid = false;
+                state.stats.timeouts += 1;
+            }
+            state.fresh = false;
+            cell.set(Some(state));
+            state.valid
+        })
+    }
+
+    /// Receive error counters for this gauge.
+    pub fn e2e_stats(&self) -> E2eStats {
+        critical_section::with(|cs| self.e2e.borrow(cs).get().unwrap_or_default().stats)
+    }
+
+    /// Forget the counter history and statistics, e.g. after the ECU rebooted.
+    pub fn reset_e2e(&self) {
+        critical_section::with(|cs| self.e2e.borrow(cs).set(None))
     }
     pub fn prim_id(&self) -> u8 {
-        let result = self.id.try_into().unwrap();
-        result
+        self.id.try_into().unwrap()
     }
 }
 
@@ -90,7 +225,8 @@ macro_rules! gauges {
 }
 
 #[repr(u16)]
-#[derive(Debug, FromRepr)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr, EnumString)]
+#[strum(ascii_case_insensitive)]
 pub enum Gauge {
     StaTime = 0x20,
     StaStatus1 = 0x21,
@@ -169,6 +305,28 @@ pub enum Gauge {
 }
 
 impl Gauge {
+    /// Look up the gauge carried by a bus id in the configured id space.
+    pub fn from_id(id: Id) -> Option<Gauge> {
+        Gauge::from_repr(crate::id_config().offset(id)?)
+    }
+
+    /// Every gauge, in id order.
+    pub fn all() -> impl Iterator<Item = Gauge> {
+        (0..=u8::MAX as u16).filter_map(Gauge::from_repr)
+    }
+
+    /// Look up a gauge by its variant name (case-insensitive) or its id, e.g. `RPM` or `0x2D`.
+    pub fn from_name(name: &str) -> Option<Gauge> {
+        if let Ok(gauge) = name.parse() {
+            return Some(gauge);
+        }
+        let id = match name.strip_prefix("0x").or_else(|| name.strip_prefix("0X")) {
+            Some(hex) => u16::from_str_radix(hex, 16).ok()?,
+            None => name.parse().ok()?,
+        };
+        Gauge::from_repr(id)
+    }
+
     fn raw_gauge(&self) -> &'static GaugeData {
         match self {
             Gauge::StaTime => &STA_TIME,
@@ -333,3 +491,164 @@ gauges! {
     SD_STA, 0x69, DataWidth::U8,
     MASTERALIVE, 0x70, DataWidth::U8
 }
+
+/// [`GaugeData::check_e2e_timeout`] for every gauge. Returns how many are valid.
+pub fn check_e2e_timeouts() -> usize {
+    Gauge::all().filter(|gauge| gauge.check_e2e_timeout()).count()
+}
+
+/// The receive error counters of all gauges added up.
+pub fn e2e_totals() -> E2eStats {
+    let mut totals = E2eStats::default();
+    for gauge in Gauge::all() {
+        totals += gauge.e2e_stats();
+    }
+    totals
+}
+
+/// The gauges are global, tests writing them take turns.
+#[cfg(test)]
+pub(crate) fn lock_gauges() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_0_2::can::StandardId;
+
+    /// Turns E2E on while it lives, with the gauges locked.
+    struct E2eOn {
+        _gauges: std::sync::MutexGuard<'static, ()>,
+    }
+
+    impl E2eOn {
+        fn new() -> Self {
+            let _gauges = lock_gauges();
+            set_e2e(true);
+            E2eOn { _gauges }
+        }
+    }
+
+    impl Drop for E2eOn {
+        fn drop(&mut self) {
+            set_e2e(false);
+        }
+    }
+
+    /// An E2E protected frame for a one byte gauge.
+    fn frame(gauge: &GaugeData, value: u8, counter: u8) -> CanFrame {
+        let crc = e2e_crc(gauge.id, &[value, counter]);
+        let id = StandardId::new(gauge.id).unwrap();
+        CanFrame::new(id, &[value, counter, crc]).unwrap()
+    }
+
+    #[test]
+    fn gauges_know_whether_a_value_arrived() {
+        let gauge = GaugeData::new(0x7F, DataWidth::U8, 0);
+        assert!(!gauge.received());
+        gauge.set(5);
+        assert!(gauge.received());
+
+        let _e2e = E2eOn::new();
+        let gauge = GaugeData::new(0x7F, DataWidth::U8, 0);
+        let crc = e2e_crc(gauge.id, &[40, 3]);
+        let id = StandardId::new(gauge.id).unwrap();
+        gauge.receive(&CanFrame::new(id, &[40, 3, !crc]).unwrap());
+        assert!(!gauge.received(), "a frame failing the CRC carries no value");
+        gauge.receive(&frame(&gauge, 40, 3));
+        assert!(gauge.received());
+    }
+
+    #[test]
+    fn gauges_are_valid_once_a_good_frame_arrives() {
+        let _e2e = E2eOn::new();
+        OIL_PRES.reset_e2e();
+        assert!(!OIL_PRES.is_valid());
+        assert_eq!(OIL_PRES.receive(&frame(&OIL_PRES, 40, 3)), E2eStatus::Ok);
+        assert!(OIL_PRES.is_valid());
+        assert_eq!(OIL_PRES.get(), 40);
+    }
+
+    #[test]
+    fn counter_sequence_is_checked() {
+        let _e2e = E2eOn::new();
+        OIL_PRES.reset_e2e();
+        assert_eq!(OIL_PRES.receive(&frame(&OIL_PRES, 40, 14)), E2eStatus::Ok);
+        assert_eq!(OIL_PRES.receive(&frame(&OIL_PRES, 40, 14)), E2eStatus::Repeated);
+        assert!(OIL_PRES.is_valid());
+        // Wrapping from 15 to 0 is in sequence, one frame lost is tolerated.
+        assert_eq!(OIL_PRES.receive(&frame(&OIL_PRES, 41, 15)), E2eStatus::Ok);
+        assert_eq!(OIL_PRES.receive(&frame(&OIL_PRES, 42, 1)), E2eStatus::Ok);
+        // Two lost frames are not: the value is taken but not trusted.
+        assert_eq!(OIL_PRES.receive(&frame(&OIL_PRES, 43, 4)), E2eStatus::WrongSequence);
+        assert!(!OIL_PRES.is_valid());
+        assert_eq!(OIL_PRES.get(), 43);
+        assert_eq!(OIL_PRES.receive(&frame(&OIL_PRES, 44, 5)), E2eStatus::Ok);
+        assert!(OIL_PRES.is_valid());
+
+        let stats = OIL_PRES.e2e_stats();
+        assert_eq!((stats.ok, stats.repeated, stats.lost), (4, 1, 3));
+    }
+
+    #[test]
+    fn corrupt_frames_are_discarded() {
+        let _e2e = E2eOn::new();
+        OIL_PRES.reset_e2e();
+        assert_eq!(OIL_PRES.receive(&frame(&OIL_PRES, 40, 0)), E2eStatus::Ok);
+
+        let mut data = frame(&OIL_PRES, 99, 1).data().to_vec();
+        data[0] ^= 0x01;
+        let id = StandardId::new(OIL_PRES.id).unwrap();
+        assert_eq!(OIL_PRES.receive(&CanFrame::new(id, &data).unwrap()), E2eStatus::Error);
+        assert_eq!(OIL_PRES.receive(&CanFrame::new(id, &data[..2]).unwrap()), E2eStatus::Error);
+        // The CRC covers the gauge id, another gauge's frame does not pass.
+        assert_eq!(OIL_PRES.receive(&frame(&FUEL_PRES, 99, 1)), E2eStatus::Error);
+        assert!(!OIL_PRES.is_valid());
+        assert_eq!(OIL_PRES.get(), 40);
+
+        let stats = OIL_PRES.e2e_stats();
+        assert_eq!((stats.crc_errors, stats.length_errors), (2, 1));
+    }
+
+    #[test]
+    fn silent_gauges_time_out() {
+        let _e2e = E2eOn::new();
+        OIL_PRES.reset_e2e();
+        assert_eq!(OIL_PRES.receive(&frame(&OIL_PRES, 40, 0)), E2eStatus::Ok);
+        // The frame came within this timeout period, the next period has none.
+        assert!(OIL_PRES.check_e2e_timeout());
+        assert!(!OIL_PRES.check_e2e_timeout());
+        assert!(!OIL_PRES.is_valid());
+        assert_eq!(OIL_PRES.e2e_stats().timeouts, 1);
+        assert!(!OIL_PRES.check_e2e_timeout());
+        assert_eq!(OIL_PRES.e2e_stats().timeouts, 1);
+
+        assert_eq!(OIL_PRES.receive(&frame(&OIL_PRES, 41, 1)), E2eStatus::Ok);
+        assert!(OIL_PRES.is_valid());
+    }
+
+    #[test]
+    fn sent_frames_pass_the_receive_checks() {
+        let _e2e = E2eOn::new();
+        VSS.set(0x1234);
+        VSS.reset_e2e();
+        for _ in 0..E2E_COUNTER_MODULO + 2 {
+            let frame = VSS.to_frame().unwrap();
+            assert_eq!(frame.dlc(), 2 + E2E_TRAILER_LEN);
+            assert_eq!(VSS.receive(&frame), E2eStatus::Ok);
+        }
+        assert_eq!(VSS.get(), 0x1234);
+    }
+
+    #[test]
+    fn everything_is_valid_without_e2e() {
+        let _gauges = lock_gauges();
+        OIL_PRES.reset_e2e();
+        assert!(OIL_PRES.is_valid());
+        let id = StandardId::new(OIL_PRES.id).unwrap();
+        assert_eq!(OIL_PRES.receive(&CanFrame::new(id, &[7]).unwrap()), E2eStatus::Ok);
+        assert_eq!(OIL_PRES.get(), 7);
+    }
+}
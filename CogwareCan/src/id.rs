@@ -0,0 +1,67 @@
+use core::cell::Cell;
+use critical_section::Mutex;
+use embedded_hal_0_2::can::{ExtendedId, Id, StandardId};
+
+/// Protocol id of the server's subscription acknowledgement, relative to the base id.
+pub const MASTER_ACK_OFFSET: u16 = 0x00;
+
+/// Protocol id of a client's subscription request, relative to the base id.
+pub const CLIENT_REQUEST_OFFSET: u16 = 0x15;
+
+/// Where a protocol's ids live on the bus.
+///
+/// Protocol ids are small offsets (gauge ids, ack, request) added to `base`. With `extended`
+/// set, the resulting ids are 29-bit so the protocol can share a bus with J1939-style networks
+/// that already use most of the 11-bit space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdConfig {
+    pub base: u32,
+    pub extended: bool,
+}
+
+impl Default for IdConfig {
+    fn default() -> Self {
+        IdConfig::STANDARD
+    }
+}
+
+impl IdConfig {
+    /// Plain 11-bit ids starting at zero, the original Cogware layout.
+    pub const STANDARD: IdConfig = IdConfig::new(0, false);
+
+    pub const fn new(base: u32, extended: bool) -> Self {
+        IdConfig { base, extended }
+    }
+
+    /// The bus id for a protocol `offset`, `None` if it doesn't fit the id width.
+    pub fn id(&self, offset: u16) -> Option<Id> {
+        let raw = self.base.checked_add(offset as u32)?;
+        if self.extended {
+            Some(Id::Extended(ExtendedId::new(raw)?))
+        } else {
+            Some(Id::Standard(StandardId::new(raw.try_into().ok()?)?))
+        }
+    }
+
+    /// The protocol offset of a bus id, `None` if the id isn't in this id space.
+    pub fn offset(&self, id: Id) -> Option<u16> {
+        let raw = match (id, self.extended) {
+            (Id::Standard(id), false) => id.as_raw() as u32,
+            (Id::Extended(id), true) => id.as_raw(),
+            _ => return None,
+        };
+        raw.checked_sub(self.base)?.try_into().ok()
+    }
+}
+
+static ID_CONFIG: Mutex<Cell<IdConfig>> = Mutex::new(Cell::new(IdConfig::STANDARD));
+
+/// The id space used by the Cogware protocol, for both client and server.
+pub fn id_config() -> IdConfig {
+    critical_section::with(|cs| ID_CONFIG.borrow(cs).get())
+}
+
+/// Move the Cogware protocol to a different id space.
+pub fn set_id_config(config: IdConfig) {
+    critical_section::with(|cs| ID_CONFIG.borrow(cs).set(config))
+}
@@ -1,10 +1,99 @@
 #![no_std]
-#![no_main]
+#![cfg_attr(not(test), no_main)]
 
+#[cfg(test)]
+extern crate std;
+
+mod e2e;
+mod gateway;
 mod gauge;
+mod id;
+mod megasquirt;
+mod speeduino;
+pub use e2e::*;
+pub use gateway::*;
 pub use gauge::*;
+pub use id::*;
+pub use megasquirt::*;
+pub use speeduino::*;
+use embedded_hal_0_2::can::Frame;
 use mcp2515::frame::CanFrame;
 
+/// The CAN dialect spoken by the ECU on the bus.
+///
+/// Every protocol decodes into the same gauge store, so the dashboard stays ECU-agnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EcuProtocol {
+    /// Cogware subscription protocol, one frame per gauge id in the id space set with
+    /// [`set_id_config`].
+    Cogware,
+    /// MegaSquirt MS2/MS3 simplified dash broadcast starting at `base`.
+    MegaSquirt { base: IdConfig },
+    /// Speeduino native realtime broadcast starting at `base`.
+    Speeduino { base: IdConfig },
+}
+
+impl EcuProtocol {
+    /// Look up a protocol by the name used in `CONFIG.TXT`, with its ids where the ECU puts
+    /// them by default.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim() {
+            n if n.eq_ignore_ascii_case("cogware") => Some(EcuProtocol::Cogware),
+            n if n.eq_ignore_ascii_case("megasquirt") => Some(EcuProtocol::MegaSquirt {
+                base: IdConfig::new(MS_DASH_BASE_ID as u32, false),
+            }),
+            n if n.eq_ignore_ascii_case("speeduino") => Some(EcuProtocol::Speeduino {
+                base: IdConfig::new(SPEEDUINO_BROADCAST_BASE_ID as u32, false),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Where the protocol's ids are on the bus.
+    pub fn ids(&self) -> IdConfig {
+        match *self {
+            EcuProtocol::Cogware => id_config(),
+            EcuProtocol::MegaSquirt { base } | EcuProtocol::Speeduino { base } => base,
+        }
+    }
+
+    /// Move the protocol's ids, e.g. to a MegaSquirt broadcast base changed in TunerStudio or to
+    /// 29-bit ids. The Cogware protocol's id space is global, see [`set_id_config`].
+    pub fn set_ids(self, ids: IdConfig) -> Self {
+        match self {
+            EcuProtocol::Cogware => {
+                set_id_config(ids);
+                EcuProtocol::Cogware
+            }
+            EcuProtocol::MegaSquirt { .. } => EcuProtocol::MegaSquirt { base: ids },
+            EcuProtocol::Speeduino { .. } => EcuProtocol::Speeduino { base: ids },
+        }
+    }
+
+    /// Decode a received frame into the gauge store.
+    ///
+    /// Returns `true` if the frame belonged to this protocol and was consumed.
+    pub fn decode(&self, frame: &CanFrame) -> bool {
+        match *self {
+            EcuProtocol::Cogware => match Gauge::from_id(frame.id()) {
+                Some(gauge) => {
+                    gauge.receive(frame);
+                    true
+                }
+                None => false,
+            },
+            EcuProtocol::MegaSquirt { base } => match base.offset(frame.id()) {
+                Some(group) if group < MS_DASH_GROUPS => megasquirt_dash_writer(group, frame),
+                _ => false,
+            },
+            EcuProtocol::Speeduino { base } => match base.offset(frame.id()) {
+                Some(index) => speeduino_broadcast_writer(index, frame),
+                None => false,
+            },
+        }
+    }
+}
+
 pub fn cli_wri(frame: CanFrame, id: u16) {
     Gauge::from_repr(id)
         .expect("bad ID fucking idiot")
@@ -15,118 +104,52 @@ pub fn server_framegen(id: u16) -> Option<CanFrame> {
     Gauge::from_repr(id)?.to_frame()
 }
 
-pub fn speeduino_n_writer(buf: [u8; 126]) {
-    STA_TIME.set(buf[3] as _);
-    STA_STATUS1.set(buf[4] as _);
-    STA_ENG.set(buf[5] as _);
-    DWELL.set(buf[6] as _);
-    MAP.set_from_bytes(&buf[7..=8]);
-    IAT.set(buf[9] as _);
-    CLNT.set(buf[10] as _);
-    BAT_CORRECT.set(buf[11] as _);
-    BAT_VOL.set(buf[12] as _);
-    AFR_PRI.set(buf[13] as _);
-    EGO_CORRECT.set(buf[14] as _);
-    IAT_CORRECT.set(buf[15] as _);
-    WUE_CORRECT.set(buf[16] as _);
-    RPM.set_from_bytes(&buf[17..=18]);
-    ACCEL_ENRICH.set(buf[19] as _);
-    GAMME_E.set(buf[20] as _);
-    VE.set(buf[21] as _);
-    AFR_TARGET.set(buf[22] as _);
-    PULSE_WIDTH1.set_from_bytes(&buf[23..=24]);
-    TPS_DOT.set(buf[25] as _);
-    CUR_SPARK_ADVANCE.set(buf[26] as _);
-    TPS.set(buf[27] as _);
-    LOOP_PS.set_from_bytes(&buf[28..=29]);
-    FREE_MEM.set_from_bytes(&buf[30..=31]);
-    BOOST_TARGET.set(buf[32] as _);
-    BOOST_PWM.set(buf[33] as _);
-    STA_SPARK.set(buf[34] as _);
-    RPM_DOT.set_from_bytes(&buf[35..=36]);
-    ETHANOL_PERCENT.set(buf[37] as _);
-    FLEX_CORRECT.set(buf[38] as _);
-    FLEX_IGN_CORRECT.set(buf[39] as _);
-    IDLE_LOAD.set(buf[40] as _);
-    TEST_OUTPUTS.set(buf[41] as _);
-    AFR_SEC.set(buf[42] as _);
-    BARO.set(buf[43] as _);
-    TPS_ADC.set(buf[76] as _);
-    NEXT_ERROR.set(buf[77] as _);
-    STA_LAUNCH_CORRECT.set(buf[78] as _);
-    PULSE_WIDTH2.set_from_bytes(&buf[79..=80]);
-    PULSE_WIDTH3.set_from_bytes(&buf[81..=82]);
-    PULSE_WIDTH4.set_from_bytes(&buf[83..=84]);
-    STA_STATUS2.set(buf[85] as _);
-    ENG_PROTECT_STA.set(buf[86] as _);
-    FUEL_LOAD.set_from_bytes(&buf[87..=88]);
-    IGN_LOAD.set_from_bytes(&buf[89..=90]);
-    INJ_ANGLE.set_from_bytes(&buf[91..=92]);
-    IDLE_DUTY.set(buf[93] as _);
-    CL_IDLE_TARGET.set(buf[94] as _);
-    MAP_DOT.set(buf[95] as _);
-    VVT_ANGLE.set(buf[96] as _);
-    VVT_TARGET_ANGLE.set(buf[97] as _);
-    VVT_DUTY.set(buf[98] as _);
-    FLEX_BOOST_CORRECT.set_from_bytes(&buf[99..=100]);
-    BARO_CORRECTION.set(buf[101] as _);
-    ASE.set(buf[102] as _);
-    VSS.set_from_bytes(&buf[103..=104]);
-    GEAR.set(buf[105] as _);
-    FUEL_PRES.set(buf[106] as _);
-    OIL_PRES.set(buf[107] as _);
-    WMI_PW.set(buf[108] as _);
-    STA_STATUS4.set(buf[109] as _);
-    VVT_ANGLE2.set(buf[110] as _);
-    VVT_TARGET_ANGLE2.set(buf[111] as _);
-    VVT_DUTY2.set(buf[112] as _);
-    STATUS_OUT_STA.set(buf[113] as _);
-    FLEX_FUEL_TEMP.set(buf[114] as _);
-    FUEL_TEMP_CORRECT.set(buf[115] as _);
-    VE1.set(buf[116] as _);
-    VE2.set(buf[117] as _);
-    ADVANCE1.set(buf[118] as _);
-    ADVANCE2.set(buf[119] as _);
-    NITRO_STA.set(buf[120] as _);
-    SD_STA.set(buf[121] as _);
+/// Frame a client sends to subscribe to `gauge_id`.
+pub fn client_request_frame(gauge_id: u8) -> Option<CanFrame> {
+    CanFrame::new(id_config().id(CLIENT_REQUEST_OFFSET)?, &[gauge_id])
 }
 
-#[allow(non_snake_case)]
-pub fn speeduino_A_writer(buf: [u8; 126]) {
-    STA_TIME.set(buf[2] as _);
-    STA_STATUS1.set(buf[3] as _);
-    STA_ENG.set(buf[4] as _);
-    DWELL.set(buf[5] as _);
-    MAP.set_from_bytes(&buf[6..=7]);
-    IAT.set(buf[8] as _);
-    CLNT.set(buf[9] as _);
-    BAT_CORRECT.set(buf[10] as _);
-    BAT_VOL.set(buf[11] as _);
-    AFR_PRI.set(buf[12] as _);
-    EGO_CORRECT.set(buf[13] as _);
-    IAT_CORRECT.set(buf[14] as _);
-    WUE_CORRECT.set(buf[15] as _);
-    RPM.set_from_bytes(&buf[16..=17]);
-    ACCEL_ENRICH.set(buf[18] as _);
-    GAMME_E.set(buf[19] as _);
-    VE.set(buf[20] as _);
-    AFR_TARGET.set(buf[21] as _);
-    PULSE_WIDTH1.set_from_bytes(&buf[22..=23]);
-    TPS_DOT.set(buf[24] as _);
-    CUR_SPARK_ADVANCE.set(buf[25] as _);
-    TPS.set(buf[26] as _);
-    LOOP_PS.set_from_bytes(&buf[27..=28]);
-    FREE_MEM.set_from_bytes(&buf[29..=30]);
-    BOOST_TARGET.set(buf[31] as _);
-    BOOST_PWM.set(buf[32] as _);
-    STA_SPARK.set(buf[33] as _);
-    RPM_DOT.set_from_bytes(&buf[34..=35]);
-    ETHANOL_PERCENT.set(buf[36] as _);
-    FLEX_CORRECT.set(buf[37] as _);
-    FLEX_IGN_CORRECT.set(buf[38] as _);
-    IDLE_LOAD.set(buf[39] as _);
-    TEST_OUTPUTS.set(buf[40] as _);
-    AFR_SEC.set(buf[41] as _);
-    BARO.set(buf[42] as _);
-    TPS_ADC.set(buf[75] as _);
+/// Frame the server answers a subscription for `gauge_id` with.
+pub fn master_ack_frame(gauge_id: u8) -> Option<CanFrame> {
+    CanFrame::new(id_config().id(MASTER_ACK_OFFSET)?, &[gauge_id])
+}
+
+/// The gauge id a client asked for, if `frame` is a subscription request.
+pub fn parse_client_request(frame: &CanFrame) -> Option<u8> {
+    let offset = id_config().offset(frame.id())?;
+    (offset == CLIENT_REQUEST_OFFSET).then(|| frame.data().first().copied())?
+}
+
+/// The gauge id the server acknowledged, if `frame` is a subscription ack.
+pub fn parse_master_ack(frame: &CanFrame) -> Option<u8> {
+    let offset = id_config().offset(frame.id())?;
+    (offset == MASTER_ACK_OFFSET).then(|| frame.data().first().copied())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_0_2::can::{ExtendedId, Id, StandardId};
+
+    #[test]
+    fn broadcast_protocols_follow_the_configured_ids() {
+        let _gauges = lock_gauges();
+        let protocol = EcuProtocol::from_name("MegaSquirt").unwrap();
+        assert_eq!(protocol.ids(), IdConfig::new(MS_DASH_BASE_ID as u32, false));
+
+        let protocol = protocol.set_ids(IdConfig::new(0x18F0_0000, true));
+        let id = Id::Extended(ExtendedId::new(0x18F0_0000).unwrap());
+        let data = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0F, 0xA0];
+        assert!(protocol.decode(&CanFrame::new(id, &data).unwrap()));
+        assert_eq!(RPM.get(), 4000);
+
+        let protocol = EcuProtocol::from_name("speeduino").unwrap();
+        assert_eq!(protocol.ids().base, SPEEDUINO_BROADCAST_BASE_ID as u32);
+        let protocol = protocol.set_ids(IdConfig::new(0x1000, true));
+        let id = Id::Extended(ExtendedId::new(0x1001).unwrap());
+        assert!(protocol.decode(&CanFrame::new(id, &[0; 8]).unwrap()));
+        // The 11-bit id of the default layout is not in this id space.
+        let id = Id::Standard(StandardId::new(0x601).unwrap());
+        assert!(!protocol.decode(&CanFrame::new(id, &[0; 8]).unwrap()));
+    }
 }
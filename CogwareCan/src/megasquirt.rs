@@ -0,0 +1,207 @@
+use embedded_hal_0_2::can::Frame;
+use mcp2515::frame::CanFrame;
+
+use crate::gauge::*;
+
+/// Default base id of the MS2/MS3 "simplified dash broadcast" (1520 decimal).
+pub const MS_DASH_BASE_ID: u16 = 0x5F0;
+
+/// Number of broadcast groups decoded, counted from the base id.
+pub const MS_DASH_GROUPS: u16 = 10;
+
+/// All MegaSquirt dash broadcast values are big endian.
+fn be_u16(data: &[u8], idx: usize) -> u16 {
+    u16::from_be_bytes([data[idx], data[idx + 1]])
+}
+
+fn be_i16(data: &[u8], idx: usize) -> i16 {
+    be_u16(data, idx) as i16
+}
+
+/// Converts a value in tenths to whole units, clamping negatives to zero for the unsigned gauges.
+fn tenths(value: i16) -> u32 {
+    (value.max(0) / 10) as u32
+}
+
+/// MegaSquirt reports temperatures in 0.1 °F, the gauge store keeps Speeduino's °C + 40.
+fn temp_from_deci_fahrenheit(value: i16) -> u32 {
+    let celsius = (value as i32 - 320) * 5 / 90;
+    (celsius + 40).clamp(0, u8::MAX as i32) as u32
+}
+
+/// Decodes one MS2/MS3 dash broadcast frame into the gauge store.
+///
+/// `group` is the frame id minus the broadcast base id. Values are converted to the units the
+/// Speeduino writers use so the dashboard doesn't need to know which ECU it is talking to.
+/// Returns `false` for groups that aren't decoded or frames that are too short.
+pub fn megasquirt_dash_writer(group: u16, frame: &CanFrame) -> bool {
+    let data = frame.data();
+    if data.len() < 8 {
+        return false;
+    }
+
+    match group {
+        0 => {
+            STA_TIME.set((be_u16(data, 0) & 0xFF) as _);
+            // 0.001 ms is one microsecond, same as Speeduino.
+            PULSE_WIDTH1.set(be_u16(data, 2) as _);
+            PULSE_WIDTH2.set(be_u16(data, 4) as _);
+            RPM.set(be_u16(data, 6) as _);
+        }
+        1 => {
+            CUR_SPARK_ADVANCE.set((be_i16(data, 0) / 10) as i8 as u8 as _);
+            // MS engine status bits line up with Speeduino's BIT_ENGINE_* layout.
+            STA_ENG.set(data[3] as _);
+            AFR_TARGET.set(data[4] as _);
+        }
+        2 => {
+            BARO.set(tenths(be_i16(data, 0)));
+            MAP.set(tenths(be_i16(data, 2)));
+            IAT.set(temp_from_deci_fahrenheit(be_i16(data, 4)));
+            CLNT.set(temp_from_deci_fahrenheit(be_i16(data, 6)));
+        }
+        3 => {
+            TPS.set(tenths(be_i16(data, 0)));
+            BAT_VOL.set(be_i16(data, 2).max(0) as _);
+            AFR_PRI.set(be_i16(data, 4).max(0) as _);
+            AFR_SEC.set(be_i16(data, 6).max(0) as _);
+        }
+        4 => {
+            EGO_CORRECT.set(tenths(be_i16(data, 2)));
+            IAT_CORRECT.set(tenths(be_i16(data, 6)));
+        }
+        5 => {
+            WUE_CORRECT.set(tenths(be_i16(data, 0)));
+            ACCEL_ENRICH.set(tenths(be_i16(data, 2)));
+            BARO_CORRECTION.set(tenths(be_i16(data, 6)));
+        }
+        6 => {
+            VE.set(tenths(be_i16(data, 2)));
+            VE1.set(tenths(be_i16(data, 2)));
+            VE2.set(tenths(be_i16(data, 4)));
+        }
+        7 => {
+            TPS_DOT.set(tenths(be_i16(data, 2)));
+            MAP_DOT.set(tenths(be_i16(data, 4)));
+            // MS sends 10 rpm/s steps, Speeduino a plain signed rpm/s.
+            RPM_DOT.set_from_bytes(&(be_i16(data, 6).saturating_mul(10)).to_le_bytes());
+        }
+        9 => {
+            DWELL.set(be_i16(data, 4).max(0) as _);
+        }
+        _ => return false,
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EcuProtocol, IdConfig};
+    use embedded_hal_0_2::can::{Id, StandardId};
+
+    fn frame(id: u16, data: &[u8]) -> CanFrame {
+        CanFrame::new(Id::Standard(StandardId::new(id).unwrap()), data).unwrap()
+    }
+
+    #[test]
+    fn group_0_timing_and_rpm() {
+        let _gauges = lock_gauges();
+        // 42 s since power-on, 3.000 ms and 2.500 ms pulse widths, 3200 rpm.
+        let data = [0x00, 0x2A, 0x0B, 0xB8, 0x09, 0xC4, 0x0C, 0x80];
+        assert!(megasquirt_dash_writer(0, &frame(MS_DASH_BASE_ID, &data)));
+        assert_eq!(STA_TIME.get(), 42);
+        assert_eq!(PULSE_WIDTH1.get(), 3000);
+        assert_eq!(PULSE_WIDTH2.get(), 2500);
+        assert_eq!(RPM.get(), 3200);
+    }
+
+    #[test]
+    fn group_1_advance_status_and_target() {
+        let _gauges = lock_gauges();
+        // 25.5° advance, squirt 0x03, engine status 0x05, AFR target 14.7.
+        let data = [0x00, 0xFF, 0x03, 0x05, 0x93, 0x93, 0x00, 0x00];
+        assert!(megasquirt_dash_writer(1, &frame(MS_DASH_BASE_ID + 1, &data)));
+        assert_eq!(CUR_SPARK_ADVANCE.get(), 25);
+        assert_eq!(STA_ENG.get(), 0x05);
+        assert_eq!(AFR_TARGET.get(), 147);
+
+        // -5.0° is stored as the signed byte Speeduino sends.
+        let data = [0xFF, 0xCE, 0x03, 0x05, 0x93, 0x93, 0x00, 0x00];
+        assert!(megasquirt_dash_writer(1, &frame(MS_DASH_BASE_ID + 1, &data)));
+        assert_eq!(CUR_SPARK_ADVANCE.get() as u8 as i8, -5);
+    }
+
+    #[test]
+    fn group_2_pressures_and_temperatures() {
+        let _gauges = lock_gauges();
+        // Baro 101.3 kPa, MAP 150.0 kPa, MAT 86.0 °F, CLT 212.0 °F.
+        let data = [0x03, 0xF5, 0x05, 0xDC, 0x03, 0x5C, 0x08, 0x48];
+        assert!(megasquirt_dash_writer(2, &frame(MS_DASH_BASE_ID + 2, &data)));
+        assert_eq!(BARO.get(), 101);
+        assert_eq!(MAP.get(), 150);
+        // °C + 40: 30 °C and 100 °C.
+        assert_eq!(IAT.get(), 70);
+        assert_eq!(CLNT.get(), 140);
+    }
+
+    #[test]
+    fn temperatures_are_clamped_to_the_gauge_range() {
+        assert_eq!(temp_from_deci_fahrenheit(320), 40);
+        assert_eq!(temp_from_deci_fahrenheit(-400), 0);
+        assert_eq!(temp_from_deci_fahrenheit(-1000), 0);
+        assert_eq!(temp_from_deci_fahrenheit(i16::MAX), 255);
+    }
+
+    #[test]
+    fn group_3_tenths_are_scaled_or_kept() {
+        let _gauges = lock_gauges();
+        // TPS 45.5 %, 13.8 V, AFR 14.7 and 12.9.
+        let data = [0x01, 0xC7, 0x00, 0x8A, 0x00, 0x93, 0x00, 0x81];
+        assert!(megasquirt_dash_writer(3, &frame(MS_DASH_BASE_ID + 3, &data)));
+        assert_eq!(TPS.get(), 45);
+        // Speeduino keeps the battery voltage and AFRs in tenths too.
+        assert_eq!(BAT_VOL.get(), 138);
+        assert_eq!(AFR_PRI.get(), 147);
+        assert_eq!(AFR_SEC.get(), 129);
+
+        // Negative tenths don't wrap around in the unsigned gauges.
+        let data = [0xFF, 0xF6, 0x00, 0x8A, 0x00, 0x93, 0x00, 0x81];
+        assert!(megasquirt_dash_writer(3, &frame(MS_DASH_BASE_ID + 3, &data)));
+        assert_eq!(TPS.get(), 0);
+    }
+
+    #[test]
+    fn group_7_rpm_rate_in_tens() {
+        let _gauges = lock_gauges();
+        // -150 steps of 10 rpm/s.
+        let data = [0x00, 0x00, 0x00, 0x64, 0x00, 0x32, 0xFF, 0x6A];
+        assert!(megasquirt_dash_writer(7, &frame(MS_DASH_BASE_ID + 7, &data)));
+        assert_eq!(TPS_DOT.get(), 10);
+        assert_eq!(MAP_DOT.get(), 5);
+        assert_eq!(RPM_DOT.get() as u16 as i16, -1500);
+    }
+
+    #[test]
+    fn short_frames_and_unknown_groups_are_ignored() {
+        let _gauges = lock_gauges();
+        RPM.set(1234);
+        assert!(!megasquirt_dash_writer(0, &frame(MS_DASH_BASE_ID, &[0x00, 0x2A, 0x0B])));
+        assert!(!megasquirt_dash_writer(8, &frame(MS_DASH_BASE_ID + 8, &[0; 8])));
+        assert_eq!(RPM.get(), 1234);
+    }
+
+    #[test]
+    fn protocol_decodes_relative_to_the_base_id() {
+        let _gauges = lock_gauges();
+        let protocol = EcuProtocol::MegaSquirt {
+            base: IdConfig::new(0x400, false),
+        };
+        let data = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x20];
+        assert!(protocol.decode(&frame(0x400, &data)));
+        assert_eq!(RPM.get(), 800);
+        // The default base is not this install's.
+        assert!(!protocol.decode(&frame(MS_DASH_BASE_ID, &data)));
+        assert!(!protocol.decode(&frame(0x400 + MS_DASH_GROUPS, &data)));
+    }
+}
@@ -0,0 +1,337 @@
+use core::cell::RefCell;
+use critical_section::Mutex;
+use embedded_hal_0_2::can::Frame;
+use mcp2515::frame::CanFrame;
+
+use crate::gauge::*;
+
+/// Length of Speeduino's realtime data block as sent by the `n` command.
+pub const SPEEDUINO_BLOCK_LEN: usize = 119;
+
+/// The legacy `A` command only covers the start of the realtime block.
+pub const SPEEDUINO_A_LEN: usize = 74;
+
+/// Default base id of Speeduino's native realtime CAN broadcast.
+pub const SPEEDUINO_BROADCAST_BASE_ID: u16 = 0x600;
+
+/// Number of 8 byte broadcast frames needed to carry the realtime block.
+pub const SPEEDUINO_BROADCAST_FRAMES: u16 = SPEEDUINO_BLOCK_LEN.div_ceil(8) as u16;
+
+/// Header byte and sub-command of an `n` response on the secondary serial port.
+const N_COMMAND: u8 = b'n';
+const N_SUBCOMMAND: u8 = 0x32;
+const N_HEADER_LEN: usize = 3;
+
+/// Where a gauge lives in Speeduino's realtime data block.
+pub struct SpeeduinoField {
+    pub gauge: Gauge,
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl SpeeduinoField {
+    const fn new(gauge: Gauge, offset: usize, len: usize) -> Self {
+        SpeeduinoField { gauge, offset, len }
+    }
+
+    fn end(&self) -> usize {
+        self.offset + self.len
+    }
+}
+
+/// Offsets into the realtime data block, shared by every Speeduino transport.
+pub static SPEEDUINO_FIELDS: [SpeeduinoField; 73] = [
+    SpeeduinoField::new(Gauge::StaTime, 0, 1),
+    SpeeduinoField::new(Gauge::StaStatus1, 1, 1),
+    SpeeduinoField::new(Gauge::StaEng, 2, 1),
+    SpeeduinoField::new(Gauge::DWELL, 3, 1),
+    SpeeduinoField::new(Gauge::MAP, 4, 2),
+    SpeeduinoField::new(Gauge::IAT, 6, 1),
+    SpeeduinoField::new(Gauge::CLNT, 7, 1),
+    SpeeduinoField::new(Gauge::BatCorrect, 8, 1),
+    SpeeduinoField::new(Gauge::BatVol, 9, 1),
+    SpeeduinoField::new(Gauge::AfrPri, 10, 1),
+    SpeeduinoField::new(Gauge::EgoCorrect, 11, 1),
+    SpeeduinoField::new(Gauge::IatCorrect, 12, 1),
+    SpeeduinoField::new(Gauge::WueCorrect, 13, 1),
+    SpeeduinoField::new(Gauge::RPM, 14, 2),
+    SpeeduinoField::new(Gauge::AccelEnrich, 16, 1),
+    SpeeduinoField::new(Gauge::GammeE, 17, 1),
+    SpeeduinoField::new(Gauge::VE, 18, 1),
+    SpeeduinoField::new(Gauge::AfrTarget, 19, 1),
+    SpeeduinoField::new(Gauge::PulseWidth1, 20, 2),
+    SpeeduinoField::new(Gauge::TpsDot, 22, 1),
+    SpeeduinoField::new(Gauge::CurSparkAdvance, 23, 1),
+    SpeeduinoField::new(Gauge::TPS, 24, 1),
+    SpeeduinoField::new(Gauge::LoopPs, 25, 2),
+    SpeeduinoField::new(Gauge::FreeMem, 27, 2),
+    SpeeduinoField::new(Gauge::BoostTarget, 29, 1),
+    SpeeduinoField::new(Gauge::BoostPwm, 30, 1),
+    SpeeduinoField::new(Gauge::StaSpark, 31, 1),
+    SpeeduinoField::new(Gauge::RpmDot, 32, 2),
+    SpeeduinoField::new(Gauge::EthanolPercent, 34, 1),
+    SpeeduinoField::new(Gauge::FlexCorrect, 35, 1),
+    SpeeduinoField::new(Gauge::FlexIgnCorrect, 36, 1),
+    SpeeduinoField::new(Gauge::IdleLoad, 37, 1),
+    SpeeduinoField::new(Gauge::TestOutputs, 38, 1),
+    SpeeduinoField::new(Gauge::AfrSec, 39, 1),
+    SpeeduinoField::new(Gauge::BARO, 40, 1),
+    SpeeduinoField::new(Gauge::TpsAdc, 73, 1),
+    SpeeduinoField::new(Gauge::NextError, 74, 1),
+    SpeeduinoField::new(Gauge::StaLaunchCorrect, 75, 1),
+    SpeeduinoField::new(Gauge::PulseWidth2, 76, 2),
+    SpeeduinoField::new(Gauge::PulseWidth3, 78, 2),
+    SpeeduinoField::new(Gauge::PulseWidth4, 80, 2),
+    SpeeduinoField::new(Gauge::StaStatus2, 82, 1),
+    SpeeduinoField::new(Gauge::EngProtectSta, 83, 1),
+    SpeeduinoField::new(Gauge::FuelLoad, 84, 2),
+    SpeeduinoField::new(Gauge::IgnLoad, 86, 2),
+    SpeeduinoField::new(Gauge::InjAngle, 88, 2),
+    SpeeduinoField::new(Gauge::IdleDuty, 90, 1),
+    SpeeduinoField::new(Gauge::ClIdleTarget, 91, 1),
+    SpeeduinoField::new(Gauge::MapDot, 92, 1),
+    SpeeduinoField::new(Gauge::VvtAngle, 93, 1),
+    SpeeduinoField::new(Gauge::VvtTargetAngle, 94, 1),
+    SpeeduinoField::new(Gauge::VvtDuty, 95, 1),
+    SpeeduinoField::new(Gauge::FlexBoostCorrect, 96, 2),
+    SpeeduinoField::new(Gauge::BaroCorrection, 98, 1),
+    SpeeduinoField::new(Gauge::ASE, 99, 1),
+    SpeeduinoField::new(Gauge::VSS, 100, 2),
+    SpeeduinoField::new(Gauge::GEAR, 102, 1),
+    SpeeduinoField::new(Gauge::FuelPres, 103, 1),
+    SpeeduinoField::new(Gauge::OilPres, 104, 1),
+    SpeeduinoField::new(Gauge::WmiPw, 105, 1),
+    SpeeduinoField::new(Gauge::StaStatus4, 106, 1),
+    SpeeduinoField::new(Gauge::VvtAngle2, 107, 1),
+    SpeeduinoField::new(Gauge::VvtTargetAngle2, 108, 1),
+    SpeeduinoField::new(Gauge::VvtDuty2, 109, 1),
+    SpeeduinoField::new(Gauge::StatusOutSta, 110, 1),
+    SpeeduinoField::new(Gauge::FlexFuelTemp, 111, 1),
+    SpeeduinoField::new(Gauge::FuelTempCorrect, 112, 1),
+    SpeeduinoField::new(Gauge::VE1, 113, 1),
+    SpeeduinoField::new(Gauge::VE2, 114, 1),
+    SpeeduinoField::new(Gauge::ADVANCE1, 115, 1),
+    SpeeduinoField::new(Gauge::ADVANCE2, 116, 1),
+    SpeeduinoField::new(Gauge::NitroSta, 117, 1),
+    SpeeduinoField::new(Gauge::SdSta, 118, 1),
+];
+
+/// Writes every field whose last byte falls inside `range` of the realtime `block`.
+///
+/// Fields spanning two CAN frames are written once their last byte arrives, with the first
+/// byte taken from what `block` already holds.
+fn write_fields(block: &[u8], range: core::ops::Range<usize>) {
+    SPEEDUINO_FIELDS
+        .iter()
+        .filter(|f| f.end() <= block.len() && range.contains(&(f.end() - 1)))
+        .for_each(|f| f.gauge.set_from_bytes(&block[f.offset..f.end()]));
+}
+
+pub fn speeduino_n_writer(buf: [u8; 126]) {
+    write_fields(&buf[N_HEADER_LEN..], 0..SPEEDUINO_BLOCK_LEN);
+}
+
+#[allow(non_snake_case)]
+pub fn speeduino_A_writer(buf: [u8; 126]) {
+    write_fields(&buf[2..], 0..SPEEDUINO_A_LEN);
+}
+
+/// Realtime block reassembled from the native CAN broadcast.
+static BROADCAST_BLOCK: Mutex<RefCell<[u8; SPEEDUINO_BLOCK_LEN]>> =
+    Mutex::new(RefCell::new([0; SPEEDUINO_BLOCK_LEN]));
+
+/// Decodes one frame of Speeduino's native realtime CAN broadcast.
+///
+/// The realtime block is split into consecutive 8 byte frames, `index` is the frame id minus the
+/// broadcast base id. Returns `false` if the index is out of range.
+pub fn speeduino_broadcast_writer(index: u16, frame: &CanFrame) -> bool {
+    if index >= SPEEDUINO_BROADCAST_FRAMES {
+        return false;
+    }
+
+    let start = index as usize * 8;
+    let end = (start + frame.dlc()).min(SPEEDUINO_BLOCK_LEN);
+    critical_section::with(|cs| {
+        let mut block = BROADCAST_BLOCK.borrow_ref_mut(cs);
+        block[start..end].copy_from_slice(&frame.data()[..end - start]);
+        write_fields(&block[..], start..end);
+    });
+    true
+}
+
+/// Stream decoder for `n` responses arriving on Speeduino's secondary serial port.
+///
+/// Feed it every received byte; bytes outside a response are skipped until the next header.
+pub struct SpeeduinoSerialDecoder {
+    buf: [u8; 126],
+    pos: usize,
+}
+
+impl Default for SpeeduinoSerialDecoder {
+    fn default() -> Self {
+        SpeeduinoSerialDecoder::new()
+    }
+}
+
+impl SpeeduinoSerialDecoder {
+    pub const fn new() -> Self {
+        SpeeduinoSerialDecoder {
+            buf: [0; 126],
+            pos: 0,
+        }
+    }
+
+    /// The request that makes Speeduino send its realtime block.
+    pub const fn request() -> &'static [u8] {
+        &[N_COMMAND]
+    }
+
+    /// Push one received byte.
+    ///
+    /// Returns `true` once a complete response has been written to the gauge store.
+    pub fn push(&mut self, byte: u8) -> bool {
+        let valid = match self.pos {
+            0 => byte == N_COMMAND,
+            1 => byte == N_SUBCOMMAND,
+            // An empty response carries nothing, the header was most likely noise.
+            2 => (1..=SPEEDUINO_BLOCK_LEN).contains(&(byte as usize)),
+            _ => true,
+        };
+        if !valid {
+            // A stray 'n' may be the start of the next response.
+            self.pos = 0;
+            if byte == N_COMMAND {
+                self.pos = 1;
+            }
+            return false;
+        }
+
+        self.buf[self.pos] = byte;
+        self.pos += 1;
+
+        if self.pos > 2 && self.pos == N_HEADER_LEN + self.buf[2] as usize {
+            let len = self.buf[2] as usize;
+            write_fields(&self.buf[N_HEADER_LEN..N_HEADER_LEN + len], 0..len);
+            self.pos = 0;
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_0_2::can::{Id, StandardId};
+
+    /// A realtime block with a few known values: 3200 rpm, 150 kPa MAP, CLT 90 °C + 40.
+    fn block() -> [u8; SPEEDUINO_BLOCK_LEN] {
+        let mut block = [0; SPEEDUINO_BLOCK_LEN];
+        block[4..6].copy_from_slice(&150u16.to_le_bytes());
+        block[7] = 130;
+        block[14..16].copy_from_slice(&3200u16.to_le_bytes());
+        block[24] = 55;
+        block[100..102].copy_from_slice(&88u16.to_le_bytes());
+        block
+    }
+
+    fn response(block: &[u8]) -> std::vec::Vec<u8> {
+        let mut bytes = std::vec![N_COMMAND, N_SUBCOMMAND, block.len() as u8];
+        bytes.extend_from_slice(block);
+        bytes
+    }
+
+    fn push_all(decoder: &mut SpeeduinoSerialDecoder, bytes: &[u8]) -> usize {
+        bytes.iter().filter(|&&b| decoder.push(b)).count()
+    }
+
+    #[test]
+    fn serial_response_is_decoded() {
+        let _gauges = lock_gauges();
+        let mut decoder = SpeeduinoSerialDecoder::new();
+        assert_eq!(push_all(&mut decoder, &response(&block())), 1);
+        assert_eq!(RPM.get(), 3200);
+        assert_eq!(MAP.get(), 150);
+        assert_eq!(CLNT.get(), 130);
+        assert_eq!(TPS.get(), 55);
+        assert_eq!(VSS.get(), 88);
+    }
+
+    #[test]
+    fn serial_gauges_change_only_once_the_response_is_complete() {
+        let _gauges = lock_gauges();
+        RPM.set(0);
+        let bytes = response(&block());
+        let mut decoder = SpeeduinoSerialDecoder::new();
+        assert_eq!(push_all(&mut decoder, &bytes[..40]), 0);
+        assert_eq!(RPM.get(), 0);
+        assert_eq!(push_all(&mut decoder, &bytes[40..]), 1);
+        assert_eq!(RPM.get(), 3200);
+    }
+
+    #[test]
+    fn serial_decoder_resyncs_after_noise() {
+        let _gauges = lock_gauges();
+        RPM.set(0);
+        let mut bytes = std::vec![0x00, 0xFF, N_COMMAND, 0x10, N_COMMAND, N_COMMAND];
+        bytes.extend(response(&block()));
+        let mut decoder = SpeeduinoSerialDecoder::new();
+        assert_eq!(push_all(&mut decoder, &bytes), 1);
+        assert_eq!(RPM.get(), 3200);
+    }
+
+    #[test]
+    fn serial_lengths_are_validated() {
+        let _gauges = lock_gauges();
+        RPM.set(0);
+        let mut decoder = SpeeduinoSerialDecoder::new();
+        // Nothing to decode in an empty response, nor room for a longer one than the block.
+        assert_eq!(push_all(&mut decoder, &[N_COMMAND, N_SUBCOMMAND, 0]), 0);
+        let too_long = [N_COMMAND, N_SUBCOMMAND, SPEEDUINO_BLOCK_LEN as u8 + 1];
+        assert_eq!(push_all(&mut decoder, &too_long), 0);
+        // The decoder is back at the start, a good response follows.
+        assert_eq!(push_all(&mut decoder, &response(&block())), 1);
+        assert_eq!(RPM.get(), 3200);
+    }
+
+    #[test]
+    fn short_serial_response_writes_only_the_fields_it_covers() {
+        let _gauges = lock_gauges();
+        RPM.set(0);
+        VSS.set(0);
+        let mut decoder = SpeeduinoSerialDecoder::new();
+        // The first byte of RPM is the last one sent.
+        assert_eq!(push_all(&mut decoder, &response(&block()[..15])), 1);
+        assert_eq!(MAP.get(), 150);
+        assert_eq!(RPM.get(), 0);
+        assert_eq!(VSS.get(), 0);
+    }
+
+    #[test]
+    fn fields_split_across_writes_wait_for_their_last_byte() {
+        let _gauges = lock_gauges();
+        MAP.set(0);
+        let block = block();
+        // MAP is bytes 4 and 5.
+        write_fields(&block, 0..5);
+        assert_eq!(MAP.get(), 0);
+        write_fields(&block, 5..8);
+        assert_eq!(MAP.get(), 150);
+    }
+
+    #[test]
+    fn broadcast_reassembles_the_block() {
+        let _gauges = lock_gauges();
+        RPM.set(0);
+        let block = block();
+        for (index, chunk) in (0..).zip(block.chunks(8)) {
+            let id = Id::Standard(StandardId::new(SPEEDUINO_BROADCAST_BASE_ID + index).unwrap());
+            assert!(speeduino_broadcast_writer(index, &CanFrame::new(id, chunk).unwrap()));
+        }
+        assert_eq!(RPM.get(), 3200);
+        assert_eq!(VSS.get(), 88);
+        let id = Id::Standard(StandardId::new(SPEEDUINO_BROADCAST_BASE_ID).unwrap());
+        let frame = CanFrame::new(id, &[0; 8]).unwrap();
+        assert!(!speeduino_broadcast_writer(SPEEDUINO_BROADCAST_FRAMES, &frame));
+    }
+}
@@ -0,0 +1,121 @@
+//! Animation for the dashboard widgets: needles easing toward their gauge, the power-on sweep
+//! and flashing alarms.
+//!
+//! Everything is worked out from the time that passed, never from the number of frames, so a
+//! needle moves at the same pace whether the screen manages 20 or 60 frames a second.
+
+use core::f64::consts::PI;
+use core::time::Duration;
+
+use crate::widget::Range;
+
+/// Lit and dark once per period.
+const FLASH_PERIOD: Duration = Duration::from_millis(600);
+/// From full colour to dim and back.
+const PULSE_PERIOD: Duration = Duration::from_millis(1200);
+/// How much of the alarm colour a pulsing alarm keeps at its dimmest.
+const PULSE_MIN_LEVEL: f64 = 64.0;
+
+/// Move `shown` toward `target`, `elapsed` after it was last moved. `damping` is the time in
+/// seconds to cover about two thirds of the way, no damping jumps straight to the target.
+///
+/// Easing is exponential, so two short steps end up where one long step does.
+pub fn ease(shown: f64, target: f64, damping: f64, elapsed: Duration) -> f64 {
+    if damping <= 0.0 || !shown.is_finite() || !target.is_finite() {
+        return target;
+    }
+    let left = libm::exp(-elapsed.as_secs_f64() / damping);
+    target + (shown - target) * left
+}
+
+/// Smooth start and stop for `t` in `0.0..=1.0`.
+fn smoothstep(t: f64) -> f64 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Where the power-on sweep is, see [`PowerOnSweep`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SweepPhase {
+    /// From the minimum to the maximum, how far along from `0.0` to `1.0`.
+    Rising(f64),
+    /// From the maximum back down to the gauge's value.
+    Falling(f64),
+}
+
+impl SweepPhase {
+    /// The value to show on a widget with `range` that would otherwise show `value`.
+    pub fn value(&self, range: &Range, value: f64) -> f64 {
+        match *self {
+            SweepPhase::Rising(t) => range.min + (range.max - range.min) * smoothstep(t),
+            SweepPhase::Falling(t) => range.max + (value - range.max) * smoothstep(t),
+        }
+    }
+}
+
+/// Swings every gauge with a range to its maximum and back when the dashboard comes up, like
+/// the instrument cluster of a car does. The sweep starts the first time it is asked for its
+/// phase.
+#[derive(Debug, Clone, Copy)]
+pub struct PowerOnSweep {
+    /// For the way up and down together.
+    pub duration: Duration,
+    started: Option<Duration>,
+}
+
+impl PowerOnSweep {
+    pub fn new(duration: Duration) -> PowerOnSweep {
+        PowerOnSweep {
+            duration,
+            started: None,
+        }
+    }
+
+    /// The phase at time `now`, `None` once the sweep is over.
+    pub fn phase(&mut self, now: Duration) -> Option<SweepPhase> {
+        let started = *self.started.get_or_insert(now);
+        let half = self.duration.as_secs_f64() / 2.0;
+        let elapsed = now.saturating_sub(started).as_secs_f64();
+        if half <= 0.0 || elapsed >= 2.0 * half {
+            None
+        } else if elapsed < half {
+            Some(SweepPhase::Rising(elapsed / half))
+        } else {
+            Some(SweepPhase::Falling(elapsed / half - 1.0))
+        }
+    }
+}
+
+/// How a triggered alarm shows its colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlarmEffect {
+    #[default]
+    Steady,
+    /// On and off.
+    Flash,
+    /// Fading between full and dim.
+    Pulse,
+}
+
+impl AlarmEffect {
+    /// How much of the alarm colour shows at time `now`, from 0 for none to 255 for all.
+    pub fn level(&self, now: Duration) -> u8 {
+        match self {
+            AlarmEffect::Steady => 255,
+            AlarmEffect::Flash => {
+                let period = FLASH_PERIOD.as_nanos();
+                if now.as_nanos() % period < period / 2 {
+                    255
+                } else {
+                    0
+                }
+            }
+            AlarmEffect::Pulse => {
+                let period = PULSE_PERIOD.as_nanos();
+                let t = (now.as_nanos() % period) as f64 / period as f64;
+                let wave = (1.0 + libm::cos(2.0 * PI * t)) / 2.0;
+                libm::round(PULSE_MIN_LEVEL + (255.0 - PULSE_MIN_LEVEL) * wave) as u8
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Anti-aliased drawing primitives.
+//!
+//! Every primitive blends its edges against what is already in the buffer, so they can be
+//! layered on top of images and each other. Positions are sub-pixel [`Coordinates`], a pixel
+//! `(x, y)` covers `x - 0.5..x + 0.5`. Angles are in degrees clockwise from 12 o'clock, like
+//! the dashboard dials.
+
+use crate::fb_trait::{Color, Coordinates, FrameBufferInterface};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    /// Ends flat at the end points.
+    Butt,
+    /// Ends flat, half the width past the end points.
+    Square,
+    /// Half circle around the end points.
+    Round,
+}
+
+/// Coverage of a pixel whose centre is `distance` away from a shape edge, negative inside.
+#[inline(always)]
+fn coverage(distance: f64) -> f64 {
+    (0.5 - distance).clamp(0.0, 1.0)
+}
+
+/// Normalise an angle into `0.0..360.0`.
+fn wrap_degrees(angle: f64) -> f64 {
+    let angle = angle % 360.0;
+    if angle < 0.0 {
+        angle + 360.0
+    } else {
+        angle
+    }
+}
+
+pub trait AntiAliased: FrameBufferInterface {
+    /// Blend `color` into a pixel with `coverage` from 0.0 to 1.0, clipped to the screen.
+    fn blend_pixel(&mut self, x: i32, y: i32, color: Color, coverage: f64) {
+        if coverage <= 0.0
+            || x < 0
+            || y < 0
+            || x as usize >= self.width()
+            || y as usize >= self.height()
+        {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+        if coverage >= 1.0 {
+            self.use_pixel(x, y, color);
+            return;
+        }
+        let under = self.get_pixel(x, y);
+        self.use_pixel(x, y, under.blend(color, (coverage * 255.0 + 0.5) as u8));
+    }
+
+    /// One pixel wide line with Xiaolin Wu's algorithm.
+    fn draw_line_aa(&mut self, from: &Coordinates, to: &Coordinates, color: Color) {
+        let (mut x0, mut y0, mut x1, mut y1) =
+            (from.virtual_x, from.virtual_y, to.virtual_x, to.virtual_y);
+        let steep = libm::fabs(y1 - y0) > libm::fabs(x1 - x0);
+        if steep {
+            core::mem::swap(&mut x0, &mut y0);
+            core::mem::swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            core::mem::swap(&mut x0, &mut x1);
+            core::mem::swap(&mut y0, &mut y1);
+        }
+        let gradient = if x1 - x0 == 0.0 {
+            1.0
+        } else {
+            (y1 - y0) / (x1 - x0)
+        };
+
+        let mut plot = |x: f64, y: f64, c: f64| {
+            let (x, y) = (x as i32, y as i32);
+            if steep {
+                self.blend_pixel(y, x, color, c);
+            } else {
+                self.blend_pixel(x, y, color, c);
+            }
+        };
+
+        // End points get coverage proportional to how much of their pixel the line spans.
+        let mut end_point = |x: f64, y: f64, gap: f64| {
+            let x_end = libm::round(x);
+            let y_end = y + gradient * (x_end - x);
+            let fract = y_end - libm::floor(y_end);
+            plot(x_end, libm::floor(y_end), (1.0 - fract) * gap);
+            plot(x_end, libm::floor(y_end) + 1.0, fract * gap);
+            (x_end, y_end)
+        };
+        let (x_start, y_start) = end_point(x0, y0, 1.0 - (x0 + 0.5 - libm::floor(x0 + 0.5)));
+        let (x_end, _) = end_point(x1, y1, x1 + 0.5 - libm::floor(x1 + 0.5));
+
+        let mut y = y_start + gradient;
+        let mut x = x_start + 1.0;
+        while x < x_end {
+            let fract = y - libm::floor(y);
+            plot(x, libm::floor(y), 1.0 - fract);
+            plot(x, libm::floor(y) + 1.0, fract);
+            y += gradient;
+            x += 1.0;
+        }
+    }
+
+    /// Line of any `width` with the given end caps.
+    fn draw_thick_line(
+        &mut self,
+        from: &Coordinates,
+        to: &Coordinates,
+        width: f64,
+        cap: LineCap,
+        color: Color,
+    ) {
+        let (ax, ay) = (from.virtual_x, from.virtual_y);
+        let (dx, dy) = (to.virtual_x - ax, to.virtual_y - ay);
+        let length = libm::sqrt(dx * dx + dy * dy);
+        let half = width / 2.0;
+        if length == 0.0 {
+            if cap != LineCap::Butt {
+                self.draw_circle_fill_aa(from, half, color);
+            }
+            return;
+        }
+        let (ux, uy) = (dx / length, dy / length);
+        let extend = if cap == LineCap::Square { half } else { 0.0 };
+
+        let margin = half + extend + 1.0;
+        let (min_x, max_x) = (ax.min(to.virtual_x) - margin, ax.max(to.virtual_x) + margin);
+        let (min_y, max_y) = (ay.min(to.virtual_y) - margin, ay.max(to.virtual_y) + margin);
+        for y in libm::floor(min_y) as i32..=libm::ceil(max_y) as i32 {
+            for x in libm::floor(min_x) as i32..=libm::ceil(max_x) as i32 {
+                let (px, py) = (x as f64 - ax, y as f64 - ay);
+                let along = px * ux + py * uy;
+                let across = libm::fabs(px * uy - py * ux);
+                let distance = match cap {
+                    LineCap::Round => {
+                        let t = along.clamp(0.0, length);
+                        let (cx, cy) = (px - ux * t, py - uy * t);
+                        libm::sqrt(cx * cx + cy * cy) - half
+                    }
+                    LineCap::Butt | LineCap::Square => {
+                        let outside = (-extend - along).max(along - length - extend);
+                        (across - half).max(outside)
+                    }
+                };
+                self.blend_pixel(x, y, color, coverage(distance));
+            }
+        }
+    }
+
+    /// Ring between `inner` and `outer` radius, running clockwise from `start` to `end`.
+    ///
+    /// `start == end` or a span of 360° or more draws the full ring; `inner == 0.0` draws a
+    /// filled pie slice.
+    fn draw_arc(
+        &mut self,
+        center: &Coordinates,
+        inner: f64,
+        outer: f64,
+        start: f64,
+        end: f64,
+        color: Color,
+    ) {
+        let full = start == end || libm::fabs(end - start) >= 360.0;
+        let span = if full { 360.0 } else { wrap_degrees(end - start) };
+        let middle = wrap_degrees(start + span / 2.0);
+
+        let (cx, cy) = (center.virtual_x, center.virtual_y);
+        let reach = outer + 1.0;
+        for y in libm::floor(cy - reach) as i32..=libm::ceil(cy + reach) as i32 {
+            for x in libm::floor(cx - reach) as i32..=libm::ceil(cx + reach) as i32 {
+                let (px, py) = (x as f64 - cx, y as f64 - cy);
+                let radius = libm::sqrt(px * px + py * py);
+                let mut distance = (inner - radius).max(radius - outer);
+                if !full && radius > 0.0 {
+                    // Angular distance from the middle of the arc, turned into pixels at this
+                    // radius.
+                    let angle = libm::atan2(px, -py).to_degrees();
+                    let off = libm::fabs(wrap_degrees(angle - middle + 180.0) - 180.0);
+                    let outside = (off - span / 2.0).to_radians() * radius;
+                    distance = distance.max(outside);
+                }
+                self.blend_pixel(x, y, color, coverage(distance));
+            }
+        }
+    }
+
+    /// Circle outline of the given stroke width.
+    fn draw_circle_aa(&mut self, center: &Coordinates, radius: f64, width: f64, color: Color) {
+        let half = width / 2.0;
+        self.draw_arc(center, (radius - half).max(0.0), radius + half, 0.0, 360.0, color);
+    }
+
+    fn draw_circle_fill_aa(&mut self, center: &Coordinates, radius: f64, color: Color) {
+        self.draw_arc(center, 0.0, radius, 0.0, 360.0, color);
+    }
+}
+
+impl<F: FrameBufferInterface + ?Sized> AntiAliased for F {}
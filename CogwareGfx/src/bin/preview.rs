@@ -0,0 +1,124 @@
+//! Render a dashboard layout file to an image, without a Pi.
+//!
+//! ```text
+//! cd CogwareGfx
+//! cargo run --features std --target <host triple> --bin preview -- LAYOUT.TXT dash.png RPM=4500
+//! ```
+//!
+//! The screen is 480x480 unless `--size 800x480` comes first, the layout is scaled to fit it.
+//! `--night` paints it in the night palette instead of the day one.
+//! Gauges are named like in the layout file (`RPM`, `0x2D`, ...) and take the raw value the ECU
+//! sends, before the widget's scale and offset. The image is a PPM if the output path ends in
+//! `.ppm`, a PNG otherwise. A layout with errors renders the error page the dashboard would show.
+//! A layout for a round screen has its corners blacked out like on the panel. Images are read
+//! from next to the layout file, only QOI ones since BMPs are decoded by the kernel.
+
+use std::{env, fs, path::Path, process::ExitCode, rc::Rc};
+
+use cogware_can::Gauge;
+use cogware_gfx::image::Image;
+use cogware_gfx::soft::SoftFrameBuffer;
+use cogware_gfx::transform::{Transform, Transformed};
+use cogware_gfx::widget::{error_dashboard, parse_layout, Palette, DEFAULT_LAYOUT_SIZE};
+
+const USAGE: &str =
+    "usage: preview [--size WxH] [--night] LAYOUT.TXT OUTPUT.png [GAUGE=VALUE ...]";
+
+/// `800x480` as `(800, 480)`.
+fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let (width, height) = size.split_once(['x', 'X'])?;
+    let size = (width.parse().ok()?, height.parse().ok()?);
+    (size.0 > 0 && size.1 > 0).then_some(size)
+}
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let (mut width, mut height) = DEFAULT_LAYOUT_SIZE;
+    let mut palette = Palette::DAY;
+    loop {
+        match args.first().map(String::as_str) {
+            Some("--size") => match args.get(1).and_then(|size| parse_size(size)) {
+                Some(size) => {
+                    (width, height) = size;
+                    args.drain(..2);
+                }
+                None => {
+                    eprintln!("{}", USAGE);
+                    return ExitCode::FAILURE;
+                }
+            },
+            Some("--night") => {
+                palette = Palette::NIGHT;
+                args.remove(0);
+            }
+            _ => break,
+        }
+    }
+    let [layout, output, values @ ..] = args.as_slice() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+
+    for value in values {
+        let parsed = value
+            .split_once('=')
+            .and_then(|(name, raw)| Some((Gauge::from_name(name.trim())?, raw.trim())))
+            .and_then(|(gauge, raw)| {
+                // Negative values are stored as their two's complement, like on the bus.
+                match raw.parse::<u32>() {
+                    Ok(raw) => Some((gauge, raw)),
+                    Err(_) => Some((gauge, raw.parse::<i32>().ok()? as u32)),
+                }
+            });
+        match parsed {
+            Some((gauge, raw)) => gauge.set(raw),
+            None => {
+                eprintln!("bad gauge value {:?}, expected GAUGE=VALUE", value);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let text = match fs::read_to_string(layout) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{}: {}", layout, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut dashboard = match parse_layout(&text) {
+        Ok(dashboard) => dashboard,
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("{}: {}", layout, error);
+            }
+            error_dashboard(layout, &errors)
+        }
+    };
+
+    let folder = Path::new(layout).parent().unwrap_or(Path::new("."));
+    dashboard.load_images(|file| {
+        let path = folder.join(file);
+        let image = fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| Image::from_qoi(&data).map_err(|e| e.to_string()));
+        match image {
+            Ok(image) => Some(Rc::new(image)),
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                None
+            }
+        }
+    });
+
+    let panel = SoftFrameBuffer::new(width, height);
+    let mut fb = Transformed::new(panel, Transform::default()).set_shape(dashboard.shape);
+    dashboard.fit(width, height);
+    dashboard.set_palette(palette);
+    dashboard.draw(&mut fb);
+    if let Err(e) = fb.inner.save(output) {
+        eprintln!("{}: {}", output, e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,258 @@
+//! Text on the screen for when there is no dashboard: the boot log and the panic screen.
+//!
+//! Both use the 16 px bitmap font on a fixed grid of character cells. The console keeps the
+//! last screenful of lines and scrolls up as new ones come in, the panic screen draws straight
+//! into the framebuffer without allocating so it works with a broken heap too.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+use core::time::Duration;
+
+use crate::damage::Rect;
+use crate::fb_trait::{
+    Color, Coordinates, FrameBufferInterface, BLACK_COLOR, LETTER_HEIGHT, LETTER_WIDTH,
+    WHITE_COLOR,
+};
+
+/// Space left around the text, in pixels.
+const MARGIN: u32 = 4;
+/// Columns a tab moves on to a multiple of.
+const TAB_WIDTH: usize = 8;
+/// Log lines starting like this are warnings, see the `warn!` macro.
+const WARNING_PREFIX: &str = "[W";
+const WARNING_COLOR: Color = Color::new(255, 170, 0);
+const PANIC_COLOR: Color = Color::new(200, 0, 0);
+
+/// The `(columns, rows)` of character cells on a `width` x `height` screen, at least one each.
+fn grid(width: u32, height: u32) -> (usize, usize) {
+    let cells =
+        |length: u32, cell: usize| (length.saturating_sub(2 * MARGIN) as usize / cell).max(1);
+    (cells(width, LETTER_WIDTH), cells(height, LETTER_HEIGHT))
+}
+
+/// Scrolling text, as many lines as fit on the screen it was made for.
+pub struct TextConsole {
+    columns: usize,
+    rows: usize,
+    /// Finished lines, the oldest first, at most `rows - 1` of them.
+    lines: Vec<String>,
+    /// The line being written, shown below the finished ones.
+    current: String,
+    /// The whole screen needs drawing, not just what changed.
+    full: bool,
+    /// Lines that went off the top since the last `draw`.
+    scrolled: usize,
+    /// The first row with new text since the last `draw`, everything below it is redrawn.
+    dirty: Option<usize>,
+}
+
+impl TextConsole {
+    /// A console filling a `width` x `height` screen.
+    pub fn new(width: u32, height: u32) -> TextConsole {
+        let (columns, rows) = grid(width, height);
+        TextConsole {
+            columns,
+            rows,
+            lines: Vec::new(),
+            current: String::new(),
+            full: true,
+            scrolled: 0,
+            dirty: None,
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The lines on screen from the top, the unfinished one last.
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.lines
+            .iter()
+            .map(String::as_str)
+            .chain(Some(self.current.as_str()))
+    }
+
+    /// Whether there is anything new to draw.
+    pub fn changed(&self) -> bool {
+        self.full || self.dirty.is_some()
+    }
+
+    fn touch(&mut self, row: usize) {
+        self.dirty = Some(self.dirty.map_or(row, |dirty| dirty.min(row)));
+    }
+
+    fn push_char(&mut self, c: char) {
+        match c {
+            '\n' => self.new_line(),
+            // Lines are only ever written from the start.
+            '\r' => {}
+            '\t' => {
+                let spaces = TAB_WIDTH - self.current.chars().count() % TAB_WIDTH;
+                for _ in 0..spaces {
+                    self.push_char(' ');
+                }
+            }
+            c if c.is_control() => {}
+            c => {
+                // Long lines wrap instead of running off the screen.
+                if self.current.chars().count() == self.columns {
+                    self.new_line();
+                }
+                self.current.push(c);
+                self.touch(self.lines.len());
+            }
+        }
+    }
+
+    fn new_line(&mut self) {
+        let line = core::mem::take(&mut self.current);
+        if self.rows > 1 {
+            if self.lines.len() + 1 >= self.rows {
+                self.lines.remove(0);
+                self.scrolled += 1;
+                self.dirty = self.dirty.map(|dirty| dirty.saturating_sub(1));
+            }
+            self.lines.push(line);
+        }
+        // The new line starts out empty, but a scroll leaves the old text in its row.
+        self.touch(self.lines.len());
+    }
+
+    /// Draw what changed since the last call, warnings in amber. Lines that scrolled are
+    /// moved up with the pixels they are drawn in, so a new log line only draws itself.
+    pub fn draw<F: FrameBufferInterface + ?Sized>(&mut self, fb: &mut F) {
+        let (width, height) = (fb.width_u32(), fb.height_u32());
+        let (first, top) = if self.full || self.scrolled >= self.rows {
+            (0, 0)
+        } else {
+            if self.scrolled > 0 {
+                let stride = width as usize;
+                let buffer = fb.raw_buffer();
+                let top = MARGIN as usize * stride;
+                let shift = self.scrolled * LETTER_HEIGHT * stride;
+                let end = (top + self.rows * LETTER_HEIGHT * stride).min(buffer.len());
+                buffer.copy_within((top + shift).min(end)..end, top);
+            }
+            let first = self.dirty.unwrap_or(self.rows);
+            (first, MARGIN + (first * LETTER_HEIGHT) as u32)
+        };
+        let below = height.saturating_sub(top);
+        fb.draw_rect_fill(&Coordinates::new(0, top), width, below, BLACK_COLOR);
+        for (row, line) in self.lines().enumerate().skip(first) {
+            let color = if line.starts_with(WARNING_PREFIX) {
+                WARNING_COLOR
+            } else {
+                WHITE_COLOR
+            };
+            let y = MARGIN + (row * LETTER_HEIGHT) as u32;
+            fb.write_str(line, Coordinates::new(MARGIN, y), color);
+        }
+        // Scrolling moved everything.
+        if self.scrolled > 0 {
+            fb.add_damage(Rect::new(0, 0, width, height));
+        } else {
+            fb.add_damage(Rect::new(0, top as i32, width, below));
+        }
+        self.full = false;
+        self.scrolled = 0;
+        self.dirty = None;
+    }
+}
+
+impl fmt::Write for TextConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.push_char(c);
+        }
+        Ok(())
+    }
+}
+
+/// What is known about a panic, for [`PanicReport::draw`].
+pub struct PanicReport<'a> {
+    pub message: &'a dyn fmt::Display,
+    pub file: &'a str,
+    pub line: u32,
+    pub column: u32,
+    /// Time since power-on.
+    pub uptime: Duration,
+}
+
+impl PanicReport<'_> {
+    /// Fill the screen with the panic: a red title bar, the message, where it happened and
+    /// when. Nothing is allocated.
+    pub fn draw<F: FrameBufferInterface + ?Sized>(&self, fb: &mut F) {
+        let (width, height) = (fb.width_u32(), fb.height_u32());
+        fb.draw_rect_fill(&Coordinates::new(0, 0), width, height, BLACK_COLOR);
+        let bar = LETTER_HEIGHT as u32 + 2 * MARGIN;
+        fb.draw_rect_fill(&Coordinates::new(0, 0), width, bar, PANIC_COLOR);
+
+        let mut text = CellWriter::new(fb, 0);
+        // Errors only mean the text ran off the screen.
+        let _ = fmt::Write::write_str(&mut text, "Kernel panic");
+        let mut text = CellWriter::new(fb, 2);
+        let _ = fmt::write(
+            &mut text,
+            format_args!(
+                "{}\n\nat {}:{}:{}\n\nafter {}.{:06} s",
+                self.message,
+                self.file,
+                self.line,
+                self.column,
+                self.uptime.as_secs(),
+                self.uptime.subsec_micros()
+            ),
+        );
+        fb.add_damage(Rect::new(0, 0, width, height));
+    }
+}
+
+/// Writes text cell by cell straight into a framebuffer, wrapping long lines.
+struct CellWriter<'f, F: ?Sized> {
+    fb: &'f mut F,
+    column: usize,
+    row: usize,
+    columns: usize,
+    rows: usize,
+}
+
+impl<'f, F: FrameBufferInterface + ?Sized> CellWriter<'f, F> {
+    /// Start at the beginning of `row`.
+    fn new(fb: &'f mut F, row: usize) -> Self {
+        let (columns, rows) = grid(fb.width_u32(), fb.height_u32());
+        CellWriter {
+            fb,
+            column: 0,
+            row,
+            columns,
+            rows,
+        }
+    }
+}
+
+impl<F: FrameBufferInterface + ?Sized> fmt::Write for CellWriter<'_, F> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if c == '\n' || self.column == self.columns {
+                self.row += 1;
+                self.column = 0;
+            }
+            if self.row >= self.rows {
+                return Err(fmt::Error);
+            }
+            if c == '\n' || c.is_control() {
+                continue;
+            }
+            let x = MARGIN + (self.column * LETTER_WIDTH) as u32;
+            let y = MARGIN + (self.row * LETTER_HEIGHT) as u32;
+            self.fb.write_char(c, Coordinates::new(x, y), WHITE_COLOR);
+            self.column += 1;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+//! Damaged screen regions, so a frame only touches what changed.
+
+/// A screen rectangle. `x` and `y` may be negative for shapes hanging off the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub const fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Square around a circle.
+    pub fn around(center_x: f64, center_y: f64, radius: f64) -> Self {
+        let x = libm::floor(center_x - radius) as i32;
+        let y = libm::floor(center_y - radius) as i32;
+        let size = libm::ceil(radius * 2.0) as u32 + 2;
+        Rect::new(x, y, size, size)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    fn right(&self) -> i64 {
+        self.x as i64 + self.width as i64
+    }
+
+    fn bottom(&self) -> i64 {
+        self.y as i64 + self.height as i64
+    }
+
+    /// Smallest rectangle covering both.
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+        Rect::new(x, y, (right - x as i64) as u32, (bottom - y as i64) as u32)
+    }
+
+    /// Whether the two share at least one pixel.
+    pub fn intersects(&self, other: &Rect) -> bool {
+        !self.is_empty()
+            && !other.is_empty()
+            && (self.x as i64) < other.right()
+            && (other.x as i64) < self.right()
+            && (self.y as i64) < other.bottom()
+            && (other.y as i64) < self.bottom()
+    }
+
+    /// Whether the two overlap or share an edge.
+    pub fn touches(&self, other: &Rect) -> bool {
+        !self.is_empty()
+            && !other.is_empty()
+            && (self.x as i64) <= other.right()
+            && (other.x as i64) <= self.right()
+            && (self.y as i64) <= other.bottom()
+            && (other.y as i64) <= self.bottom()
+    }
+
+    /// The part of the rectangle on a `width` x `height` screen.
+    pub fn clip(&self, width: u32, height: u32) -> Rect {
+        let x = self.x.max(0);
+        let y = self.y.max(0);
+        let right = self.right().min(width as i64);
+        let bottom = self.bottom().min(height as i64);
+        if right <= x as i64 || bottom <= y as i64 {
+            return Rect::new(0, 0, 0, 0);
+        }
+        Rect::new(x, y, (right - x as i64) as u32, (bottom - y as i64) as u32)
+    }
+}
+
+/// Most separate regions tracked per frame before giving up and taking the whole screen.
+const MAX_DAMAGE: usize = 16;
+
+/// The regions drawn to during one frame.
+///
+/// Touching regions are merged as they come in, which keeps the list short for dashboards
+/// where neighbouring widgets change together.
+#[derive(Debug, Clone)]
+pub struct Damage {
+    rects: [Rect; MAX_DAMAGE],
+    len: usize,
+    full: bool,
+}
+
+impl Damage {
+    pub const fn new() -> Self {
+        Damage {
+            rects: [Rect::new(0, 0, 0, 0); MAX_DAMAGE],
+            len: 0,
+            full: false,
+        }
+    }
+
+    pub fn add(&mut self, rect: Rect) {
+        if self.full || rect.is_empty() {
+            return;
+        }
+        let mut rect = rect;
+        // Merging can make the result touch regions it did not before, so go again.
+        let mut i = 0;
+        while i < self.len {
+            if self.rects[i].touches(&rect) {
+                rect = rect.union(&self.rects[i]);
+                self.len -= 1;
+                self.rects[i] = self.rects[self.len];
+                i = 0;
+            } else {
+                i += 1;
+            }
+        }
+        if self.len == MAX_DAMAGE {
+            self.full = true;
+            return;
+        }
+        self.rects[self.len] = rect;
+        self.len += 1;
+    }
+
+    /// The whole screen changed.
+    pub fn add_all(&mut self) {
+        self.full = true;
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.full
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.full && self.len == 0
+    }
+
+    /// The damaged regions, unclipped. Not meaningful when [`Damage::is_full`].
+    pub fn rects(&self) -> &[Rect] {
+        &self.rects[..self.len]
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.full = false;
+    }
+}
+
+impl Default for Damage {
+    fn default() -> Self {
+        Damage::new()
+    }
+}
@@ -0,0 +1,125 @@
+//! `embedded-graphics` support for the framebuffer.
+//!
+//! [`Canvas`] turns any [`FrameBufferInterface`] into a `DrawTarget<Color = Rgb888>`, so the
+//! embedded-graphics primitives, mono fonts and `tinybmp` images can draw on the display.
+
+use core::convert::Infallible;
+use embedded_graphics::{
+    pixelcolor::Rgb888,
+    prelude::*,
+    primitives::Rectangle,
+};
+
+use crate::damage::Rect;
+use crate::fb_trait::{Color, FrameBufferInterface};
+
+impl From<Rgb888> for Color {
+    #[inline(always)]
+    fn from(color: Rgb888) -> Self {
+        Color::new(color.r(), color.g(), color.b())
+    }
+}
+
+impl From<Color> for Rgb888 {
+    #[inline(always)]
+    fn from(color: Color) -> Self {
+        Rgb888::new(color.red(), color.green(), color.blue())
+    }
+}
+
+/// Borrows a framebuffer as an embedded-graphics draw target.
+pub struct Canvas<'a, F: FrameBufferInterface + ?Sized> {
+    fb: &'a mut F,
+}
+
+impl<'a, F: FrameBufferInterface + ?Sized> Canvas<'a, F> {
+    pub fn new(fb: &'a mut F) -> Self {
+        Canvas { fb }
+    }
+
+    /// Report `area` as drawn, so targets presenting only what changed show it.
+    fn add_damage(&mut self, area: &Rectangle) {
+        let Point { x, y } = area.top_left;
+        self.fb.add_damage(Rect::new(x, y, area.size.width, area.size.height));
+    }
+}
+
+impl<F: FrameBufferInterface + ?Sized> OriginDimensions for Canvas<'_, F> {
+    fn size(&self) -> Size {
+        Size::new(self.fb.width() as u32, self.fb.height() as u32)
+    }
+}
+
+impl<F: FrameBufferInterface + ?Sized> DrawTarget for Canvas<'_, F> {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let (width, height) = (self.fb.width() as i32, self.fb.height() as i32);
+        // The corners of what was drawn.
+        let mut drawn: Option<(Point, Point)> = None;
+        for Pixel(point, color) in pixels {
+            if (0..width).contains(&point.x) && (0..height).contains(&point.y) {
+                self.fb
+                    .use_pixel(point.x as usize, point.y as usize, color.into());
+                drawn = Some(match drawn {
+                    Some((min, max)) => (min.component_min(point), max.component_max(point)),
+                    None => (point, point),
+                });
+            }
+        }
+        if let Some((min, max)) = drawn {
+            self.add_damage(&Rectangle::with_corners(min, max));
+        }
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        // Partly off screen: the colours still have to be consumed in order.
+        if area.intersection(&self.bounding_box()) != *area {
+            let pixels = area.points().zip(colors).map(|(p, c)| Pixel(p, c));
+            return self.draw_iter(pixels);
+        }
+
+        let stride = self.fb.width();
+        let (x, w) = (area.top_left.x as usize, area.size.width as usize);
+        let buffer = self.fb.raw_buffer();
+        let mut colors = colors.into_iter();
+        for y in area.rows() {
+            let start = y as usize * stride + x;
+            for (pixel, color) in buffer[start..start + w].iter_mut().zip(&mut colors) {
+                *pixel = Color::from(color).rgb();
+            }
+        }
+        self.add_damage(area);
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.bounding_box());
+        if area.is_zero_sized() {
+            return Ok(());
+        }
+
+        let stride = self.fb.width();
+        let (x, w) = (area.top_left.x as usize, area.size.width as usize);
+        let rgb = Color::from(color).rgb();
+        let buffer = self.fb.raw_buffer();
+        for y in area.rows() {
+            let start = y as usize * stride + x;
+            buffer[start..start + w].fill(rgb);
+        }
+        self.add_damage(&area);
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid(&self.bounding_box(), color)
+    }
+}
@@ -0,0 +1,109 @@
+//! Fixed-size histories of gauge values, for graphs of what happened over the last seconds.
+//!
+//! A history is sampled at a fixed period rather than once per frame, so a graph scrolls at the
+//! same pace however fast the screen is drawn. The buffer is allocated once and then reused,
+//! the oldest sample making way for the newest.
+
+use alloc::{vec, vec::Vec};
+use core::time::Duration;
+
+/// A ring buffer of the last `capacity` values, one every `period`.
+#[derive(Debug, Clone)]
+pub struct History {
+    samples: Vec<f64>,
+    /// Index of the oldest sample.
+    start: usize,
+    len: usize,
+    pub period: Duration,
+    /// When the next sample is due, `None` before the first.
+    next: Option<Duration>,
+}
+
+impl History {
+    /// Room for `capacity` samples, at least one.
+    pub fn new(capacity: usize, period: Duration) -> History {
+        History {
+            samples: vec![0.0; capacity.max(1)],
+            start: 0,
+            len: 0,
+            period,
+            next: None,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Add a sample, dropping the oldest one if the buffer is full.
+    pub fn push(&mut self, value: f64) {
+        let capacity = self.capacity();
+        if self.len < capacity {
+            self.samples[(self.start + self.len) % capacity] = value;
+            self.len += 1;
+        } else {
+            self.samples[self.start] = value;
+            self.start = (self.start + 1) % capacity;
+        }
+    }
+
+    /// Sample `value` at time `now` if a sample is due. A frame that came late repeats the
+    /// value for every period it missed, so the samples stay evenly spaced in time. Returns how
+    /// many samples were added.
+    pub fn record(&mut self, now: Duration, value: f64) -> usize {
+        let due = match self.next {
+            None => 1,
+            Some(next) if now < next => 0,
+            Some(_) if self.period.is_zero() => 1,
+            Some(next) => ((now - next).as_nanos() / self.period.as_nanos()) as usize + 1,
+        };
+        if due == 0 {
+            return 0;
+        }
+        let added = due.min(self.capacity());
+        for _ in 0..added {
+            self.push(value);
+        }
+        self.next = Some(match self.next {
+            // After a long pause the schedule starts over instead of catching up.
+            Some(next) if due <= self.capacity() => next + self.period * due as u32,
+            _ => now + self.period,
+        });
+        added
+    }
+
+    /// Forget every sample.
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+        self.next = None;
+    }
+
+    /// The samples from the oldest to the newest.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = f64> + '_ {
+        let capacity = self.capacity();
+        (0..self.len).map(move |i| self.samples[(self.start + i) % capacity])
+    }
+
+    pub fn latest(&self) -> Option<f64> {
+        self.iter().next_back()
+    }
+
+    /// The smallest and the largest finite sample, `None` if there is none.
+    pub fn min_max(&self) -> Option<(f64, f64)> {
+        self.iter()
+            .filter(|value| value.is_finite())
+            .fold(None, |range, value| match range {
+                None => Some((value, value)),
+                Some((min, max)) => Some((value.min(min), value.max(max))),
+            })
+    }
+}
@@ -0,0 +1,300 @@
+//! Decoded images for splash screens and gauge face artwork.
+//!
+//! Images are decoded once and kept on the heap. Pixels are stored in [`Color`] layout with
+//! the alpha in the top byte, so opaque images are copied straight into a framebuffer and
+//! transparent ones are blended with what is already there.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::damage::Rect;
+use crate::fb_trait::{Color, Coordinates, FrameBufferInterface};
+
+/// Largest width or height accepted, anything bigger is surely not meant for the dashboard and
+/// would only eat the heap.
+pub const MAX_IMAGE_SIZE: u32 = 2048;
+
+#[derive(Debug)]
+pub enum ImageError {
+    /// Not a QOI file, or a broken one.
+    Qoi(qoi::Error),
+    /// Zero pixels wide or high.
+    Empty,
+    TooLarge { width: u32, height: u32 },
+    /// The pixel data does not match the image size.
+    BadLength { expected: usize, actual: usize },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Qoi(e) => write!(f, "bad QOI image: {}", e),
+            ImageError::Empty => write!(f, "empty image"),
+            ImageError::TooLarge { width, height } => write!(
+                f,
+                "image is {}x{}, at most {}x{} is supported",
+                width, height, MAX_IMAGE_SIZE, MAX_IMAGE_SIZE
+            ),
+            ImageError::BadLength { expected, actual } => {
+                write!(f, "expected {} pixels, got {}", expected, actual)
+            }
+        }
+    }
+}
+
+/// An image in memory, ready to draw.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    /// `0xAARRGGBB`, line after line.
+    pixels: Vec<u32>,
+    /// No pixel is even partly transparent, so drawing can copy whole lines.
+    opaque: bool,
+}
+
+impl Image {
+    /// An image from `0xAARRGGBB` pixels, line after line.
+    pub fn from_argb(width: u32, height: u32, pixels: Vec<u32>) -> Result<Image, ImageError> {
+        Image::check_size(width, height)?;
+        let expected = width as usize * height as usize;
+        if pixels.len() != expected {
+            return Err(ImageError::BadLength {
+                expected,
+                actual: pixels.len(),
+            });
+        }
+        let opaque = pixels.iter().all(|pixel| pixel >> 24 == 0xFF);
+        Ok(Image {
+            width,
+            height,
+            pixels,
+            opaque,
+        })
+    }
+
+    /// Decode a QOI file, with or without alpha. The size is checked before anything is
+    /// decoded.
+    pub fn from_qoi(data: &[u8]) -> Result<Image, ImageError> {
+        let header = qoi::decode_header(data).map_err(ImageError::Qoi)?;
+        Image::check_size(header.width, header.height)?;
+        let (header, bytes) = qoi::decode_to_vec(data).map_err(ImageError::Qoi)?;
+        let pixels = match header.channels {
+            qoi::Channels::Rgb => bytes
+                .as_chunks()
+                .0
+                .iter()
+                .map(|&[r, g, b]| Color::new(r, g, b).rgb())
+                .collect(),
+            qoi::Channels::Rgba => bytes
+                .as_chunks()
+                .0
+                .iter()
+                .map(|&[r, g, b, a]| (a as u32) << 24 | (Color::new(r, g, b).rgb() & 0xFFFFFF))
+                .collect(),
+        };
+        Image::from_argb(header.width, header.height, pixels)
+    }
+
+    /// Whether a `width` x `height` image is accepted, to check before decoding it.
+    pub fn check_size(width: u32, height: u32) -> Result<(), ImageError> {
+        if width == 0 || height == 0 {
+            Err(ImageError::Empty)
+        } else if width > MAX_IMAGE_SIZE || height > MAX_IMAGE_SIZE {
+            Err(ImageError::TooLarge { width, height })
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// `0xAARRGGBB`, line after line.
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.opaque
+    }
+
+    /// The colour and alpha of a pixel inside the image.
+    pub fn pixel(&self, x: u32, y: u32) -> (Color, u8) {
+        let argb = self.pixels[(y * self.width + x) as usize];
+        (Color::from_rgb(argb), (argb >> 24) as u8)
+    }
+
+    /// Draw the image with its top left corner at `(x, y)`, blending transparent pixels and
+    /// clipping to the screen.
+    pub fn draw<F: FrameBufferInterface + ?Sized>(&self, fb: &mut F, x: i32, y: i32) {
+        blit(fb, &self.pixels, self.width, x, y, self.opaque);
+    }
+
+    /// Draw the image stretched to `width` x `height`, nearest neighbour.
+    pub fn draw_scaled<F: FrameBufferInterface + ?Sized>(
+        &self,
+        fb: &mut F,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    ) {
+        if (width, height) == (self.width, self.height) {
+            self.draw(fb, x, y);
+            return;
+        }
+        let area = Rect::new(x, y, width, height).clip(fb.width_u32(), fb.height_u32());
+        for row in area.y..area.y + area.height as i32 {
+            let source_y = (row - y) as u64 * self.height as u64 / height as u64;
+            for column in area.x..area.x + area.width as i32 {
+                let source_x = (column - x) as u64 * self.width as u64 / width as u64;
+                let argb = self.pixels[(source_y * self.width as u64 + source_x) as usize];
+                blend(fb, column as usize, row as usize, argb);
+            }
+        }
+    }
+
+    /// Draw the image turned `angle` degrees clockwise around `pivot`, a point in the image
+    /// that lands on `at`, and grown or shrunk by `scale`. Pixels are sampled bilinearly, so
+    /// edges stay smooth at any angle. Like everywhere else a pixel `(x, y)` covers
+    /// `x - 0.5..x + 0.5`, in the image too.
+    pub fn draw_rotated<F: FrameBufferInterface + ?Sized>(
+        &self,
+        fb: &mut F,
+        pivot: (f64, f64),
+        at: &Coordinates,
+        angle: f64,
+        scale: f64,
+    ) {
+        if scale <= 0.0 {
+            return;
+        }
+        let area = self
+            .rotated_bounds(pivot, at, angle, scale)
+            .clip(fb.width_u32(), fb.height_u32());
+        let (sin, cos) = (libm::sin(angle.to_radians()), libm::cos(angle.to_radians()));
+        for y in area.y..area.y + area.height as i32 {
+            for x in area.x..area.x + area.width as i32 {
+                // Back from the screen into the image.
+                let (dx, dy) = (x as f64 - at.virtual_x, y as f64 - at.virtual_y);
+                let u = (dx * cos + dy * sin) / scale + pivot.0;
+                let v = (dy * cos - dx * sin) / scale + pivot.1;
+                blend(fb, x as usize, y as usize, self.sample(u, v));
+            }
+        }
+    }
+
+    /// The screen area [`Image::draw_rotated`] draws in.
+    pub fn rotated_bounds(&self, pivot: (f64, f64), at: &Coordinates, angle: f64, scale: f64) -> Rect {
+        let (sin, cos) = (libm::sin(angle.to_radians()), libm::cos(angle.to_radians()));
+        let (right, bottom) = (self.width as f64 - 0.5, self.height as f64 - 0.5);
+        let corners = [(-0.5, -0.5), (right, -0.5), (-0.5, bottom), (right, bottom)];
+        let (mut min_x, mut min_y) = (f64::MAX, f64::MAX);
+        let (mut max_x, mut max_y) = (f64::MIN, f64::MIN);
+        for (x, y) in corners {
+            let (dx, dy) = ((x - pivot.0) * scale, (y - pivot.1) * scale);
+            let (x, y) = (at.virtual_x + dx * cos - dy * sin, at.virtual_y + dx * sin + dy * cos);
+            (min_x, max_x) = (min_x.min(x), max_x.max(x));
+            (min_y, max_y) = (min_y.min(y), max_y.max(y));
+        }
+        let (left, top) = (libm::floor(min_x) as i32, libm::floor(min_y) as i32);
+        Rect::new(
+            left,
+            top,
+            (libm::ceil(max_x) as i32 - left + 1) as u32,
+            (libm::ceil(max_y) as i32 - top + 1) as u32,
+        )
+    }
+
+    /// Bilinear sample at `(u, v)` in image pixels, fading to transparent past the edges.
+    /// Colours are weighted by their alpha so transparent pixels don't darken the edges.
+    fn sample(&self, u: f64, v: f64) -> u32 {
+        let (left, top) = (libm::floor(u), libm::floor(v));
+        let (fx, fy) = (u - left, v - top);
+        let (left, top) = (left as i64, top as i64);
+        let (width, height) = (self.width as i64, self.height as i64);
+        if left < -1 || top < -1 || left >= width || top >= height {
+            return 0;
+        }
+        let texel = |x: i64, y: i64| {
+            if x < 0 || y < 0 || x >= width || y >= height {
+                0
+            } else {
+                self.pixels[(y * width + x) as usize]
+            }
+        };
+        let samples = [
+            (texel(left, top), (1.0 - fx) * (1.0 - fy)),
+            (texel(left + 1, top), fx * (1.0 - fy)),
+            (texel(left, top + 1), (1.0 - fx) * fy),
+            (texel(left + 1, top + 1), fx * fy),
+        ];
+        let (mut alpha, mut red, mut green, mut blue) = (0.0, 0.0, 0.0, 0.0);
+        for (argb, weight) in samples {
+            let a = (argb >> 24) as f64 * weight;
+            alpha += a;
+            red += ((argb >> 16) & 0xFF) as f64 * a;
+            green += ((argb >> 8) & 0xFF) as f64 * a;
+            blue += (argb & 0xFF) as f64 * a;
+        }
+        if alpha < 0.5 {
+            return 0;
+        }
+        let channel = |sum: f64| (libm::round(sum / alpha) as u32).min(255);
+        (libm::round(alpha) as u32).min(255) << 24
+            | channel(red) << 16
+            | channel(green) << 8
+            | channel(blue)
+    }
+}
+
+/// Draw `0xAARRGGBB` pixels, `width` per line, with the top left corner at `(x, y)`. Clipped to
+/// the screen, `opaque` pixels are copied line by line instead of blended.
+pub(crate) fn blit<F: FrameBufferInterface + ?Sized>(
+    fb: &mut F,
+    pixels: &[u32],
+    width: u32,
+    x: i32,
+    y: i32,
+    opaque: bool,
+) {
+    let height = pixels.len() as u32 / width.max(1);
+    let area = Rect::new(x, y, width, height).clip(fb.width_u32(), fb.height_u32());
+    if area.is_empty() {
+        return;
+    }
+    let stride = fb.width();
+    let (left, w) = (area.x as usize, area.width as usize);
+    let skip = (area.x - x) as usize;
+    for row in area.y..area.y + area.height as i32 {
+        let line = (row - y) as usize * width as usize + skip;
+        let source = &pixels[line..line + w];
+        if opaque {
+            let start = row as usize * stride + left;
+            fb.raw_buffer()[start..start + w].copy_from_slice(source);
+            continue;
+        }
+        for (i, &argb) in source.iter().enumerate() {
+            blend(fb, left + i, row as usize, argb);
+        }
+    }
+}
+
+/// Put an `0xAARRGGBB` pixel on top of what is on screen.
+#[inline(always)]
+fn blend<F: FrameBufferInterface + ?Sized>(fb: &mut F, x: usize, y: usize, argb: u32) {
+    match (argb >> 24) as u8 {
+        0 => {}
+        0xFF => fb.use_pixel(x, y, Color::from_rgb(argb)),
+        alpha => {
+            let under = fb.get_pixel(x, y);
+            fb.use_pixel(x, y, under.blend(Color::from_rgb(argb), alpha));
+        }
+    }
+}
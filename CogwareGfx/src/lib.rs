@@ -0,0 +1,27 @@
+//! Drawing for the Cogware dashboard: the framebuffer trait, anti-aliased primitives, text and
+//! the dashboard widgets.
+//!
+//! Everything draws through [`fb_trait::FrameBufferInterface`], so the same code renders on the
+//! Pi's framebuffer and, with the `std` feature, into a [`soft::SoftFrameBuffer`] on a host.
+
+#![no_std]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+pub mod animation;
+pub mod antialias;
+pub mod console;
+pub mod damage;
+pub mod draw_target;
+pub mod fb_trait;
+pub mod history;
+pub mod image;
+pub mod mask;
+pub mod screenshot;
+#[cfg(feature = "std")]
+pub mod soft;
+pub mod text;
+pub mod transform;
+pub mod widget;
@@ -0,0 +1,107 @@
+//! Which pixels of a panel can actually be seen.
+//!
+//! Round panels like the HyperPixel 2.1 Round are driven as a square, but the corners of that
+//! square are not there. Layouts are checked against the mask so nothing important ends up in
+//! them, and [`crate::transform::Transformed`] keeps them black.
+
+use core::ops::Range;
+
+use crate::damage::Rect;
+
+/// The outline of the visible part of a screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Shape {
+    #[default]
+    Rectangle,
+    /// The largest circle that fits the screen, centred.
+    Round,
+}
+
+/// The visible pixels of a `width` x `height` screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClipMask {
+    pub shape: Shape,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ClipMask {
+    pub const fn new(shape: Shape, width: u32, height: u32) -> Self {
+        ClipMask {
+            shape,
+            width,
+            height,
+        }
+    }
+
+    fn center(&self) -> (f64, f64) {
+        (self.width as f64 / 2.0, self.height as f64 / 2.0)
+    }
+
+    fn radius(&self) -> f64 {
+        self.width.min(self.height) as f64 / 2.0
+    }
+
+    /// The visible pixels of row `y`, empty above and below the screen.
+    pub fn span(&self, y: i32) -> Range<i32> {
+        if y < 0 || y >= self.height as i32 {
+            return 0..0;
+        }
+        match self.shape {
+            Shape::Rectangle => 0..self.width as i32,
+            Shape::Round => {
+                // A pixel is visible if its centre is inside the circle.
+                let (cx, cy) = self.center();
+                let dy = y as f64 + 0.5 - cy;
+                let r = self.radius();
+                if dy.abs() >= r {
+                    return 0..0;
+                }
+                let half = libm::sqrt(r * r - dy * dy);
+                let start = libm::ceil(cx - half - 0.5).max(0.0) as i32;
+                let end = (libm::floor(cx + half - 0.5) as i32 + 1).min(self.width as i32);
+                start..end.max(start)
+            }
+        }
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.span(y).contains(&x)
+    }
+
+    /// Whether every pixel of `rect` is visible. The visible area is convex, so checking the
+    /// corners is enough.
+    pub fn contains_rect(&self, rect: &Rect) -> bool {
+        if rect.is_empty() {
+            return true;
+        }
+        let (right, bottom) = (
+            rect.x + rect.width as i32 - 1,
+            rect.y + rect.height as i32 - 1,
+        );
+        [
+            (rect.x, rect.y),
+            (right, rect.y),
+            (rect.x, bottom),
+            (right, bottom),
+        ]
+        .iter()
+        .all(|&(x, y)| self.contains(x, y))
+    }
+
+    /// Whether the whole circle is visible.
+    pub fn contains_circle(&self, center_x: f64, center_y: f64, radius: f64) -> bool {
+        match self.shape {
+            Shape::Rectangle => {
+                center_x - radius >= 0.0
+                    && center_y - radius >= 0.0
+                    && center_x + radius <= self.width as f64
+                    && center_y + radius <= self.height as f64
+            }
+            Shape::Round => {
+                let (cx, cy) = self.center();
+                libm::hypot(center_x - cx, center_y - cy) + radius <= self.radius()
+            }
+        }
+    }
+}
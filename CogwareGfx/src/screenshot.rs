@@ -0,0 +1,158 @@
+//! Screenshots of the dashboard, for bug reports and for pictures of layouts.
+//!
+//! A screenshot is the upright picture as the widgets drew it, with whatever a round panel
+//! cuts off painted black. It is saved as an uncompressed 24-bit BMP that anything can open,
+//! or as a much smaller QOI. Over a serial line the file is sent as base64 text between two
+//! marker lines, so it can share the wire with the log.
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+
+use crate::fb_trait::FrameBufferInterface;
+use crate::image::{Image, ImageError};
+
+/// Starts the marker line in front of a streamed file, followed by the file name and size.
+pub const STREAM_BEGIN: &str = "--- screenshot";
+/// The line after the last line of base64.
+pub const STREAM_END: &str = "--- end of screenshot";
+/// Bytes per line of base64, 76 characters like in MIME.
+const BASE64_LINE_BYTES: usize = 57;
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+/// Size of the BMP file header and the `BITMAPINFOHEADER` after it.
+const BMP_HEADER_SIZE: u32 = 14 + 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Bmp,
+    Qoi,
+}
+
+impl Format {
+    /// `bmp` or `qoi`, in any case.
+    pub fn from_name(name: &str) -> Option<Format> {
+        if name.eq_ignore_ascii_case("bmp") {
+            Some(Format::Bmp)
+        } else if name.eq_ignore_ascii_case("qoi") {
+            Some(Format::Qoi)
+        } else {
+            None
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Bmp => "BMP",
+            Format::Qoi => "QOI",
+        }
+    }
+
+    /// The 8.3 file name of screenshot `number`, e.g. `SHOT0042.BMP`.
+    pub fn file_name(&self, number: u32) -> String {
+        format!("SHOT{:04}.{}", number % 10_000, self.extension())
+    }
+
+    /// The file contents for `image`. Alpha is left out, screenshots are opaque.
+    pub fn encode(&self, image: &Image) -> Vec<u8> {
+        match self {
+            Format::Bmp => encode_bmp(image),
+            Format::Qoi => encode_qoi(image),
+        }
+    }
+}
+
+/// What `fb` shows: its pixels, upright, black outside the panel's shape.
+pub fn capture<F: FrameBufferInterface + ?Sized>(fb: &mut F) -> Result<Image, ImageError> {
+    let (width, height) = (fb.width_u32(), fb.height_u32());
+    let mask = fb.clip_mask();
+    let mut pixels = fb.raw_buffer()[..width as usize * height as usize].to_vec();
+    for (y, row) in pixels.chunks_exact_mut(width as usize).enumerate() {
+        let visible = mask.span(y as i32);
+        for (x, pixel) in row.iter_mut().enumerate() {
+            *pixel = if visible.contains(&(x as i32)) {
+                *pixel | 0xFF00_0000
+            } else {
+                0xFF00_0000
+            };
+        }
+    }
+    Image::from_argb(width, height, pixels)
+}
+
+/// A bottom-up 24-bit BMP, lines padded to four bytes.
+fn encode_bmp(image: &Image) -> Vec<u8> {
+    let (width, height) = (image.width(), image.height());
+    let stride = (width * 3 + 3) & !3;
+    let size = BMP_HEADER_SIZE + stride * height;
+    let mut out = Vec::with_capacity(size as usize);
+    // File header.
+    out.extend_from_slice(b"BM");
+    out.extend_from_slice(&size.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&BMP_HEADER_SIZE.to_le_bytes());
+    // BITMAPINFOHEADER: one plane, 24 bits per pixel, no compression, 72 dpi.
+    out.extend_from_slice(&40u32.to_le_bytes());
+    out.extend_from_slice(&(width as i32).to_le_bytes());
+    out.extend_from_slice(&(height as i32).to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&24u16.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&(stride * height).to_le_bytes());
+    out.extend_from_slice(&2835i32.to_le_bytes());
+    out.extend_from_slice(&2835i32.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+
+    let padding = (stride - width * 3) as usize;
+    for row in image.pixels().chunks_exact(width as usize).rev() {
+        for pixel in row {
+            // Blue, green, red: the low three bytes of `0xAARRGGBB`.
+            out.extend_from_slice(&pixel.to_le_bytes()[..3]);
+        }
+        out.extend_from_slice(&[0; 3][..padding]);
+    }
+    out
+}
+
+fn encode_qoi(image: &Image) -> Vec<u8> {
+    let rgb: Vec<u8> = image
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let [_, r, g, b] = pixel.to_be_bytes();
+            [r, g, b]
+        })
+        .collect();
+    // The only failures are a size of zero or one that does not fit the buffer, `Image`
+    // rules out both.
+    qoi::encode_to_vec(rgb, image.width(), image.height()).expect("QOI encoding failed")
+}
+
+/// Write `data` as base64, 76 characters and a newline per line.
+pub fn write_base64<W: fmt::Write + ?Sized>(out: &mut W, data: &[u8]) -> fmt::Result {
+    for line in data.chunks(BASE64_LINE_BYTES) {
+        for group in line.chunks(3) {
+            let bytes = [group[0], *group.get(1).unwrap_or(&0), *group.get(2).unwrap_or(&0)];
+            let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+            for i in 0..4 {
+                let c = if i <= group.len() {
+                    BASE64_ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char
+                } else {
+                    '='
+                };
+                out.write_char(c)?;
+            }
+        }
+        out.write_char('\n')?;
+    }
+    Ok(())
+}
+
+/// Write file `name` for the host to pick out of the serial output: a marker line with the
+/// name and size, the contents in base64 and an end marker.
+pub fn write_stream<W: fmt::Write + ?Sized>(out: &mut W, name: &str, data: &[u8]) -> fmt::Result {
+    writeln!(out, "{} {} {}", STREAM_BEGIN, name, data.len())?;
+    write_base64(out, data)?;
+    writeln!(out, "{}", STREAM_END)
+}
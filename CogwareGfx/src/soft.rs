@@ -0,0 +1,208 @@
+//! A framebuffer in plain memory, for rendering on a host.
+//!
+//! Frames can be written out as PPM or PNG and read back from PNG, which is what the golden
+//! image tests and the `preview` tool are built on.
+
+use core::convert::Infallible;
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*, primitives::Rectangle};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+    vec,
+    vec::Vec,
+    write,
+};
+
+use crate::damage::{Damage, Rect};
+use crate::draw_target::Canvas;
+use crate::fb_trait::{Color, Coordinates, FrameBufferInterface};
+
+/// Only the low 24 bits of a pixel are colour.
+const RGB_MASK: u32 = 0x00FF_FFFF;
+
+/// A single-buffered `width` x `height` framebuffer backed by a `Vec<u32>`.
+pub struct SoftFrameBuffer {
+    pixels: Vec<u32>,
+    width: u32,
+    height: u32,
+    /// Regions drawn to since the last `update`.
+    pub damage: Damage,
+    /// The damage of the frame presented by the last `update`.
+    pub presented: Damage,
+    /// Number of frames presented so far.
+    pub frames: u32,
+}
+
+impl SoftFrameBuffer {
+    /// A black framebuffer.
+    pub fn new(width: u32, height: u32) -> Self {
+        SoftFrameBuffer {
+            pixels: vec![0; (width * height) as usize],
+            width,
+            height,
+            damage: Damage::new(),
+            presented: Damage::new(),
+            frames: 0,
+        }
+    }
+
+    /// Build a framebuffer from packed 8-bit RGB rows.
+    pub fn from_rgb8(width: u32, height: u32, data: &[u8]) -> Self {
+        let mut fb = SoftFrameBuffer::new(width, height);
+        for (pixel, &[r, g, b]) in fb.pixels.iter_mut().zip(data.as_chunks().0) {
+            *pixel = Color::new(r, g, b).rgb();
+        }
+        fb
+    }
+
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        Color::from_rgb(self.pixels[(y * self.width + x) as usize])
+    }
+
+    /// The frame as packed 8-bit RGB, row by row.
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.pixels.len() * 3);
+        for pixel in &self.pixels {
+            let color = Color::from_rgb(*pixel);
+            data.extend_from_slice(&[color.red(), color.green(), color.blue()]);
+        }
+        data
+    }
+
+    /// Write the frame as a binary PPM (`P6`).
+    pub fn write_ppm<W: Write>(&self, out: W) -> io::Result<()> {
+        let mut out = BufWriter::new(out);
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        out.write_all(&self.to_rgb8())?;
+        out.flush()
+    }
+
+    pub fn write_png<W: Write>(&self, out: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(BufWriter::new(out), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.to_rgb8())?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Save the frame as PPM if `path` ends in `.ppm`, as PNG otherwise.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path)?;
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("ppm") => self.write_ppm(file),
+            _ => self.write_png(file),
+        }
+    }
+
+    /// Read an 8-bit RGB or RGBA PNG, dropping the alpha channel.
+    pub fn load_png<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data)?;
+        let data = &data[..info.buffer_size()];
+        let rgb: Vec<u8> = match info.color_type {
+            png::ColorType::Rgb => data.to_vec(),
+            png::ColorType::Rgba => data
+                .as_chunks()
+                .0
+                .iter()
+                .flat_map(|&[r, g, b, _]| [r, g, b])
+                .collect(),
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    std::format!("unsupported PNG colour type {:?}", other),
+                ))
+            }
+        };
+        Ok(SoftFrameBuffer::from_rgb8(info.width, info.height, &rgb))
+    }
+
+    /// Number of pixels that differ from `other`. Frames of different sizes differ everywhere.
+    pub fn diff(&self, other: &SoftFrameBuffer) -> usize {
+        if (self.width, self.height) != (other.width, other.height) {
+            return self.pixels.len().max(other.pixels.len());
+        }
+        (self.pixels.iter().zip(&other.pixels))
+            .filter(|(a, b)| *a & RGB_MASK != *b & RGB_MASK)
+            .count()
+    }
+}
+
+impl FrameBufferInterface for SoftFrameBuffer {
+    fn raw_buffer(&mut self) -> &mut [u32] {
+        &mut self.pixels
+    }
+
+    fn width_u32(&self) -> u32 {
+        self.width
+    }
+
+    fn height_u32(&self) -> u32 {
+        self.height
+    }
+
+    fn clear_screen(&mut self) {
+        self.pixels.fill(0);
+        self.damage.add_all();
+    }
+
+    /// Clipped to the screen, unlike the default.
+    fn draw_rect_fill(&mut self, point: &Coordinates, width: u32, height: u32, color: Color) {
+        let rect = Rect::new(point.x() as i32, point.y() as i32, width, height)
+            .clip(self.width, self.height);
+        let stride = self.width as usize;
+        let (x, w) = (rect.x as usize, rect.width as usize);
+        for y in rect.y as usize..rect.y as usize + rect.height as usize {
+            self.pixels[y * stride + x..y * stride + x + w].fill(color.rgb());
+        }
+    }
+
+    fn add_damage(&mut self, rect: Rect) {
+        self.damage.add(rect);
+    }
+
+    fn update(&mut self) {
+        self.presented = core::mem::take(&mut self.damage);
+        self.frames += 1;
+    }
+}
+
+impl OriginDimensions for SoftFrameBuffer {
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+}
+
+impl DrawTarget for SoftFrameBuffer {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        Canvas::new(self).draw_iter(pixels)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        Canvas::new(self).fill_contiguous(area, colors)
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        Canvas::new(self).fill_solid(area, color)
+    }
+}
@@ -0,0 +1,273 @@
+//! Text drawing on top of `noto_sans_mono_bitmap` and seven-segment digits.
+//!
+//! Glyphs are blended with their anti-aliasing intensity and never paint a background unless
+//! one is asked for. Characters missing from the raster set are drawn as [`FALLBACK_GLYPH`].
+
+use noto_sans_mono_bitmap::{get_raster, get_raster_width, FontWeight, RasterHeight, RasterizedChar};
+
+use crate::antialias::{AntiAliased, LineCap};
+use crate::fb_trait::{Color, Coordinates, FrameBufferInterface, WHITE_COLOR};
+
+/// Drawn in place of characters the font has no raster for.
+pub const FALLBACK_GLYPH: char = '\u{FFFD}';
+
+#[derive(Debug, Clone, Copy)]
+pub struct Font {
+    pub height: RasterHeight,
+    pub weight: FontWeight,
+}
+
+impl Font {
+    pub const SMALL: Font = Font::new(RasterHeight::Size16, FontWeight::Regular);
+    pub const MEDIUM: Font = Font::new(RasterHeight::Size20, FontWeight::Regular);
+    pub const LARGE: Font = Font::new(RasterHeight::Size24, FontWeight::Bold);
+    pub const HUGE: Font = Font::new(RasterHeight::Size32, FontWeight::Bold);
+
+    pub const fn new(height: RasterHeight, weight: FontWeight) -> Self {
+        Font { height, weight }
+    }
+
+    /// Every glyph of a monospace font has the same advance.
+    pub const fn char_width(&self) -> usize {
+        get_raster_width(self.weight, self.height)
+    }
+
+    pub const fn line_height(&self) -> usize {
+        self.height.val()
+    }
+
+    /// The raster for `c`, or the fallback glyph (or `?`) if the font does not have it.
+    pub fn glyph(&self, c: char) -> RasterizedChar {
+        get_raster(c, self.weight, self.height)
+            .or_else(|| get_raster(FALLBACK_GLYPH, self.weight, self.height))
+            .or_else(|| get_raster('?', self.weight, self.height))
+            .expect("font has no '?'")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    /// The position is the left edge of the text.
+    Left,
+    /// The position is the horizontal centre of the text.
+    Center,
+    /// The position is the right edge of the text.
+    Right,
+}
+
+impl Align {
+    /// Left edge of a run `width` pixels wide anchored at `x`.
+    fn left(&self, x: f64, width: f64) -> f64 {
+        match self {
+            Align::Left => x,
+            Align::Center => x - width / 2.0,
+            Align::Right => x - width,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextStyle {
+    pub font: Font,
+    pub color: Color,
+    pub align: Align,
+    /// Filled behind the text's bounding box, transparent when `None`.
+    pub background: Option<Color>,
+}
+
+impl TextStyle {
+    pub const fn new(font: Font, color: Color) -> Self {
+        TextStyle {
+            font,
+            color,
+            align: Align::Left,
+            background: None,
+        }
+    }
+
+    pub const fn set_align(self, align: Align) -> Self {
+        let mut style = self;
+        style.align = align;
+        style
+    }
+
+    pub const fn set_background(self, color: Color) -> Self {
+        let mut style = self;
+        style.background = Some(color);
+        style
+    }
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle::new(Font::SMALL, WHITE_COLOR)
+    }
+}
+
+/// Width and height of a box around `text`, multiple lines included.
+pub fn measure_text(text: &str, font: &Font) -> (u32, u32) {
+    let columns = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+    let lines = text.lines().count().max(1);
+    (
+        (columns * font.char_width()) as u32,
+        (lines * font.line_height()) as u32,
+    )
+}
+
+/// Seven-segment digit proportions, relative to the digit height.
+const SEGMENT_WIDTH: f64 = 0.55;
+const SEGMENT_STROKE: f64 = 0.12;
+const SEGMENT_GAP: f64 = 0.25;
+
+/// Lit segments for a character, bits `a` to `g` clockwise from the top, `g` in the middle.
+fn segments(c: char) -> Option<u8> {
+    let bits = match c {
+        '0' | 'O' => 0b0111111,
+        '1' => 0b0000110,
+        '2' => 0b1011011,
+        '3' => 0b1001111,
+        '4' => 0b1100110,
+        '5' | 'S' => 0b1101101,
+        '6' => 0b1111101,
+        '7' => 0b0000111,
+        '8' => 0b1111111,
+        '9' => 0b1101111,
+        '-' => 0b1000000,
+        'A' => 0b1110111,
+        'E' => 0b1111001,
+        'F' => 0b1110001,
+        'H' => 0b1110110,
+        'L' => 0b0111000,
+        'P' => 0b1110011,
+        ' ' => 0,
+        _ => return None,
+    };
+    Some(bits)
+}
+
+/// Width of `text` drawn as seven-segment digits of the given height.
+pub fn measure_seven_segment(text: &str, height: f64) -> f64 {
+    text.chars()
+        .map(|c| match c {
+            '.' | ':' => height * SEGMENT_GAP,
+            _ => height * (SEGMENT_WIDTH + SEGMENT_GAP),
+        })
+        .sum()
+}
+
+pub trait TextRenderer: FrameBufferInterface {
+    /// Draw a glyph with its top left corner at `(x, y)`.
+    fn draw_glyph(&mut self, c: char, x: i32, y: i32, font: &Font, color: Color) {
+        let glyph = font.glyph(c);
+        for (row_i, row) in glyph.raster().iter().enumerate() {
+            for (col_i, intensity) in row.iter().enumerate() {
+                let coverage = *intensity as f64 / 255.0;
+                self.blend_pixel(x + col_i as i32, y + row_i as i32, color, coverage);
+            }
+        }
+    }
+
+    /// Draw `text` anchored at `position` following the style's alignment. Every line of a
+    /// multi-line text is aligned on its own. Returns the size of the drawn text.
+    fn draw_text(&mut self, text: &str, position: &Coordinates, style: &TextStyle) -> (u32, u32) {
+        let font = &style.font;
+        let (width, height) = measure_text(text, font);
+        if let Some(background) = style.background {
+            let left = style.align.left(position.virtual_x, width as f64).max(0.0);
+            let top_left = Coordinates {
+                virtual_x: left,
+                virtual_y: position.virtual_y.max(0.0),
+            };
+            self.draw_rect_fill(&top_left, width, height, background);
+        }
+
+        for (line_i, line) in text.lines().enumerate() {
+            let line_width = (line.chars().count() * font.char_width()) as f64;
+            let mut x = style.align.left(position.virtual_x, line_width) as i32;
+            let y = position.virtual_y as i32 + (line_i * font.line_height()) as i32;
+            for c in line.chars() {
+                self.draw_glyph(c, x, y, font, style.color);
+                x += font.char_width() as i32;
+            }
+        }
+        (width, height)
+    }
+
+    /// Draw `text` as seven-segment digits `height` pixels tall, anchored like `draw_text`.
+    ///
+    /// Digits, `-`, a few letters, `.` and `:` are supported, anything else is a blank cell.
+    /// With `unlit` set, the dark segments are drawn in that colour like on a real display.
+    fn draw_seven_segment(
+        &mut self,
+        text: &str,
+        position: &Coordinates,
+        height: f64,
+        align: Align,
+        color: Color,
+        unlit: Option<Color>,
+    ) {
+        let stroke = height * SEGMENT_STROKE;
+        let width = height * SEGMENT_WIDTH;
+        let mut x = align.left(position.virtual_x, measure_seven_segment(text, height));
+        let top = position.virtual_y;
+
+        for c in text.chars() {
+            let point = |dx: f64, dy: f64| Coordinates {
+                virtual_x: x + dx,
+                virtual_y: top + dy,
+            };
+            let (left, right) = (stroke / 2.0, width - stroke / 2.0);
+            let (upper, middle, lower) = (stroke / 2.0, height / 2.0, height - stroke / 2.0);
+
+            match c {
+                '.' => {
+                    let dot = point(height * SEGMENT_GAP / 2.0, lower);
+                    self.draw_circle_fill_aa(&dot, stroke * 0.6, color);
+                    x += height * SEGMENT_GAP;
+                    continue;
+                }
+                ':' => {
+                    for dy in [height * 0.3, height * 0.7] {
+                        let dot = point(height * SEGMENT_GAP / 2.0, dy);
+                        self.draw_circle_fill_aa(&dot, stroke * 0.6, color);
+                    }
+                    x += height * SEGMENT_GAP;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let lit = segments(c.to_ascii_uppercase()).unwrap_or(0);
+            // Segment end points, `a` to `g`, shortened so neighbours do not touch.
+            let inset = stroke * 0.7;
+            let ends = [
+                ((left + inset, upper), (right - inset, upper)),
+                ((right, upper + inset), (right, middle - inset)),
+                ((right, middle + inset), (right, lower - inset)),
+                ((left + inset, lower), (right - inset, lower)),
+                ((left, middle + inset), (left, lower - inset)),
+                ((left, upper + inset), (left, middle - inset)),
+                ((left + inset, middle), (right - inset, middle)),
+            ];
+            for (i, ((x0, y0), (x1, y1))) in ends.into_iter().enumerate() {
+                let segment_color = if lit & (1 << i) != 0 {
+                    color
+                } else if let Some(unlit) = unlit {
+                    unlit
+                } else {
+                    continue;
+                };
+                self.draw_thick_line(
+                    &point(x0, y0),
+                    &point(x1, y1),
+                    stroke,
+                    LineCap::Round,
+                    segment_color,
+                );
+            }
+            x += height * (SEGMENT_WIDTH + SEGMENT_GAP);
+        }
+    }
+}
+
+impl<F: FrameBufferInterface + ?Sized> TextRenderer for F {}
@@ -0,0 +1,215 @@
+//! Rotated, mirrored and round panels.
+//!
+//! [`Transformed`] wraps a framebuffer and gives drawing code an upright screen of its own.
+//! Every `update` turns the damaged parts onto the panel underneath, so a panel mounted on its
+//! side or seen through a mirror shows the dashboard the right way round.
+
+use alloc::{vec, vec::Vec};
+
+use crate::damage::{Damage, Rect};
+use crate::fb_trait::{Color, Coordinates, FrameBufferInterface, BLACK_COLOR};
+use crate::mask::{ClipMask, Shape};
+
+/// How far the picture is turned clockwise, to make up for a panel mounted turned the other way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rotation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Rotation {
+    /// `None` unless `degrees` is a multiple of 90.
+    pub fn from_degrees(degrees: u32) -> Option<Rotation> {
+        match degrees % 360 {
+            0 => Some(Rotation::Deg0),
+            90 => Some(Rotation::Deg90),
+            180 => Some(Rotation::Deg180),
+            270 => Some(Rotation::Deg270),
+            _ => None,
+        }
+    }
+
+    pub fn degrees(&self) -> u32 {
+        match self {
+            Rotation::Deg0 => 0,
+            Rotation::Deg90 => 90,
+            Rotation::Deg180 => 180,
+            Rotation::Deg270 => 270,
+        }
+    }
+}
+
+/// Maps the upright screen drawing code sees onto the panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Transform {
+    pub rotation: Rotation,
+    /// Flip left and right before rotating, for a panel seen in a mirror or windscreen.
+    pub mirror: bool,
+}
+
+impl Transform {
+    pub const fn new(rotation: Rotation, mirror: bool) -> Self {
+        Transform { rotation, mirror }
+    }
+
+    /// Whether the upright screen is the panel on its side.
+    pub fn swaps_axes(&self) -> bool {
+        matches!(self.rotation, Rotation::Deg90 | Rotation::Deg270)
+    }
+
+    /// The size of the upright screen on a `width` x `height` panel.
+    pub fn upright_size(&self, width: u32, height: u32) -> (u32, u32) {
+        if self.swaps_axes() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Where pixel `x`, `y` of a `width` x `height` upright screen lands on the panel.
+    #[inline(always)]
+    pub fn point(&self, x: u32, y: u32, width: u32, height: u32) -> (u32, u32) {
+        let x = if self.mirror { width - 1 - x } else { x };
+        match self.rotation {
+            Rotation::Deg0 => (x, y),
+            Rotation::Deg90 => (height - 1 - y, x),
+            Rotation::Deg180 => (width - 1 - x, height - 1 - y),
+            Rotation::Deg270 => (y, width - 1 - x),
+        }
+    }
+
+    /// Where `rect`, already clipped to the `width` x `height` upright screen, lands on the panel.
+    pub fn rect(&self, rect: &Rect, width: u32, height: u32) -> Rect {
+        if rect.is_empty() {
+            return *rect;
+        }
+        let (right, bottom) = (
+            rect.x as u32 + rect.width - 1,
+            rect.y as u32 + rect.height - 1,
+        );
+        let (x0, y0) = self.point(rect.x as u32, rect.y as u32, width, height);
+        let (x1, y1) = self.point(right, bottom, width, height);
+        let (x, y) = (x0.min(x1), y0.min(y1));
+        Rect::new(x as i32, y as i32, x0.abs_diff(x1) + 1, y0.abs_diff(y1) + 1)
+    }
+}
+
+/// An upright screen drawn in memory and turned onto the framebuffer `inner` on `update`.
+///
+/// Pixels outside the [`ClipMask`] are written black, so a round panel's corners never show
+/// anything that was drawn there by accident.
+pub struct Transformed<F: FrameBufferInterface> {
+    pub inner: F,
+    pub transform: Transform,
+    pub shape: Shape,
+    pixels: Vec<u32>,
+    width: u32,
+    height: u32,
+    /// Regions drawn to since the last `update`.
+    pub damage: Damage,
+}
+
+impl<F: FrameBufferInterface> Transformed<F> {
+    /// A black upright screen. The first `update` writes all of it.
+    pub fn new(inner: F, transform: Transform) -> Self {
+        let (width, height) = transform.upright_size(inner.width_u32(), inner.height_u32());
+        let mut damage = Damage::new();
+        damage.add_all();
+        Transformed {
+            inner,
+            transform,
+            shape: Shape::Rectangle,
+            pixels: vec![0; (width * height) as usize],
+            width,
+            height,
+            damage,
+        }
+    }
+
+    pub fn set_shape(self, shape: Shape) -> Transformed<F> {
+        let mut transformed = self;
+        transformed.shape = shape;
+        transformed
+    }
+
+    /// Turn `rect` of the upright screen onto the panel.
+    fn write_rect(&mut self, rect: Rect) {
+        if rect.is_empty() {
+            return;
+        }
+        let mask = self.clip_mask();
+        let (width, height) = (self.width, self.height);
+        let panel_width = self.inner.width();
+        let target = self.inner.raw_buffer();
+        for y in rect.y..rect.y + rect.height as i32 {
+            let visible = mask.span(y);
+            let row = y as usize * width as usize;
+            for x in rect.x..rect.x + rect.width as i32 {
+                let pixel = if visible.contains(&x) {
+                    self.pixels[row + x as usize]
+                } else {
+                    BLACK_COLOR.rgb()
+                };
+                let (px, py) = self.transform.point(x as u32, y as u32, width, height);
+                target[py as usize * panel_width + px as usize] = pixel;
+            }
+        }
+        let panel_rect = self.transform.rect(&rect, width, height);
+        self.inner.add_damage(panel_rect);
+    }
+}
+
+impl<F: FrameBufferInterface> FrameBufferInterface for Transformed<F> {
+    fn raw_buffer(&mut self) -> &mut [u32] {
+        &mut self.pixels
+    }
+
+    fn width_u32(&self) -> u32 {
+        self.width
+    }
+
+    fn height_u32(&self) -> u32 {
+        self.height
+    }
+
+    fn clip_mask(&self) -> ClipMask {
+        ClipMask::new(self.shape, self.width, self.height)
+    }
+
+    fn clear_screen(&mut self) {
+        self.pixels.fill(0);
+        self.damage.add_all();
+    }
+
+    /// Clipped to the screen, unlike the default.
+    fn draw_rect_fill(&mut self, point: &Coordinates, width: u32, height: u32, color: Color) {
+        let rect = Rect::new(point.x() as i32, point.y() as i32, width, height)
+            .clip(self.width, self.height);
+        let stride = self.width as usize;
+        let (x, w) = (rect.x as usize, rect.width as usize);
+        for y in rect.y as usize..rect.y as usize + rect.height as usize {
+            self.pixels[y * stride + x..y * stride + x + w].fill(color.rgb());
+        }
+    }
+
+    fn add_damage(&mut self, rect: Rect) {
+        self.damage.add(rect);
+    }
+
+    fn update(&mut self) {
+        let (width, height) = (self.width, self.height);
+        if self.damage.is_full() {
+            self.write_rect(Rect::new(0, 0, width, height));
+        } else {
+            for i in 0..self.damage.rects().len() {
+                let rect = self.damage.rects()[i].clip(width, height);
+                self.write_rect(rect);
+            }
+        }
+        self.damage.clear();
+        self.inner.update();
+    }
+}
@@ -0,0 +1,108 @@
+use super::{alarm_color, Alarm, Binding, Fit, Paint, Palette, Range, Role};
+use crate::damage::Rect;
+use crate::fb_trait::{Coordinates, FrameBufferInterface};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    /// Fills from left to right.
+    Horizontal,
+    /// Fills from bottom to top.
+    Vertical,
+}
+
+/// Bar graph filling up with the bound value.
+pub struct Bar {
+    pub binding: Binding,
+    pub range: Range,
+    pub top_left: Coordinates,
+    pub width: u32,
+    pub height: u32,
+    pub orientation: Orientation,
+    pub color: Paint,
+    pub border_color: Paint,
+    pub alarm: Option<Alarm>,
+}
+
+impl Bar {
+    pub fn new(
+        binding: Binding,
+        range: Range,
+        top_left: Coordinates,
+        width: u32,
+        height: u32,
+        orientation: Orientation,
+    ) -> Bar {
+        Bar {
+            binding,
+            range,
+            top_left,
+            width,
+            height,
+            orientation,
+            color: Role::Accent.into(),
+            border_color: Role::Foreground.into(),
+            alarm: None,
+        }
+    }
+
+    pub fn set_color(self, color: impl Into<Paint>) -> Bar {
+        let mut bar = self;
+        bar.color = color.into();
+        bar
+    }
+
+    pub fn set_border_color(self, color: impl Into<Paint>) -> Bar {
+        let mut bar = self;
+        bar.border_color = color.into();
+        bar
+    }
+
+    pub fn set_alarm(self, alarm: Alarm) -> Bar {
+        let mut bar = self;
+        bar.alarm = Some(alarm);
+        bar
+    }
+
+    pub fn fit(&mut self, fit: &Fit) {
+        self.top_left = fit.point(&self.top_left);
+        self.width = fit.length(self.width);
+        self.height = fit.length(self.height);
+    }
+
+    /// The border is drawn one pixel past `width` and `height`.
+    pub fn bounds(&self) -> Rect {
+        let (x, y) = (self.top_left.x() as i32, self.top_left.y() as i32);
+        Rect::new(x, y, self.width + 1, self.height + 1)
+    }
+
+    pub fn draw<F: FrameBufferInterface + ?Sized>(&self, fb: &mut F, palette: &Palette) {
+        let value = self.binding.value();
+        let color = alarm_color(&self.alarm, value, self.color, palette);
+        let fraction = self.range.fraction(value);
+
+        // Inside of the border.
+        let inner = Coordinates::new(self.top_left.x() + 1, self.top_left.y() + 1);
+        let (inner_w, inner_h) = (self.width.saturating_sub(1), self.height.saturating_sub(1));
+        fb.draw_rect_fill(&inner, inner_w, inner_h, palette.background);
+
+        match self.orientation {
+            Orientation::Horizontal => {
+                let filled = (inner_w as f64 * fraction) as u32;
+                fb.draw_rect_fill(&inner, filled, inner_h, color);
+            }
+            Orientation::Vertical => {
+                let filled = (inner_h as f64 * fraction) as u32;
+                let start = Coordinates::new(inner.x(), inner.y() + inner_h - filled);
+                fb.draw_rect_fill(&start, inner_w, filled, color);
+            }
+        }
+
+        // An empty bar in the critical colour while the gauge cannot be trusted.
+        let border = if value.is_finite() {
+            self.border_color.color(palette)
+        } else {
+            palette.critical
+        };
+        fb.draw_rect(self.top_left, self.width, self.height, border);
+    }
+}
@@ -0,0 +1,267 @@
+use alloc::{rc::Rc, string::String};
+
+use super::{
+    alarm_color, centered, format_value, polar, write_str_scaled, Alarm, Binding, Fit, Paint,
+    Palette, Range, Role,
+};
+use crate::antialias::{AntiAliased, LineCap};
+use crate::damage::Rect;
+use crate::text::{measure_seven_segment, Align, TextRenderer};
+use crate::fb_trait::{Color, Coordinates, FrameBufferInterface, LETTER_HEIGHT, LETTER_WIDTH};
+use crate::image::Image;
+
+/// Artwork drawn instead of the needle line. It points at 12 o'clock in the file and is turned
+/// around `pivot`, which sits on the centre of the dial.
+pub struct NeedleArt {
+    pub file: String,
+    /// The point in the image the needle turns around, the middle of the image if `None`.
+    pub pivot: Option<(f64, f64)>,
+    /// How much fitting the layout to the screen grew or shrunk it.
+    pub scale: f64,
+    /// Handed over by [`super::Dashboard::load_images`], the plain needle is drawn until then.
+    pub image: Option<Rc<Image>>,
+}
+
+impl NeedleArt {
+    pub fn new(file: &str, pivot: Option<(f64, f64)>) -> NeedleArt {
+        NeedleArt {
+            file: file.into(),
+            pivot,
+            scale: 1.0,
+            image: None,
+        }
+    }
+
+    /// Draw the needle pointing at `angle` with the pivot on `center`, `false` if the image is
+    /// not loaded.
+    fn draw<F: FrameBufferInterface + ?Sized>(
+        &self,
+        fb: &mut F,
+        center: &Coordinates,
+        angle: f64,
+    ) -> bool {
+        let Some(image) = &self.image else {
+            return false;
+        };
+        image.draw_rotated(fb, self.pivot(image), center, angle, self.scale);
+        true
+    }
+
+    fn bounds(&self, center: &Coordinates, angle: f64) -> Option<Rect> {
+        let image = self.image.as_ref()?;
+        Some(image.rotated_bounds(self.pivot(image), center, angle, self.scale))
+    }
+
+    fn pivot(&self, image: &Image) -> (f64, f64) {
+        let middle = ((image.width() as f64 - 1.0) / 2.0, (image.height() as f64 - 1.0) / 2.0);
+        self.pivot.unwrap_or(middle)
+    }
+}
+
+/// Analog sweep dial with a needle, tick marks, label and value.
+pub struct Dial {
+    pub binding: Binding,
+    pub range: Range,
+    pub center: Coordinates,
+    pub radius: u32,
+    /// Angle of `range.min`, degrees clockwise from 12 o'clock.
+    pub start_angle: f64,
+    /// Degrees the needle travels from `range.min` to `range.max`.
+    pub sweep: f64,
+    /// Number of tick intervals around the face.
+    pub ticks: u32,
+    pub decimals: usize,
+    pub label: String,
+    pub color: Paint,
+    /// Orange in every palette unless the layout gives it a role.
+    pub needle_color: Paint,
+    pub needle_art: Option<NeedleArt>,
+    pub alarm: Option<Alarm>,
+    /// Draw the value as seven-segment digits instead of the bitmap font.
+    pub seven_segment: bool,
+}
+
+impl Dial {
+    /// A classic 270° dial from 7:30 to 4:30 o'clock.
+    pub fn new(binding: Binding, range: Range, center: Coordinates, radius: u32) -> Dial {
+        Dial {
+            binding,
+            range,
+            center,
+            radius,
+            start_angle: -135.0,
+            sweep: 270.0,
+            ticks: 10,
+            decimals: 0,
+            label: String::new(),
+            color: Role::Foreground.into(),
+            needle_color: Color::new(255, 80, 0).into(),
+            needle_art: None,
+            alarm: None,
+            seven_segment: false,
+        }
+    }
+
+    pub fn set_label(self, label: &str) -> Dial {
+        let mut dial = self;
+        dial.label = label.into();
+        dial
+    }
+
+    pub fn set_decimals(self, decimals: usize) -> Dial {
+        let mut dial = self;
+        dial.decimals = decimals;
+        dial
+    }
+
+    pub fn set_ticks(self, ticks: u32) -> Dial {
+        let mut dial = self;
+        dial.ticks = ticks;
+        dial
+    }
+
+    pub fn set_sweep(self, start_angle: f64, sweep: f64) -> Dial {
+        let mut dial = self;
+        dial.start_angle = start_angle;
+        dial.sweep = sweep;
+        dial
+    }
+
+    pub fn set_color(self, color: impl Into<Paint>) -> Dial {
+        let mut dial = self;
+        dial.color = color.into();
+        dial
+    }
+
+    pub fn set_needle_color(self, color: impl Into<Paint>) -> Dial {
+        let mut dial = self;
+        dial.needle_color = color.into();
+        dial
+    }
+
+    pub fn set_needle_art(self, art: NeedleArt) -> Dial {
+        let mut dial = self;
+        dial.needle_art = Some(art);
+        dial
+    }
+
+    pub fn set_alarm(self, alarm: Alarm) -> Dial {
+        let mut dial = self;
+        dial.alarm = Some(alarm);
+        dial
+    }
+
+    pub fn set_seven_segment(self, seven_segment: bool) -> Dial {
+        let mut dial = self;
+        dial.seven_segment = seven_segment;
+        dial
+    }
+
+    pub fn fit(&mut self, fit: &Fit) {
+        self.center = fit.point(&self.center);
+        self.radius = fit.length(self.radius);
+        if let Some(art) = &mut self.needle_art {
+            art.scale *= fit.scale;
+        }
+    }
+
+    /// Where the needle points for `value`, degrees clockwise from 12 o'clock.
+    fn angle(&self, value: f64) -> f64 {
+        self.start_angle + self.sweep * self.range.fraction(value)
+    }
+
+    /// Top of the label, the value is drawn one line below it.
+    fn text_y(&self) -> f64 {
+        self.center.virtual_y + self.radius as f64 * 0.35
+    }
+
+    /// The face, with room for the anti-aliased edge, needle artwork reaching past it and the
+    /// label and value, which keep their size and spill out of a small face.
+    pub fn bounds(&self) -> Rect {
+        let face =
+            Rect::around(self.center.virtual_x, self.center.virtual_y, self.radius as f64 + 2.0);
+        let value = self.binding.value();
+        let needle = self.needle_art.as_ref().filter(|_| value.is_finite());
+        let face = match needle.and_then(|art| art.bounds(&self.center, self.angle(value))) {
+            Some(needle) => face.union(&needle),
+            None => face,
+        };
+        face.union(&self.label_bounds()).union(&self.value_bounds(value))
+    }
+
+    fn label_bounds(&self) -> Rect {
+        let label = centered(&self.label, 1, self.center.virtual_x, self.text_y());
+        let width = (self.label.chars().count() * LETTER_WIDTH) as u32;
+        Rect::new(label.x() as i32, label.y() as i32, width, LETTER_HEIGHT as u32)
+    }
+
+    fn value_bounds(&self, value: f64) -> Rect {
+        let text = format_value(value, self.decimals);
+        let value_y = self.text_y() + LETTER_HEIGHT as f64;
+        let height = (LETTER_HEIGHT * 2) as u32;
+        if self.seven_segment {
+            // Anti-aliased strokes bleed a pixel past the measured run.
+            let width = measure_seven_segment(&text, height as f64);
+            let x = libm::floor(self.center.virtual_x - width / 2.0) as i32 - 1;
+            let y = libm::floor(value_y) as i32 - 1;
+            Rect::new(x, y, libm::ceil(width) as u32 + 3, height + 3)
+        } else {
+            let position = centered(&text, 2, self.center.virtual_x, value_y);
+            let width = (text.chars().count() * LETTER_WIDTH) as u32 * 2;
+            Rect::new(position.x() as i32, position.y() as i32, width, height)
+        }
+    }
+
+    pub fn draw<F: FrameBufferInterface + ?Sized>(&self, fb: &mut F, palette: &Palette) {
+        let value = self.binding.value();
+        let color = alarm_color(&self.alarm, value, self.color, palette);
+        let radius = self.radius as f64;
+
+        fb.draw_circle_aa(&self.center, radius, 2.0, color);
+
+        let ticks = self.ticks.max(1);
+        for i in 0..=ticks {
+            let angle = self.start_angle + self.sweep * i as f64 / ticks as f64;
+            let inner = polar(&self.center, radius * 0.85, angle);
+            let outer = polar(&self.center, radius, angle);
+            fb.draw_thick_line(&inner, &outer, 2.0, LineCap::Butt, color);
+        }
+
+        // No needle at all while the gauge cannot be trusted, rather than one at zero.
+        if value.is_finite() {
+            let angle = self.angle(value);
+            let art_drawn = self
+                .needle_art
+                .as_ref()
+                .is_some_and(|art| art.draw(fb, &self.center, angle));
+            if !art_drawn {
+                let needle_color = self.needle_color.color(palette);
+                let tip = polar(&self.center, radius * 0.8, angle);
+                fb.draw_thick_line(&self.center, &tip, 3.0, LineCap::Round, needle_color);
+                fb.draw_circle_fill_aa(&self.center, (radius / 20.0).max(2.0), needle_color);
+            }
+        }
+
+        let text_y = self.text_y();
+        write_str_scaled(
+            fb,
+            &self.label,
+            centered(&self.label, 1, self.center.virtual_x, text_y),
+            1,
+            color,
+        );
+        let text = format_value(value, self.decimals);
+        let value_y = text_y + LETTER_HEIGHT as f64;
+        if self.seven_segment {
+            let position = Coordinates {
+                virtual_x: self.center.virtual_x,
+                virtual_y: value_y,
+            };
+            let height = (LETTER_HEIGHT * 2) as f64;
+            fb.draw_seven_segment(&text, &position, height, Align::Center, color, None);
+        } else {
+            let position = centered(&text, 2, self.center.virtual_x, value_y);
+            write_str_scaled(fb, &text, position, 2, color);
+        }
+    }
+}
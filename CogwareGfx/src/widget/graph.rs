@@ -0,0 +1,218 @@
+use alloc::{format, string::String, vec::Vec};
+use core::time::Duration;
+
+use super::{write_str_scaled, Binding, Fit, Paint, Palette, Range, Role};
+use crate::antialias::AntiAliased;
+use crate::damage::Rect;
+use crate::fb_trait::{Color, Coordinates, FrameBufferInterface, LETTER_HEIGHT, LETTER_WIDTH};
+use crate::history::History;
+
+/// Colours of the traces added after the first, in turn. They are the same in every palette.
+pub const TRACE_COLORS: [Color; 4] = [
+    Color::new(255, 230, 0),
+    Color::new(0, 200, 255),
+    Color::new(255, 0, 200),
+    Color::new(255, 80, 0),
+];
+
+/// One line of a graph and the samples it is drawn from.
+pub struct Trace {
+    pub binding: Binding,
+    pub color: Paint,
+    pub history: History,
+}
+
+/// Scrolling line graph of one or more gauges, the newest sample at the right edge.
+pub struct Graph {
+    pub top_left: Coordinates,
+    pub width: u32,
+    pub height: u32,
+    /// Never empty, every trace keeps as many samples at the same rate.
+    pub traces: Vec<Trace>,
+    /// Fixed values at the bottom and top, otherwise the graph is scaled to fit the samples.
+    pub range: Option<Range>,
+    pub border_color: Paint,
+    pub grid_color: Paint,
+    pub label: String,
+    /// Times the traces were sampled, so the dashboard knows when to redraw.
+    sampled: u64,
+}
+
+impl Graph {
+    /// A graph of `binding` keeping `samples` values, one every `period`.
+    pub fn new(
+        binding: Binding,
+        top_left: Coordinates,
+        width: u32,
+        height: u32,
+        samples: usize,
+        period: Duration,
+    ) -> Graph {
+        Graph {
+            top_left,
+            width,
+            height,
+            traces: alloc::vec![Trace {
+                binding,
+                color: Role::Accent.into(),
+                history: History::new(samples, period),
+            }],
+            range: None,
+            border_color: Role::Foreground.into(),
+            grid_color: Role::Dim.into(),
+            label: String::new(),
+            sampled: 0,
+        }
+    }
+
+    /// Draw `binding` as well, in `color` or the next of [`TRACE_COLORS`].
+    pub fn add_trace(self, binding: Binding, color: Option<Paint>) -> Graph {
+        let mut graph = self;
+        let first = &graph.traces[0].history;
+        let history = History::new(first.capacity(), first.period);
+        let color = color
+            .unwrap_or(TRACE_COLORS[(graph.traces.len() - 1) % TRACE_COLORS.len()].into());
+        graph.traces.push(Trace {
+            binding,
+            color,
+            history,
+        });
+        graph
+    }
+
+    /// Colour of the first trace.
+    pub fn set_color(self, color: impl Into<Paint>) -> Graph {
+        let mut graph = self;
+        graph.traces[0].color = color.into();
+        graph
+    }
+
+    pub fn set_range(self, range: Range) -> Graph {
+        let mut graph = self;
+        graph.range = Some(range);
+        graph
+    }
+
+    pub fn set_border_color(self, color: impl Into<Paint>) -> Graph {
+        let mut graph = self;
+        graph.border_color = color.into();
+        graph
+    }
+
+    pub fn set_label(self, label: &str) -> Graph {
+        let mut graph = self;
+        graph.label = label.into();
+        graph
+    }
+
+    /// Sample every trace that is due at time `now`. Returns whether anything was added.
+    pub fn sample(&mut self, now: Duration) -> bool {
+        let mut added = false;
+        for trace in &mut self.traces {
+            added |= trace.history.record(now, trace.binding.reading()) > 0;
+        }
+        if added {
+            self.sampled += 1;
+        }
+        added
+    }
+
+    /// Changes whenever there are new samples to show.
+    pub fn sampled(&self) -> u64 {
+        self.sampled
+    }
+
+    /// The values at the bottom and the top of the graph.
+    pub fn shown_range(&self) -> Range {
+        if let Some(range) = self.range {
+            return range;
+        }
+        let extremes = self.traces.iter().filter_map(|trace| trace.history.min_max());
+        let Some((min, max)) = extremes.reduce(|(a, b), (c, d)| (a.min(c), b.max(d))) else {
+            return Range::new(0.0, 1.0);
+        };
+        // A little room above and below, and some height for a flat line.
+        let margin = if max > min {
+            (max - min) * 0.05
+        } else {
+            max.abs().max(1.0) * 0.05
+        };
+        Range::new(min - margin, max + margin)
+    }
+
+    pub fn fit(&mut self, fit: &Fit) {
+        self.top_left = fit.point(&self.top_left);
+        self.width = fit.length(self.width);
+        self.height = fit.length(self.height);
+    }
+
+    /// The border is drawn one pixel past `width` and `height`.
+    pub fn bounds(&self) -> Rect {
+        let (x, y) = (self.top_left.x() as i32, self.top_left.y() as i32);
+        Rect::new(x, y, self.width + 1, self.height + 1)
+    }
+
+    /// The frame, grid and traces only use clipped drawing, so the graph may hang off the screen.
+    pub fn draw<F: FrameBufferInterface + ?Sized>(&self, fb: &mut F, palette: &Palette) {
+        let inner = Coordinates::new(self.top_left.x() + 1, self.top_left.y() + 1);
+        let (inner_w, inner_h) = (self.width.saturating_sub(1), self.height.saturating_sub(1));
+        let border = self.border_color.color(palette);
+        fb.draw_rect_fill(&inner, inner_w, inner_h, palette.background);
+        if inner_w < 2 || inner_h < 2 {
+            fb.draw_rect(self.top_left, self.width, self.height, border);
+            return;
+        }
+
+        // Quarter lines.
+        let (left, right) = (inner.virtual_x, inner.virtual_x + (inner_w - 1) as f64);
+        for quarter in 1..4 {
+            let y = inner.virtual_y + (inner_h * quarter / 4) as f64;
+            let from = Coordinates { virtual_x: left, virtual_y: y };
+            let to = Coordinates { virtual_x: right, virtual_y: y };
+            fb.draw_line(&from, &to, self.grid_color.color(palette));
+        }
+
+        let range = self.shown_range();
+        for trace in &self.traces {
+            let color = trace.color.color(palette);
+            let history = &trace.history;
+            let steps = (history.capacity() - 1).max(1) as f64;
+            let first = history.capacity() - history.len();
+            let point = |i: usize, value: f64| Coordinates {
+                virtual_x: left + (right - left) * (first + i) as f64 / steps,
+                virtual_y: inner.virtual_y
+                    + (inner_h - 1) as f64 * (1.0 - range.fraction(value)),
+            };
+            let mut previous: Option<Coordinates> = None;
+            for (i, value) in history.iter().enumerate() {
+                // Gaps where a gauge had no number to give.
+                if !value.is_finite() {
+                    previous = None;
+                    continue;
+                }
+                let current = point(i, value);
+                match &previous {
+                    Some(previous) => fb.draw_line_aa(previous, &current, color),
+                    None => {
+                        let (x, y) = (libm::round(current.virtual_x), libm::round(current.virtual_y));
+                        fb.blend_pixel(x as i32, y as i32, color, 1.0);
+                    }
+                }
+                previous = Some(current);
+            }
+        }
+
+        fb.draw_rect(self.top_left, self.width, self.height, border);
+
+        // The label top left, the scale on the right.
+        let (x, y) = (inner.x() + 2, inner.y() + 2);
+        write_str_scaled(fb, &self.label, Coordinates::new(x, y), 1, palette.foreground);
+        let bottom = inner.y() + inner_h.saturating_sub(LETTER_HEIGHT as u32 + 2);
+        for (value, y) in [(range.max, y), (range.min, bottom)] {
+            let text = format!("{:.1}", value);
+            let width = (text.chars().count() * LETTER_WIDTH) as u32;
+            let x = (inner.x() + inner_w).saturating_sub(width + 2);
+            write_str_scaled(fb, &text, Coordinates::new(x, y), 1, palette.foreground);
+        }
+    }
+}
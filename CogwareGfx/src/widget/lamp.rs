@@ -0,0 +1,84 @@
+use alloc::string::String;
+
+use super::{centered, write_str_scaled, Alarm, Binding, Fit, Paint, Palette, Role};
+use crate::damage::Rect;
+use crate::fb_trait::{Coordinates, FrameBufferInterface, LETTER_HEIGHT, LETTER_WIDTH};
+
+/// Round warning lamp that lights up while its alarm is triggered.
+pub struct WarningLamp {
+    pub binding: Binding,
+    pub center: Coordinates,
+    pub radius: u32,
+    pub alarm: Alarm,
+    pub off_color: Paint,
+    pub label: String,
+}
+
+impl WarningLamp {
+    /// The lamp lights in the alarm's colour.
+    pub fn new(binding: Binding, center: Coordinates, radius: u32, alarm: Alarm) -> WarningLamp {
+        WarningLamp {
+            binding,
+            center,
+            radius,
+            alarm,
+            off_color: Role::Dim.into(),
+            label: String::new(),
+        }
+    }
+
+    pub fn set_label(self, label: &str) -> WarningLamp {
+        let mut lamp = self;
+        lamp.label = label.into();
+        lamp
+    }
+
+    pub fn set_off_color(self, color: impl Into<Paint>) -> WarningLamp {
+        let mut lamp = self;
+        lamp.off_color = color.into();
+        lamp
+    }
+
+    pub fn is_lit(&self) -> bool {
+        self.alarm.triggered(self.binding.value())
+    }
+
+    pub fn fit(&mut self, fit: &Fit) {
+        self.center = fit.point(&self.center);
+        self.radius = fit.length(self.radius);
+    }
+
+    /// The lamp and the label below it.
+    pub fn bounds(&self) -> Rect {
+        let (cx, cy) = (self.center.virtual_x, self.center.virtual_y);
+        Rect::around(cx, cy, self.radius as f64 + 1.0).union(&self.label_bounds())
+    }
+
+    pub fn label_bounds(&self) -> Rect {
+        let (cx, cy) = (self.center.virtual_x, self.center.virtual_y);
+        let label = centered(&self.label, 1, cx, cy + self.radius as f64 + 4.0);
+        let width = (self.label.chars().count() * LETTER_WIDTH) as u32;
+        Rect::new(label.x() as i32, label.y() as i32, width, LETTER_HEIGHT as u32)
+    }
+
+    pub fn draw<F: FrameBufferInterface + ?Sized>(&self, fb: &mut F, palette: &Palette) {
+        let off_color = self.off_color.color(palette);
+        let color = if self.is_lit() {
+            self.alarm.shade(off_color, palette)
+        } else {
+            off_color
+        };
+        fb.draw_circle_fill(&self.center, self.radius, color);
+        // The lamp cannot light up while the gauge cannot be trusted, its ring says so.
+        let outline = if self.binding.value().is_finite() {
+            palette.foreground
+        } else {
+            palette.critical
+        };
+        fb.draw_circle(&self.center, self.radius, outline);
+
+        let text_y = self.center.virtual_y + self.radius as f64 + 4.0;
+        let position = centered(&self.label, 1, self.center.virtual_x, text_y);
+        write_str_scaled(fb, &self.label, position, 1, palette.foreground);
+    }
+}
@@ -0,0 +1,647 @@
+//! Dashboard layouts described in a text file, usually `LAYOUT.TXT` on the SD card.
+//!
+//! One widget per line, lines starting with `#` are comments. A line is the widget kind, the
+//! gauge it is bound to (by name like `RPM` or id like `0x2D`, not for `text` and `image`) and
+//! `key=value` settings. Values with spaces can be quoted.
+//!
+//! ```text
+//! dial    RPM    x=240 y=160 r=140 min=0 max=8000 ticks=8 label=RPM alarm_above=7000 damping=0.05
+//! readout MAP    x=16 y=316 scale=0.145038 offset=-14.5038 label=BOOST unit=psi decimals=1 size=2
+//! bar     TPS    x=16 y=436 w=300 h=24 min=0 max=100 color=#00C800
+//! bar     CLNT   x=440 y=200 w=20 h=200 min=0 max=120 offset=-40 vertical
+//! lamp    BatVol x=376 y=424 r=14 scale=0.1 alarm_below=12 alarm_color=amber alarm_effect=flash
+//! text           x=16 y=8 label="Track day" size=2 color=yellow
+//! image   FACE.QOI x=40 y=40 w=400 h=400
+//! graph   AfrPri x=16 y=40 w=300 h=120 scale=0.1 trace=AfrTarget min=10 max=20 label=AFR
+//! ```
+//!
+//! Positions and sizes are for a 480x480 screen unless the layout has a `screen w=800 h=480`
+//! line. On a screen of another size the whole layout is scaled to fit, see [`Fit`]. A layout
+//! for a round panel says `screen round`, every widget must then be inside the circle. On other
+//! screens a widget may hang off the edge, but not be off the screen altogether.
+//!
+//! Settings every gauge widget understands: `x`, `y`, `scale`, `offset`, `damping`, `color`,
+//! `label`, `alarm_below`, `alarm_above`, `alarm_color` and `alarm_effect`. Colours are
+//! `#RRGGBB`, one of the names in [`parse_color`] or a palette role like `accent` or
+//! `critical` that follows the day and night palettes, see [`Role`]. Widgets are painted in
+//! roles unless the layout says otherwise. `segments` draws the value as
+//! seven-segment digits. `damping` is how many seconds the shown value lags behind the gauge,
+//! and `alarm_effect` is `steady`, `flash` or `pulse`.
+//!
+//! | Kind      | Required        | Optional                                                           |
+//! |-----------|-----------------|--------------------------------------------------------------------|
+//! | `dial`    | `r`, `max`      | `min`, `ticks`, `decimals`, `start`, `sweep`, `needle`, `segments` |
+//! |           |                 | `needle_image`, `pivot_x`, `pivot_y`                               |
+//! | `bar`     | `w`, `h`, `max` | `min`, `vertical`, `border`                                        |
+//! | `readout` |                 | `unit`, `decimals`, `size`, `segments`                             |
+//! | `lamp`    | `r`, an alarm   | `off`                                                              |
+//! | `text`    | `label`         | `size`                                                             |
+//! | `image`   |                 | `w`, `h`                                                           |
+//! | `graph`   | `w`, `h`        | `min`, `max`, `rate`, `samples`, `trace`, `trace_color`, `border`  |
+//!
+//! An `image` names a QOI or BMP file on the SD card instead of a gauge and is drawn at its
+//! own size unless `w` and `h` are given. Widgets are drawn in the order they are listed, so
+//! artwork goes before the widgets on top of it. A dial's `needle_image` is artwork pointing
+//! at 12 o'clock that is turned around `pivot_x`, `pivot_y` (the middle of the image if not
+//! given), which sits on the centre of the dial.
+//!
+//! A `graph` scrolls the last `samples` values of its gauge (one per pixel of `w` if not given),
+//! taken `rate` times a second (10 if not given). Every `trace=GAUGE` draws another gauge on
+//! the same graph with the same `scale` and `offset`, `trace_color`s colour them in turn.
+//! Without `min` and `max` the graph is scaled to fit the samples.
+//!
+//! A theme file, usually `THEME.TXT`, sets the colours of the roles with a line per palette:
+//!
+//! ```text
+//! day   background=#000000 foreground=white accent=#00C800 warning=amber critical=red dim=grey
+//! night foreground=#969696 accent=#006E00 warning=#AA6E00 critical=#BE0000 dim=#181818
+//! ```
+//!
+//! Roles left out keep the colours of [`Palette::DAY`] and [`Palette::NIGHT`].
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use cogware_can::Gauge;
+use core::{fmt, time::Duration};
+
+use super::*;
+use crate::animation::AlarmEffect;
+use crate::fb_trait::{Color, Coordinates, LETTER_HEIGHT, WHITE_COLOR};
+use crate::mask::{ClipMask, Shape};
+
+/// Graph samples taken a second unless the layout says otherwise.
+const DEFAULT_GRAPH_RATE: f64 = 10.0;
+/// Most samples a graph trace keeps.
+const MAX_GRAPH_SAMPLES: usize = 4096;
+
+/// What went wrong on a line of the layout file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutErrorKind {
+    UnknownWidget(String),
+    /// A theme line that is not for the `day` or `night` palette.
+    UnknownPalette(String),
+    UnknownGauge(String),
+    MissingGauge,
+    MissingImage,
+    UnknownSetting(String),
+    BadValue { key: String, value: String },
+    MissingSetting(&'static str),
+    UnterminatedQuote,
+    /// The widget is off the screen, or reaches into the corners of a round one.
+    NotVisible,
+}
+
+/// A layout error with the 1-based line number it was found on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutError {
+    pub line: usize,
+    pub kind: LayoutErrorKind,
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            LayoutErrorKind::UnknownWidget(kind) => write!(f, "unknown widget {:?}", kind),
+            LayoutErrorKind::UnknownPalette(name) => write!(f, "unknown palette {:?}", name),
+            LayoutErrorKind::UnknownGauge(name) => write!(f, "unknown gauge {:?}", name),
+            LayoutErrorKind::MissingGauge => write!(f, "missing gauge"),
+            LayoutErrorKind::MissingImage => write!(f, "missing image file"),
+            LayoutErrorKind::UnknownSetting(key) => write!(f, "unknown setting {:?}", key),
+            LayoutErrorKind::BadValue { key, value } => {
+                write!(f, "bad value {:?} for {}", value, key)
+            }
+            LayoutErrorKind::MissingSetting(key) => write!(f, "missing setting {}", key),
+            LayoutErrorKind::UnterminatedQuote => write!(f, "unterminated quote"),
+            LayoutErrorKind::NotVisible => write!(f, "widget is outside the screen"),
+        }
+    }
+}
+
+/// Parse a layout file into a dashboard.
+///
+/// Every line is checked, so all the errors in the file are reported at once.
+pub fn parse_layout(text: &str) -> Result<Dashboard, Vec<LayoutError>> {
+    let mut widgets = Vec::new();
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+    let mut size = DEFAULT_LAYOUT_SIZE;
+    let mut shape = Shape::Rectangle;
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let is_screen = line
+            .split_whitespace()
+            .next()
+            .is_some_and(|kind| kind.eq_ignore_ascii_case("screen"));
+        let parsed = if is_screen {
+            parse_screen(line).map(|screen| (size, shape) = screen)
+        } else {
+            parse_widget(line).map(|widget| {
+                widgets.push(widget);
+                lines.push(i + 1);
+            })
+        };
+        if let Err(kind) = parsed {
+            errors.push(LayoutError { line: i + 1, kind });
+        }
+    }
+
+    // The screen line may come last, so the widgets are checked against it afterwards.
+    // Widgets hanging off a rectangular screen are cut off at the edge, a round one would lose
+    // them in the corners.
+    let mask = ClipMask::new(shape, size.0, size.1);
+    for (widget, line) in widgets.iter().zip(&lines) {
+        let visible = match shape {
+            Shape::Round => widget.visible_in(&mask),
+            Shape::Rectangle => !widget.bounds().clip(size.0, size.1).is_empty(),
+        };
+        if !visible {
+            errors.push(LayoutError {
+                line: *line,
+                kind: LayoutErrorKind::NotVisible,
+            });
+        }
+    }
+    errors.sort_by_key(|error| error.line);
+
+    if errors.is_empty() {
+        Ok(Dashboard::new(widgets)
+            .set_size(size.0, size.1)
+            .set_shape(shape))
+    } else {
+        Err(errors)
+    }
+}
+
+/// Where the error list starts, inside the square that still fits on a round panel.
+const ERROR_MARGIN: u32 = 72;
+
+/// A dashboard that lists layout errors in red, so a broken layout is fixed at the car
+/// instead of staring at a blank screen.
+pub fn error_dashboard(file: &str, errors: &[LayoutError]) -> Dashboard {
+    let red = Role::Critical;
+    let mut widgets = Vec::new();
+    widgets.push(Widget::Text(
+        Text::new(
+            &format!("{} has errors:", file),
+            Coordinates::new(ERROR_MARGIN, ERROR_MARGIN),
+        )
+        .set_color(red),
+    ));
+    for (i, error) in errors.iter().enumerate() {
+        let y = ERROR_MARGIN + (i as u32 + 1) * LETTER_HEIGHT as u32;
+        widgets.push(Widget::Text(
+            Text::new(&error.to_string(), Coordinates::new(ERROR_MARGIN, y)).set_color(red),
+        ));
+    }
+    Dashboard::new(widgets)
+}
+
+/// `#RRGGBB` or a handful of colour names.
+pub fn parse_color(value: &str) -> Option<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let rgb = u32::from_str_radix(hex, 16).ok()?;
+        return Some(Color::new((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+    }
+    let color = match value.to_ascii_lowercase().as_str() {
+        "black" => Color::new(0, 0, 0),
+        "white" => WHITE_COLOR,
+        "red" => Color::new(255, 0, 0),
+        "green" => Color::new(0, 200, 0),
+        "blue" => Color::new(0, 80, 255),
+        "yellow" => Color::new(255, 230, 0),
+        "amber" => Color::new(255, 170, 0),
+        "orange" => Color::new(255, 80, 0),
+        "grey" | "gray" => Color::new(40, 40, 40),
+        _ => return None,
+    };
+    Some(color)
+}
+
+/// A palette role by name, or a fixed colour for [`parse_color`].
+pub fn parse_paint(value: &str) -> Option<Paint> {
+    Role::from_name(value)
+        .map(Paint::Role)
+        .or_else(|| parse_color(value).map(Paint::Fixed))
+}
+
+fn parse_alarm_effect(value: &str) -> Option<AlarmEffect> {
+    match value.to_ascii_lowercase().as_str() {
+        "steady" => Some(AlarmEffect::Steady),
+        "flash" => Some(AlarmEffect::Flash),
+        "pulse" => Some(AlarmEffect::Pulse),
+        _ => None,
+    }
+}
+
+/// Split a line into words, keeping quoted values together and dropping the quotes.
+fn tokenize(line: &str) -> Result<Vec<String>, LayoutErrorKind> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_token = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_token = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if has_token {
+                    tokens.push(core::mem::take(&mut current));
+                    has_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_token = true;
+            }
+        }
+    }
+    if in_quotes {
+        return Err(LayoutErrorKind::UnterminatedQuote);
+    }
+    if has_token {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+/// The `key=value` settings of one line. Settings are taken as the widget is built, whatever
+/// is left over afterwards was not understood.
+struct Settings {
+    values: Vec<(String, String)>,
+}
+
+impl Settings {
+    /// Settings from the words after the widget kind and gauge.
+    fn new(tokens: impl Iterator<Item = String>) -> Self {
+        Settings {
+            values: tokens
+                .map(|token| match token.split_once('=') {
+                    Some((k, v)) => (k.into(), v.into()),
+                    None => (token, String::new()),
+                })
+                .collect(),
+        }
+    }
+
+    fn take(&mut self, key: &str) -> Option<String> {
+        let i = self
+            .values
+            .iter()
+            .position(|(k, _)| k.eq_ignore_ascii_case(key))?;
+        Some(self.values.remove(i).1)
+    }
+
+    fn has(&self, key: &str) -> bool {
+        self.values.iter().any(|(k, _)| k.eq_ignore_ascii_case(key))
+    }
+
+    fn flag(&mut self, key: &str) -> bool {
+        self.take(key).is_some()
+    }
+
+    fn parsed<T>(
+        &mut self,
+        key: &'static str,
+        parse: impl Fn(&str) -> Option<T>,
+    ) -> Result<Option<T>, LayoutErrorKind> {
+        match self.take(key) {
+            Some(value) => parse(&value).map(Some).ok_or(LayoutErrorKind::BadValue {
+                key: key.into(),
+                value,
+            }),
+            None => Ok(None),
+        }
+    }
+
+    fn number<T: core::str::FromStr>(
+        &mut self,
+        key: &'static str,
+    ) -> Result<Option<T>, LayoutErrorKind> {
+        self.parsed(key, |v| v.parse().ok())
+    }
+
+    fn required<T: core::str::FromStr>(&mut self, key: &'static str) -> Result<T, LayoutErrorKind> {
+        self.number(key)?
+            .ok_or(LayoutErrorKind::MissingSetting(key))
+    }
+
+    /// A required size in pixels, at least 1.
+    fn pixels(&mut self, key: &'static str) -> Result<u32, LayoutErrorKind> {
+        self.parsed(key, |v| v.parse().ok().filter(|&n: &u32| n > 0))?
+            .ok_or(LayoutErrorKind::MissingSetting(key))
+    }
+
+    fn color(&mut self, key: &'static str) -> Result<Option<Paint>, LayoutErrorKind> {
+        self.parsed(key, parse_paint)
+    }
+
+    fn position(&mut self) -> Result<Coordinates, LayoutErrorKind> {
+        Ok(Coordinates::new(self.required("x")?, self.required("y")?))
+    }
+
+    fn binding(&mut self, gauge: Gauge) -> Result<Binding, LayoutErrorKind> {
+        let binding = Binding::scaled(
+            gauge,
+            self.number("scale")?.unwrap_or(1.0),
+            self.number("offset")?.unwrap_or(0.0),
+        );
+        let damping = self.parsed("damping", |v| v.parse().ok().filter(|&d: &f64| d >= 0.0))?;
+        Ok(binding.set_damping(damping.unwrap_or(0.0)))
+    }
+
+    fn alarm(&mut self) -> Result<Option<Alarm>, LayoutErrorKind> {
+        let below = self.number("alarm_below")?;
+        let above = self.number("alarm_above")?;
+        let color = self.color("alarm_color")?.unwrap_or(Role::Critical.into());
+        let effect = self.parsed("alarm_effect", parse_alarm_effect)?;
+        if below.is_none() && above.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(
+            Alarm::new(below, above, color).set_effect(effect.unwrap_or_default()),
+        ))
+    }
+
+    fn finish(self) -> Result<(), LayoutErrorKind> {
+        match self.values.into_iter().next() {
+            Some((key, _)) => Err(LayoutErrorKind::UnknownSetting(key)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parse a theme file, see the module documentation.
+///
+/// Every line is checked, so all the errors in the file are reported at once.
+pub fn parse_theme(text: &str) -> Result<Theme, Vec<LayoutError>> {
+    let mut theme = Theme::default();
+    let mut errors = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Err(kind) = parse_palette(line, &mut theme) {
+            errors.push(LayoutError { line: i + 1, kind });
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(theme)
+    } else {
+        Err(errors)
+    }
+}
+
+/// `night foreground=#969696 ...`, changing the roles given.
+fn parse_palette(line: &str, theme: &mut Theme) -> Result<(), LayoutErrorKind> {
+    let mut tokens = tokenize(line)?.into_iter();
+    let name = tokens.next().unwrap_or_default();
+    let palette = match name.to_ascii_lowercase().as_str() {
+        "day" => &mut theme.day,
+        "night" => &mut theme.night,
+        _ => return Err(LayoutErrorKind::UnknownPalette(name)),
+    };
+    let mut settings = Settings::new(tokens);
+    for role in Role::ALL {
+        if let Some(color) = settings.parsed(role.name(), parse_color)? {
+            palette.set_color(role, color);
+        }
+    }
+    settings.finish()
+}
+
+/// `screen w=800 h=480 round`, the screen the layout is designed for. The size defaults to
+/// [`DEFAULT_LAYOUT_SIZE`] if neither `w` nor `h` is given.
+fn parse_screen(line: &str) -> Result<((u32, u32), Shape), LayoutErrorKind> {
+    let mut settings = Settings::new(tokenize(line)?.into_iter().skip(1));
+    let size = if settings.has("w") || settings.has("h") {
+        (settings.pixels("w")?, settings.pixels("h")?)
+    } else {
+        DEFAULT_LAYOUT_SIZE
+    };
+    let shape = if settings.flag("round") {
+        Shape::Round
+    } else {
+        Shape::Rectangle
+    };
+    settings.finish()?;
+    Ok((size, shape))
+}
+
+fn parse_widget(line: &str) -> Result<Widget, LayoutErrorKind> {
+    let mut tokens = tokenize(line)?.into_iter().peekable();
+    let kind = tokens.next().unwrap_or_default().to_ascii_lowercase();
+
+    // An image names its file where other widgets name the gauge.
+    let file = match kind.as_str() {
+        "image" => tokens.next_if(|word| !word.contains('=')),
+        _ => None,
+    };
+
+    // The gauge is the only word without an `=`, apart from flags like `vertical`.
+    let gauge = match (kind.as_str(), tokens.peek()) {
+        ("text" | "image", _) => None,
+        (_, Some(word)) if !word.contains('=') => {
+            let word = tokens.next().unwrap();
+            Some(Gauge::from_name(&word).ok_or(LayoutErrorKind::UnknownGauge(word))?)
+        }
+        _ => None,
+    };
+
+    let mut settings = Settings::new(tokens);
+
+    let widget = match kind.as_str() {
+        "text" => {
+            let label = settings
+                .take("label")
+                .ok_or(LayoutErrorKind::MissingSetting("label"))?;
+            let mut text = Text::new(&label, settings.position()?)
+                .set_scale(settings.number("size")?.unwrap_or(1));
+            if let Some(color) = settings.color("color")? {
+                text = text.set_color(color);
+            }
+            Widget::Text(text)
+        }
+        "image" => {
+            let file = file.ok_or(LayoutErrorKind::MissingImage)?;
+            let mut picture = Picture::new(&file, settings.position()?);
+            if settings.has("w") || settings.has("h") {
+                picture = picture.set_size(settings.pixels("w")?, settings.pixels("h")?);
+            }
+            Widget::Picture(picture)
+        }
+        "dial" => {
+            let gauge = gauge.ok_or(LayoutErrorKind::MissingGauge)?;
+            let range = Range::new(
+                settings.number("min")?.unwrap_or(0.0),
+                settings.required("max")?,
+            );
+            let mut dial = Dial::new(
+                settings.binding(gauge)?,
+                range,
+                settings.position()?,
+                settings.required("r")?,
+            );
+            if let Some(ticks) = settings.number("ticks")? {
+                dial = dial.set_ticks(ticks);
+            }
+            if let Some(decimals) = settings.number("decimals")? {
+                dial = dial.set_decimals(decimals);
+            }
+            let start = settings.number("start")?.unwrap_or(dial.start_angle);
+            let sweep = settings.number("sweep")?.unwrap_or(dial.sweep);
+            dial = dial.set_sweep(start, sweep);
+            if let Some(color) = settings.color("color")? {
+                dial = dial.set_color(color);
+            }
+            if let Some(color) = settings.color("needle")? {
+                dial = dial.set_needle_color(color);
+            }
+            if let Some(file) = settings.take("needle_image") {
+                let pivot = if settings.has("pivot_x") || settings.has("pivot_y") {
+                    Some((settings.required("pivot_x")?, settings.required("pivot_y")?))
+                } else {
+                    None
+                };
+                dial = dial.set_needle_art(NeedleArt::new(&file, pivot));
+            }
+            if let Some(label) = settings.take("label") {
+                dial = dial.set_label(&label);
+            }
+            if let Some(alarm) = settings.alarm()? {
+                dial = dial.set_alarm(alarm);
+            }
+            dial = dial.set_seven_segment(settings.flag("segments"));
+            Widget::Dial(dial)
+        }
+        "bar" => {
+            let gauge = gauge.ok_or(LayoutErrorKind::MissingGauge)?;
+            let range = Range::new(
+                settings.number("min")?.unwrap_or(0.0),
+                settings.required("max")?,
+            );
+            let orientation = if settings.flag("vertical") {
+                Orientation::Vertical
+            } else {
+                Orientation::Horizontal
+            };
+            let mut bar = Bar::new(
+                settings.binding(gauge)?,
+                range,
+                settings.position()?,
+                settings.required("w")?,
+                settings.required("h")?,
+                orientation,
+            );
+            if let Some(color) = settings.color("color")? {
+                bar = bar.set_color(color);
+            }
+            if let Some(color) = settings.color("border")? {
+                bar = bar.set_border_color(color);
+            }
+            if let Some(alarm) = settings.alarm()? {
+                bar = bar.set_alarm(alarm);
+            }
+            Widget::Bar(bar)
+        }
+        "readout" => {
+            let gauge = gauge.ok_or(LayoutErrorKind::MissingGauge)?;
+            let mut readout = Readout::new(settings.binding(gauge)?, settings.position()?);
+            if let Some(label) = settings.take("label") {
+                readout = readout.set_label(&label);
+            }
+            if let Some(unit) = settings.take("unit") {
+                readout = readout.set_unit(&unit);
+            }
+            if let Some(decimals) = settings.number("decimals")? {
+                readout = readout.set_decimals(decimals);
+            }
+            if let Some(size) = settings.number("size")? {
+                readout = readout.set_scale(size);
+            }
+            if let Some(color) = settings.color("color")? {
+                readout = readout.set_color(color);
+            }
+            if let Some(alarm) = settings.alarm()? {
+                readout = readout.set_alarm(alarm);
+            }
+            readout = readout.set_seven_segment(settings.flag("segments"));
+            Widget::Readout(readout)
+        }
+        "graph" => {
+            let gauge = gauge.ok_or(LayoutErrorKind::MissingGauge)?;
+            let binding = settings.binding(gauge)?;
+            let top_left = settings.position()?;
+            let (width, height) = (settings.pixels("w")?, settings.pixels("h")?);
+            let samples = settings
+                .parsed("samples", |v| {
+                    v.parse().ok().filter(|n| (2..=MAX_GRAPH_SAMPLES).contains(n))
+                })?
+                .unwrap_or((width as usize).clamp(2, MAX_GRAPH_SAMPLES));
+            let rate = settings
+                .parsed("rate", |v| v.parse().ok().filter(|&r: &f64| r > 0.0 && r <= 1000.0))?
+                .unwrap_or(DEFAULT_GRAPH_RATE);
+            let period = Duration::from_secs_f64(1.0 / rate);
+            let mut graph = Graph::new(binding, top_left, width, height, samples, period);
+            if let Some(color) = settings.color("color")? {
+                graph = graph.set_color(color);
+            }
+            while let Some(name) = settings.take("trace") {
+                let gauge = Gauge::from_name(&name).ok_or(LayoutErrorKind::UnknownGauge(name))?;
+                let trace = Binding { gauge, ..binding };
+                graph = graph.add_trace(trace, settings.color("trace_color")?);
+            }
+            if settings.has("min") || settings.has("max") {
+                let range = Range::new(
+                    settings.number("min")?.unwrap_or(0.0),
+                    settings.required("max")?,
+                );
+                graph = graph.set_range(range);
+            }
+            if let Some(color) = settings.color("border")? {
+                graph = graph.set_border_color(color);
+            }
+            if let Some(label) = settings.take("label") {
+                graph = graph.set_label(&label);
+            }
+            Widget::Graph(graph)
+        }
+        "lamp" => {
+            let gauge = gauge.ok_or(LayoutErrorKind::MissingGauge)?;
+            let binding = settings.binding(gauge)?;
+            let center = settings.position()?;
+            let radius = settings.required("r")?;
+            let alarm = settings.alarm()?.ok_or(LayoutErrorKind::MissingSetting(
+                "alarm_below or alarm_above",
+            ))?;
+            let mut lamp = WarningLamp::new(binding, center, radius, alarm);
+            if let Some(color) = settings.color("off")? {
+                lamp = lamp.set_off_color(color);
+            }
+            if let Some(label) = settings.take("label") {
+                lamp = lamp.set_label(&label);
+            }
+            Widget::Lamp(lamp)
+        }
+        _ => return Err(LayoutErrorKind::UnknownWidget(kind)),
+    };
+
+    settings.finish()?;
+    Ok(widget)
+}
@@ -0,0 +1,738 @@
+//! Dashboard widgets.
+//!
+//! Every widget is bound to a gauge from the `cogware_can` store and renders itself through
+//! [`FrameBufferInterface`], so the same widgets work on any framebuffer.
+
+mod bar;
+mod dial;
+mod graph;
+mod lamp;
+mod layout;
+mod pages;
+mod picture;
+mod readout;
+mod text;
+mod theme;
+
+use alloc::{format, rc::Rc, string::String, vec, vec::Vec};
+use cogware_can::{DataWidth, Gauge};
+use core::time::Duration;
+
+use crate::animation::{ease, AlarmEffect, PowerOnSweep};
+use crate::damage::Rect;
+use crate::fb_trait::{Color, Coordinates, FrameBufferInterface, BLACK_COLOR, LETTER_WIDTH};
+use crate::image::Image;
+use crate::mask::{ClipMask, Shape};
+use crate::text::Font;
+pub use bar::*;
+pub use dial::*;
+pub use graph::*;
+pub use lamp::*;
+pub use layout::*;
+pub use pages::*;
+pub use picture::*;
+pub use readout::*;
+pub use text::*;
+pub use theme::*;
+
+/// Reads a gauge and converts the raw value to display units: `raw * scale + offset`.
+#[derive(Debug, Clone, Copy)]
+pub struct Binding {
+    pub gauge: Gauge,
+    pub scale: f64,
+    pub offset: f64,
+    /// Seconds the shown value takes to cover about two thirds of a change, see
+    /// [`crate::animation::ease`]. No damping shows every reading as it comes.
+    pub damping: f64,
+    /// What [`Dashboard::animate`] last moved the shown value to, the reading until then.
+    pub shown: Option<f64>,
+}
+
+impl Binding {
+    pub const fn raw(gauge: Gauge) -> Self {
+        Self::scaled(gauge, 1.0, 0.0)
+    }
+
+    pub const fn scaled(gauge: Gauge, scale: f64, offset: f64) -> Self {
+        Binding {
+            gauge,
+            scale,
+            offset,
+            damping: 0.0,
+            shown: None,
+        }
+    }
+
+    pub const fn set_damping(self, damping: f64) -> Binding {
+        let mut binding = self;
+        binding.damping = damping;
+        binding
+    }
+
+    /// The value to show, eased and swept by [`Dashboard::animate`].
+    pub fn value(&self) -> f64 {
+        self.shown.unwrap_or_else(|| self.reading())
+    }
+
+    /// The gauge's latest value in display units, `NaN` while the gauge cannot be trusted, see
+    /// [`cogware_can::GaugeData::is_valid`].
+    pub fn reading(&self) -> f64 {
+        if !self.gauge.is_valid() {
+            return f64::NAN;
+        }
+        let raw = self.gauge.get();
+        let raw = match self.gauge.width {
+            DataWidth::I16 => raw as u16 as i16 as f64,
+            _ => raw as f64,
+        };
+        raw * self.scale + self.offset
+    }
+}
+
+/// The display range of a widget, in display units.
+#[derive(Debug, Clone, Copy)]
+pub struct Range {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Range {
+    pub const fn new(min: f64, max: f64) -> Self {
+        Range { min, max }
+    }
+
+    /// Where `value` sits in the range, clamped to `0.0..=1.0`.
+    pub fn fraction(&self, value: f64) -> f64 {
+        if self.max <= self.min {
+            return 0.0;
+        }
+        ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+    }
+}
+
+/// Maps a layout designed for one screen size onto another. The layout is scaled uniformly
+/// until it fits and centred in the space left over.
+#[derive(Debug, Clone, Copy)]
+pub struct Fit {
+    pub scale: f64,
+    pub offset_x: f64,
+    pub offset_y: f64,
+}
+
+impl Fit {
+    /// `from` and `to` are `(width, height)` and must not be zero.
+    pub fn new(from: (u32, u32), to: (u32, u32)) -> Self {
+        let (from_w, from_h) = (from.0 as f64, from.1 as f64);
+        let (to_w, to_h) = (to.0 as f64, to.1 as f64);
+        let scale = (to_w / from_w).min(to_h / from_h);
+        Fit {
+            scale,
+            offset_x: (to_w - from_w * scale) / 2.0,
+            offset_y: (to_h - from_h * scale) / 2.0,
+        }
+    }
+
+    pub fn point(&self, point: &Coordinates) -> Coordinates {
+        Coordinates {
+            virtual_x: point.virtual_x * self.scale + self.offset_x,
+            virtual_y: point.virtual_y * self.scale + self.offset_y,
+        }
+    }
+
+    /// A length or integer text scale, rounded and never shrunk to nothing.
+    pub fn length(&self, length: u32) -> u32 {
+        libm::round(length as f64 * self.scale).max(1.0) as u32
+    }
+}
+
+/// Recolours a widget while its value is outside the safe band.
+#[derive(Debug, Clone, Copy)]
+pub struct Alarm {
+    pub below: Option<f64>,
+    pub above: Option<f64>,
+    pub color: Paint,
+    pub effect: AlarmEffect,
+    /// How much of `color` shows right now, set by [`Dashboard::animate`] for the effect.
+    pub level: u8,
+}
+
+impl Alarm {
+    pub fn new(below: Option<f64>, above: Option<f64>, color: impl Into<Paint>) -> Alarm {
+        Alarm {
+            below,
+            above,
+            color: color.into(),
+            effect: AlarmEffect::Steady,
+            level: 255,
+        }
+    }
+
+    pub fn below(limit: f64, color: impl Into<Paint>) -> Alarm {
+        Alarm::new(Some(limit), None, color)
+    }
+
+    pub fn above(limit: f64, color: impl Into<Paint>) -> Alarm {
+        Alarm::new(None, Some(limit), color)
+    }
+
+    pub fn set_effect(self, effect: AlarmEffect) -> Alarm {
+        let mut alarm = self;
+        alarm.effect = effect;
+        alarm
+    }
+
+    /// `color` with as much of the alarm colour on top as the effect shows right now.
+    pub fn shade(&self, color: Color, palette: &Palette) -> Color {
+        color.blend(self.color.color(palette), self.level)
+    }
+
+    pub fn triggered(&self, value: f64) -> bool {
+        self.below.is_some_and(|b| value < b) || self.above.is_some_and(|a| value > a)
+    }
+}
+
+/// Pick `color`, or the alarm colour if the alarm is triggered by `value`.
+fn alarm_color(alarm: &Option<Alarm>, value: f64, color: Paint, palette: &Palette) -> Color {
+    let color = color.color(palette);
+    match alarm {
+        Some(alarm) if alarm.triggered(value) => alarm.shade(color, palette),
+        _ => color,
+    }
+}
+
+/// Any widget that can go on a dashboard.
+pub enum Widget {
+    Dial(Dial),
+    Bar(Bar),
+    Readout(Readout),
+    Lamp(WarningLamp),
+    Text(Text),
+    Picture(Picture),
+    Graph(Graph),
+}
+
+impl Widget {
+    pub fn draw<F: FrameBufferInterface + ?Sized>(&self, fb: &mut F, palette: &Palette) {
+        match self {
+            Widget::Dial(w) => w.draw(fb, palette),
+            Widget::Bar(w) => w.draw(fb, palette),
+            Widget::Readout(w) => w.draw(fb, palette),
+            Widget::Lamp(w) => w.draw(fb, palette),
+            Widget::Text(w) => w.draw(fb, palette),
+            Widget::Picture(w) => w.draw(fb),
+            Widget::Graph(w) => w.draw(fb, palette),
+        }
+    }
+
+    /// The gauge binding, if the widget shows a gauge at all.
+    pub fn binding(&self) -> Option<&Binding> {
+        match self {
+            Widget::Dial(w) => Some(&w.binding),
+            Widget::Bar(w) => Some(&w.binding),
+            Widget::Readout(w) => Some(&w.binding),
+            Widget::Lamp(w) => Some(&w.binding),
+            Widget::Text(_) | Widget::Picture(_) | Widget::Graph(_) => None,
+        }
+    }
+
+    fn binding_mut(&mut self) -> Option<&mut Binding> {
+        match self {
+            Widget::Dial(w) => Some(&mut w.binding),
+            Widget::Bar(w) => Some(&mut w.binding),
+            Widget::Readout(w) => Some(&mut w.binding),
+            Widget::Lamp(w) => Some(&mut w.binding),
+            Widget::Text(_) | Widget::Picture(_) | Widget::Graph(_) => None,
+        }
+    }
+
+    /// The value the widget shows right now, `None` for static widgets.
+    pub fn value(&self) -> Option<f64> {
+        self.binding().map(Binding::value)
+    }
+
+    /// The range the value is shown in, for widgets that have one.
+    pub fn range(&self) -> Option<Range> {
+        match self {
+            Widget::Dial(w) => Some(w.range),
+            Widget::Bar(w) => Some(w.range),
+            _ => None,
+        }
+    }
+
+    pub fn alarm(&self) -> Option<&Alarm> {
+        match self {
+            Widget::Dial(w) => w.alarm.as_ref(),
+            Widget::Bar(w) => w.alarm.as_ref(),
+            Widget::Readout(w) => w.alarm.as_ref(),
+            Widget::Lamp(w) => Some(&w.alarm),
+            Widget::Text(_) | Widget::Picture(_) | Widget::Graph(_) => None,
+        }
+    }
+
+    fn alarm_mut(&mut self) -> Option<&mut Alarm> {
+        match self {
+            Widget::Dial(w) => w.alarm.as_mut(),
+            Widget::Bar(w) => w.alarm.as_mut(),
+            Widget::Readout(w) => w.alarm.as_mut(),
+            Widget::Lamp(w) => Some(&mut w.alarm),
+            Widget::Text(_) | Widget::Picture(_) | Widget::Graph(_) => None,
+        }
+    }
+
+    /// Samples taken so far, a graph is redrawn whenever it has a new one.
+    fn sampled(&self) -> u64 {
+        match self {
+            Widget::Graph(w) => w.sampled(),
+            _ => 0,
+        }
+    }
+
+    /// How much of the alarm colour shows, `None` while the alarm is not triggered.
+    fn alarm_level(&self) -> Option<u8> {
+        let value = self.value()?;
+        self.alarm()
+            .filter(|alarm| alarm.triggered(value))
+            .map(|alarm| alarm.level)
+    }
+
+    /// Move and resize the widget for another screen size.
+    pub fn fit(&mut self, fit: &Fit) {
+        match self {
+            Widget::Dial(w) => w.fit(fit),
+            Widget::Bar(w) => w.fit(fit),
+            Widget::Readout(w) => w.fit(fit),
+            Widget::Lamp(w) => w.fit(fit),
+            Widget::Text(w) => w.fit(fit),
+            Widget::Picture(w) => w.fit(fit),
+            Widget::Graph(w) => w.fit(fit),
+        }
+    }
+
+    /// The area the widget draws in when showing its current value.
+    pub fn bounds(&self) -> Rect {
+        match self {
+            Widget::Dial(w) => w.bounds(),
+            Widget::Bar(w) => w.bounds(),
+            Widget::Readout(w) => w.bounds(),
+            Widget::Lamp(w) => w.bounds(),
+            Widget::Text(w) => w.bounds(),
+            Widget::Picture(w) => w.bounds(),
+            Widget::Graph(w) => w.bounds(),
+        }
+    }
+
+    /// The image file the widget shows, and where its pixels go once loaded.
+    fn image_mut(&mut self) -> Option<(&str, &mut Option<Rc<Image>>)> {
+        match self {
+            Widget::Picture(w) => Some((&w.file, &mut w.image)),
+            Widget::Dial(w) => {
+                let art = w.needle_art.as_mut()?;
+                Some((&art.file, &mut art.image))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether all of the widget can be seen on a screen with `mask`. Round parts only have
+    /// to fit with their outline, not with the square around them.
+    pub fn visible_in(&self, mask: &ClipMask) -> bool {
+        match self {
+            Widget::Dial(w) => mask.contains_circle(
+                w.center.virtual_x,
+                w.center.virtual_y,
+                w.radius as f64 + 2.0,
+            ),
+            Widget::Lamp(w) => {
+                mask.contains_circle(w.center.virtual_x, w.center.virtual_y, w.radius as f64 + 1.0)
+                    && mask.contains_rect(&w.label_bounds())
+            }
+            // Artwork for a round panel is drawn square with transparent or black corners.
+            Widget::Picture(_) => true,
+            _ => mask.contains_rect(&self.bounds()),
+        }
+    }
+}
+
+/// What a widget looked like when it was last drawn.
+#[derive(Debug, Clone, Copy)]
+struct Drawn {
+    value: Option<f64>,
+    alarm_level: Option<u8>,
+    sampled: u64,
+    bounds: Rect,
+}
+
+impl Drawn {
+    fn of(widget: &Widget) -> Self {
+        Drawn {
+            value: widget.value(),
+            alarm_level: widget.alarm_level(),
+            sampled: widget.sampled(),
+            bounds: widget.bounds(),
+        }
+    }
+
+    /// Compares bit patterns, so a `NaN` value does not redraw every frame.
+    fn shows(&self, widget: &Widget) -> bool {
+        self.value.map(f64::to_bits) == widget.value().map(f64::to_bits)
+            && self.alarm_level == widget.alarm_level()
+            && self.sampled == widget.sampled()
+    }
+}
+
+/// The screen size layouts are designed for unless they say otherwise, the HyperPixel's.
+pub const DEFAULT_LAYOUT_SIZE: (u32, u32) = (480, 480);
+
+/// A screen full of widgets.
+pub struct Dashboard {
+    pub widgets: Vec<Widget>,
+    /// The `(width, height)` of the screen the widgets are placed for.
+    pub size: (u32, u32),
+    /// The visible part of that screen.
+    pub shape: Shape,
+    /// One entry per widget once the whole dashboard has been drawn.
+    drawn: Vec<Drawn>,
+    /// Sweeping the gauges after power-on, until it is over.
+    pub sweep: Option<PowerOnSweep>,
+    /// When [`Dashboard::animate`] last ran.
+    last_frame: Option<Duration>,
+    /// The colours the widgets' roles are painted in.
+    pub palette: Palette,
+}
+
+impl Dashboard {
+    pub fn new(widgets: Vec<Widget>) -> Self {
+        Dashboard {
+            widgets,
+            size: DEFAULT_LAYOUT_SIZE,
+            shape: Shape::Rectangle,
+            drawn: Vec::new(),
+            sweep: None,
+            last_frame: None,
+            palette: Palette::DAY,
+        }
+    }
+
+    pub fn set_size(self, width: u32, height: u32) -> Dashboard {
+        let mut dashboard = self;
+        dashboard.size = (width, height);
+        dashboard
+    }
+
+    pub fn set_shape(self, shape: Shape) -> Dashboard {
+        let mut dashboard = self;
+        dashboard.shape = shape;
+        dashboard
+    }
+
+    /// Swing the gauges to their maximum and back over `duration`, starting with the next
+    /// [`Dashboard::animate`].
+    pub fn set_sweep(self, duration: Duration) -> Dashboard {
+        let mut dashboard = self;
+        dashboard.sweep = Some(PowerOnSweep::new(duration));
+        dashboard
+    }
+
+    /// Paint the widgets in `palette` from the next `draw` on, redrawing all of them if it
+    /// differs from the one shown.
+    pub fn set_palette(&mut self, palette: Palette) {
+        if palette != self.palette {
+            self.palette = palette;
+            self.invalidate();
+        }
+    }
+
+    /// The pixels of the screen the widgets are placed for that can be seen.
+    pub fn mask(&self) -> ClipMask {
+        ClipMask::new(self.shape, self.size.0, self.size.1)
+    }
+
+    /// Rescale the widgets for a `width` x `height` screen, see [`Fit`].
+    pub fn fit(&mut self, width: u32, height: u32) {
+        if self.size == (width, height) {
+            return;
+        }
+        let fit = Fit::new(self.size, (width, height));
+        for widget in &mut self.widgets {
+            widget.fit(&fit);
+        }
+        self.size = (width, height);
+        self.invalidate();
+    }
+
+    /// Hand the widgets their artwork. `load` is called with every image file the layout
+    /// names that has not been loaded yet and returns `None` if the file cannot be used.
+    pub fn load_images(&mut self, mut load: impl FnMut(&str) -> Option<Rc<Image>>) {
+        for widget in &mut self.widgets {
+            if let Some((file, image @ None)) = widget.image_mut() {
+                *image = load(file);
+            }
+        }
+        self.invalidate();
+    }
+
+    /// Take the graph samples that are due at time `now`, usually the uptime.
+    pub fn sample(&mut self, now: Duration) {
+        for widget in &mut self.widgets {
+            if let Widget::Graph(graph) = widget {
+                graph.sample(now);
+            }
+        }
+    }
+
+    /// Move the shown values along, step the alarm effects and sample the graphs for time
+    /// `now`, usually the uptime. Call it before every `draw`, widgets show their readings as
+    /// they are until then.
+    pub fn animate(&mut self, now: Duration) {
+        self.sample(now);
+        let elapsed = self.last_frame.map_or(Duration::ZERO, |last| now.saturating_sub(last));
+        self.last_frame = Some(now);
+        let phase = self.sweep.as_mut().and_then(|sweep| sweep.phase(now));
+        if phase.is_none() {
+            self.sweep = None;
+        }
+
+        for widget in &mut self.widgets {
+            let range = widget.range();
+            if let Some(binding) = widget.binding_mut() {
+                let reading = binding.reading();
+                binding.shown = match (phase, range) {
+                    (Some(phase), Some(range)) => Some(phase.value(&range, reading)),
+                    _ if binding.damping > 0.0 => {
+                        let shown = binding.shown.unwrap_or(reading);
+                        Some(ease(shown, reading, binding.damping, elapsed))
+                    }
+                    _ => None,
+                };
+            }
+            if let Some(alarm) = widget.alarm_mut() {
+                alarm.level = alarm.effect.level(now);
+            }
+        }
+    }
+
+    /// Forget what is on screen, the next `draw` starts from a cleared screen.
+    pub fn invalidate(&mut self) {
+        self.drawn.clear();
+    }
+
+    /// The stock layout, designed for the 480x480 display.
+    pub fn default_layout() -> Self {
+        // Smooths the steps between CAN frames without lagging behind the engine.
+        const RPM_DAMPING: f64 = 0.05;
+        let red = Role::Critical;
+        let amber = Role::Warning;
+        let temperature = |gauge| Binding::scaled(gauge, 2.0, -91.0);
+
+        Dashboard::new(vec![
+            Widget::Dial(
+                Dial::new(
+                    Binding::raw(Gauge::RPM).set_damping(RPM_DAMPING),
+                    Range::new(0.0, 8000.0),
+                    Coordinates::new(240, 160),
+                    140,
+                )
+                .set_label("RPM")
+                .set_ticks(8)
+                .set_seven_segment(true)
+                .set_alarm(Alarm::above(7000.0, red).set_effect(AlarmEffect::Flash)),
+            ),
+            Widget::Readout(
+                Readout::new(
+                    Binding::scaled(Gauge::MAP, 0.145038, -14.5038),
+                    Coordinates::new(16, 316),
+                )
+                .set_label("BOOST")
+                .set_unit("psi")
+                .set_decimals(1)
+                .set_scale(2)
+                .set_seven_segment(true),
+            ),
+            Widget::Readout(
+                Readout::new(
+                    Binding::scaled(Gauge::AfrPri, 0.1, 0.0),
+                    Coordinates::new(176, 316),
+                )
+                .set_label("AFR")
+                .set_decimals(1)
+                .set_scale(2),
+            ),
+            Widget::Readout(
+                Readout::new(temperature(Gauge::CLNT), Coordinates::new(320, 316))
+                    .set_label("CLNT")
+                    .set_unit("C")
+                    .set_scale(2)
+                    .set_alarm(Alarm::above(105.0, red)),
+            ),
+            Widget::Readout(
+                Readout::new(temperature(Gauge::IAT), Coordinates::new(16, 380))
+                    .set_label("IAT")
+                    .set_unit("C")
+                    .set_scale(1),
+            ),
+            Widget::Readout(
+                Readout::new(
+                    Binding::scaled(Gauge::BatVol, 0.1, 0.0),
+                    Coordinates::new(120, 380),
+                )
+                .set_label("BATT")
+                .set_unit("V")
+                .set_decimals(1)
+                .set_scale(1),
+            ),
+            Widget::Readout(
+                Readout::new(Binding::raw(Gauge::StaTime), Coordinates::new(224, 380))
+                    .set_label("STA")
+                    .set_scale(1),
+            ),
+            Widget::Bar(Bar::new(
+                Binding::raw(Gauge::TPS),
+                Range::new(0.0, 100.0),
+                Coordinates::new(16, 436),
+                300,
+                24,
+                Orientation::Horizontal,
+            )),
+            Widget::Lamp(
+                WarningLamp::new(
+                    Binding::scaled(Gauge::BatVol, 0.1, 0.0),
+                    Coordinates::new(376, 424),
+                    14,
+                    Alarm::below(12.0, amber),
+                )
+                .set_label("BATT"),
+            ),
+            Widget::Lamp(
+                WarningLamp::new(
+                    temperature(Gauge::CLNT),
+                    Coordinates::new(436, 424),
+                    14,
+                    Alarm::above(105.0, red).set_effect(AlarmEffect::Pulse),
+                )
+                .set_label("HOT"),
+            ),
+        ])
+    }
+
+    /// Redraw the widgets whose value changed into the back buffer and present it.
+    ///
+    /// A redrawn widget's old and new area is cleared first, so every widget overlapping that
+    /// area is drawn again as well. Nothing is presented if nothing changed.
+    pub fn draw<F: FrameBufferInterface + ?Sized>(&mut self, fb: &mut F) {
+        let palette = self.palette;
+        if self.drawn.len() != self.widgets.len() {
+            fb.clear_screen();
+            if palette.background != BLACK_COLOR {
+                let (width, height) = (fb.width_u32(), fb.height_u32());
+                fb.draw_rect_fill(&Coordinates::new(0, 0), width, height, palette.background);
+            }
+            for widget in &self.widgets {
+                widget.draw(fb, &palette);
+            }
+            self.drawn = self.widgets.iter().map(Drawn::of).collect();
+            fb.update();
+            return;
+        }
+
+        let mut dirty: Vec<bool> = (self.widgets.iter().zip(&self.drawn))
+            .map(|(widget, drawn)| !drawn.shows(widget))
+            .collect();
+        if !dirty.contains(&true) {
+            return;
+        }
+        let areas: Vec<Rect> = (self.widgets.iter().zip(&self.drawn))
+            .map(|(widget, drawn)| drawn.bounds.union(&widget.bounds()))
+            .collect();
+        loop {
+            let mut spread = false;
+            for i in 0..areas.len() {
+                if !dirty[i] && (0..areas.len()).any(|j| dirty[j] && areas[j].intersects(&areas[i])) {
+                    dirty[i] = true;
+                    spread = true;
+                }
+            }
+            if !spread {
+                break;
+            }
+        }
+
+        let (width, height) = (fb.width() as u32, fb.height() as u32);
+        for (area, _) in areas.iter().zip(&dirty).filter(|(_, dirty)| **dirty) {
+            let area = area.clip(width, height);
+            let top_left = Coordinates::new(area.x as u32, area.y as u32);
+            fb.draw_rect_fill(&top_left, area.width, area.height, palette.background);
+            fb.add_damage(area);
+        }
+        for (i, widget) in self.widgets.iter().enumerate() {
+            if dirty[i] {
+                widget.draw(fb, &palette);
+                self.drawn[i] = Drawn::of(widget);
+            }
+        }
+        fb.update();
+    }
+}
+
+/// `value` with `decimals`, or dashes while the gauge cannot be trusted.
+fn format_value(value: f64, decimals: usize) -> String {
+    if value.is_finite() {
+        format!("{:.*}", decimals, value)
+    } else {
+        String::from("---")
+    }
+}
+
+/// Point at `radius` from `center`, `angle` in degrees clockwise from 12 o'clock.
+fn polar(center: &Coordinates, radius: f64, angle: f64) -> Coordinates {
+    let rad = angle.to_radians();
+    Coordinates {
+        virtual_x: center.virtual_x + radius * libm::sin(rad),
+        virtual_y: center.virtual_y - radius * libm::cos(rad),
+    }
+}
+
+/// Top left corner for `text` centred horizontally on `center_x`.
+fn centered(text: &str, scale: u32, center_x: f64, y: f64) -> Coordinates {
+    let width = (text.chars().count() * LETTER_WIDTH) as f64 * scale as f64;
+    Coordinates {
+        virtual_x: (center_x - width / 2.0).max(0.0),
+        virtual_y: y.max(0.0),
+    }
+}
+
+/// Draw `text` with every font pixel blown up to a `scale`×`scale` block.
+///
+/// Unknown characters use the fallback glyph and the background is left untouched.
+fn write_str_scaled<F: FrameBufferInterface + ?Sized>(
+    fb: &mut F,
+    text: &str,
+    coordinates: Coordinates,
+    scale: u32,
+    color: Color,
+) {
+    let scale = scale.max(1) as i32;
+    let (mut x, y) = (coordinates.x() as i32, coordinates.y() as i32);
+    for c in text.chars() {
+        let raster = Font::SMALL.glyph(c);
+        // Half the glyph's darkest pixel, so faint strokes like the minus sign show as well.
+        let darkest = raster.raster().iter().flat_map(|row| row.iter()).max().copied();
+        let threshold = darkest.map_or(0x80, |darkest| darkest.div_ceil(2).clamp(1, 0x80));
+        for (row_i, row) in raster.raster().iter().enumerate() {
+            for (col_i, intensity) in row.iter().enumerate() {
+                if *intensity < threshold {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        fb.use_pixel_clipped(
+                            x + col_i as i32 * scale + dx,
+                            y + row_i as i32 * scale + dy,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+        x += LETTER_WIDTH as i32 * scale;
+    }
+}
@@ -0,0 +1,73 @@
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use super::{Dashboard, Palette};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageAction {
+    Next,
+    Previous,
+    /// Back to the first page.
+    First,
+}
+
+/// A set of dashboards of which one is on screen.
+pub struct Pages {
+    pages: Vec<Dashboard>,
+    current: usize,
+}
+
+impl Pages {
+    /// `pages` must not be empty. An out of range `current` falls back to the first page.
+    pub fn new(pages: Vec<Dashboard>, current: usize) -> Self {
+        assert!(!pages.is_empty(), "at least one page is needed");
+        let current = if current < pages.len() { current } else { 0 };
+        Pages { pages, current }
+    }
+
+    pub fn current_mut(&mut self) -> &mut Dashboard {
+        &mut self.pages[self.current]
+    }
+
+    /// Animate the page on screen for time `now` and keep sampling the graphs on the others,
+    /// see [`Dashboard::animate`].
+    pub fn animate(&mut self, now: Duration) {
+        for (i, page) in self.pages.iter_mut().enumerate() {
+            if i == self.current {
+                page.animate(now);
+            } else {
+                page.sample(now);
+            }
+        }
+    }
+
+    /// Paint every page in `palette`, see [`Dashboard::set_palette`].
+    pub fn set_palette(&mut self, palette: Palette) {
+        for page in &mut self.pages {
+            page.set_palette(palette);
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        self.current
+    }
+
+    pub fn count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Switch page, wrapping around at both ends. Returns whether the page changed.
+    pub fn apply(&mut self, action: PageAction) -> bool {
+        let previous = self.current;
+        self.current = match action {
+            PageAction::Next => (self.current + 1) % self.pages.len(),
+            PageAction::Previous => (self.current + self.pages.len() - 1) % self.pages.len(),
+            PageAction::First => 0,
+        };
+        if self.current == previous {
+            return false;
+        }
+        self.pages[self.current].invalidate();
+        true
+    }
+}
@@ -0,0 +1,73 @@
+use alloc::rc::Rc;
+use alloc::string::String;
+
+use super::Fit;
+use crate::damage::Rect;
+use crate::fb_trait::{Coordinates, FrameBufferInterface};
+use crate::image::Image;
+
+/// Artwork from the SD card, e.g. a gauge face under a dial.
+///
+/// The layout only names the file, the image is handed over by
+/// [`super::Dashboard::load_images`]. Until then nothing is drawn.
+pub struct Picture {
+    pub file: String,
+    pub top_left: Coordinates,
+    /// Size on screen before fitting, the image's own size if `None`.
+    pub size: Option<(u32, u32)>,
+    /// How much fitting the layout to the screen grew or shrunk it.
+    pub scale: f64,
+    pub image: Option<Rc<Image>>,
+}
+
+impl Picture {
+    pub fn new(file: &str, top_left: Coordinates) -> Picture {
+        Picture {
+            file: file.into(),
+            top_left,
+            size: None,
+            scale: 1.0,
+            image: None,
+        }
+    }
+
+    pub fn set_size(self, width: u32, height: u32) -> Picture {
+        let mut picture = self;
+        picture.size = Some((width, height));
+        picture
+    }
+
+    pub fn set_image(self, image: Rc<Image>) -> Picture {
+        let mut picture = self;
+        picture.image = Some(image);
+        picture
+    }
+
+    pub fn fit(&mut self, fit: &Fit) {
+        self.top_left = fit.point(&self.top_left);
+        self.scale *= fit.scale;
+    }
+
+    /// The size on screen, `None` while there is no image and no size to go by.
+    fn screen_size(&self) -> Option<(u32, u32)> {
+        let (width, height) = self
+            .size
+            .or_else(|| self.image.as_ref().map(|image| (image.width(), image.height())))?;
+        let scaled = |length: u32| libm::round(length as f64 * self.scale).max(1.0) as u32;
+        Some((scaled(width), scaled(height)))
+    }
+
+    pub fn bounds(&self) -> Rect {
+        let (x, y) = (self.top_left.x() as i32, self.top_left.y() as i32);
+        let (width, height) = self.screen_size().unwrap_or((0, 0));
+        Rect::new(x, y, width, height)
+    }
+
+    pub fn draw<F: FrameBufferInterface + ?Sized>(&self, fb: &mut F) {
+        let (Some(image), Some((width, height))) = (&self.image, self.screen_size()) else {
+            return;
+        };
+        let (x, y) = (self.top_left.x() as i32, self.top_left.y() as i32);
+        image.draw_scaled(fb, x, y, width, height);
+    }
+}
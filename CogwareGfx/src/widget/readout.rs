@@ -0,0 +1,129 @@
+use alloc::string::String;
+
+use super::{
+    alarm_color, format_value, write_str_scaled, Alarm, Binding, Fit, Paint, Palette, Role,
+};
+use crate::damage::Rect;
+use crate::text::{measure_seven_segment, Align, TextRenderer};
+use crate::fb_trait::{Coordinates, FrameBufferInterface, LETTER_HEIGHT, LETTER_WIDTH};
+
+/// Large numeric value with a label above it and the unit after it.
+pub struct Readout {
+    pub binding: Binding,
+    pub top_left: Coordinates,
+    pub label: String,
+    pub unit: String,
+    pub decimals: usize,
+    /// Size of the value digits, as a multiple of the label font.
+    pub scale: u32,
+    pub color: Paint,
+    pub alarm: Option<Alarm>,
+    /// Draw the value as seven-segment digits instead of the bitmap font.
+    pub seven_segment: bool,
+}
+
+impl Readout {
+    pub fn new(binding: Binding, top_left: Coordinates) -> Readout {
+        Readout {
+            binding,
+            top_left,
+            label: String::new(),
+            unit: String::new(),
+            decimals: 0,
+            scale: 3,
+            color: Role::Foreground.into(),
+            alarm: None,
+            seven_segment: false,
+        }
+    }
+
+    pub fn set_label(self, label: &str) -> Readout {
+        let mut readout = self;
+        readout.label = label.into();
+        readout
+    }
+
+    pub fn set_unit(self, unit: &str) -> Readout {
+        let mut readout = self;
+        readout.unit = unit.into();
+        readout
+    }
+
+    pub fn set_decimals(self, decimals: usize) -> Readout {
+        let mut readout = self;
+        readout.decimals = decimals;
+        readout
+    }
+
+    pub fn set_scale(self, scale: u32) -> Readout {
+        let mut readout = self;
+        readout.scale = scale;
+        readout
+    }
+
+    pub fn set_color(self, color: impl Into<Paint>) -> Readout {
+        let mut readout = self;
+        readout.color = color.into();
+        readout
+    }
+
+    pub fn set_alarm(self, alarm: Alarm) -> Readout {
+        let mut readout = self;
+        readout.alarm = Some(alarm);
+        readout
+    }
+
+    pub fn set_seven_segment(self, seven_segment: bool) -> Readout {
+        let mut readout = self;
+        readout.seven_segment = seven_segment;
+        readout
+    }
+
+    /// Text is scaled in whole steps.
+    pub fn fit(&mut self, fit: &Fit) {
+        self.top_left = fit.point(&self.top_left);
+        self.scale = fit.length(self.scale);
+    }
+
+    /// Width of the digits for `text`.
+    fn digits_width(&self, text: &str) -> f64 {
+        if self.seven_segment {
+            measure_seven_segment(text, (LETTER_HEIGHT as u32 * self.scale) as f64)
+        } else {
+            (text.chars().count() * LETTER_WIDTH) as f64 * self.scale as f64
+        }
+    }
+
+    /// The label and the current value with its unit.
+    pub fn bounds(&self) -> Rect {
+        let text = format_value(self.binding.value(), self.decimals);
+        let unit_width = (self.unit.chars().count() * LETTER_WIDTH) as f64;
+        let label_width = (self.label.chars().count() * LETTER_WIDTH) as f64;
+        let width = label_width.max(self.digits_width(&text) + 4.0 + unit_width);
+        let height = LETTER_HEIGHT as u32 * (1 + self.scale.max(1)) + 2;
+        let (x, y) = (self.top_left.x() as i32, self.top_left.y() as i32);
+        Rect::new(x, y, libm::ceil(width) as u32 + 1, height)
+    }
+
+    pub fn draw<F: FrameBufferInterface + ?Sized>(&self, fb: &mut F, palette: &Palette) {
+        let value = self.binding.value();
+        let color = alarm_color(&self.alarm, value, self.color, palette);
+
+        write_str_scaled(fb, &self.label, self.top_left, 1, color);
+
+        let mut position = self.top_left;
+        position.virtual_y += LETTER_HEIGHT as f64;
+        let text = format_value(value, self.decimals);
+        if self.seven_segment {
+            let height = (LETTER_HEIGHT as u32 * self.scale) as f64;
+            fb.draw_seven_segment(&text, &position, height, Align::Left, color, None);
+        } else {
+            write_str_scaled(fb, &text, position, self.scale, color);
+        }
+
+        // The unit sits on the baseline of the digits.
+        position.virtual_x += self.digits_width(&text) + 4.0;
+        position.virtual_y += (LETTER_HEIGHT as u32 * (self.scale.max(1) - 1)) as f64;
+        write_str_scaled(fb, &self.unit, position, 1, color);
+    }
+}
@@ -0,0 +1,53 @@
+use alloc::string::String;
+
+use super::{write_str_scaled, Fit, Paint, Palette, Role};
+use crate::damage::Rect;
+use crate::fb_trait::{Coordinates, FrameBufferInterface, LETTER_HEIGHT, LETTER_WIDTH};
+
+/// Fixed text that is not bound to a gauge, e.g. a heading or a layout error.
+pub struct Text {
+    pub text: String,
+    pub top_left: Coordinates,
+    pub scale: u32,
+    pub color: Paint,
+}
+
+impl Text {
+    pub fn new(text: &str, top_left: Coordinates) -> Text {
+        Text {
+            text: text.into(),
+            top_left,
+            scale: 1,
+            color: Role::Foreground.into(),
+        }
+    }
+
+    pub fn set_scale(self, scale: u32) -> Text {
+        let mut text = self;
+        text.scale = scale;
+        text
+    }
+
+    pub fn set_color(self, color: impl Into<Paint>) -> Text {
+        let mut text = self;
+        text.color = color.into();
+        text
+    }
+
+    /// Text is scaled in whole steps.
+    pub fn fit(&mut self, fit: &Fit) {
+        self.top_left = fit.point(&self.top_left);
+        self.scale = fit.length(self.scale);
+    }
+
+    pub fn bounds(&self) -> Rect {
+        let (x, y) = (self.top_left.x() as i32, self.top_left.y() as i32);
+        let width = (self.text.chars().count() * LETTER_WIDTH) as u32 * self.scale.max(1);
+        Rect::new(x, y, width, LETTER_HEIGHT as u32 * self.scale.max(1))
+    }
+
+    pub fn draw<F: FrameBufferInterface + ?Sized>(&self, fb: &mut F, palette: &Palette) {
+        let color = self.color.color(palette);
+        write_str_scaled(fb, &self.text, self.top_left, self.scale, color);
+    }
+}
@@ -0,0 +1,262 @@
+//! Colour themes: widgets are painted in palette roles instead of fixed colours, so switching
+//! between the day and the night palette recolours the whole dashboard.
+//!
+//! The day palette is the stock look, the night one is dimmer so it does not glare in the dark.
+//! Both can be changed with a theme file, see [`super::parse_theme`].
+
+use core::time::Duration;
+
+use crate::fb_trait::{Color, BLACK_COLOR, WHITE_COLOR};
+
+/// What a colour is for, looked up in the palette shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Background,
+    /// Text, dial faces and borders.
+    Foreground,
+    /// Unlit lamps and grid lines.
+    Dim,
+    /// Bar fills and graph traces.
+    Accent,
+    Warning,
+    Critical,
+}
+
+impl Role {
+    pub const ALL: [Role; 6] = [
+        Role::Background,
+        Role::Foreground,
+        Role::Dim,
+        Role::Accent,
+        Role::Warning,
+        Role::Critical,
+    ];
+
+    /// The name used in layout and theme files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::Background => "background",
+            Role::Foreground => "foreground",
+            Role::Dim => "dim",
+            Role::Accent => "accent",
+            Role::Warning => "warning",
+            Role::Critical => "critical",
+        }
+    }
+
+    /// Case-insensitive.
+    pub fn from_name(name: &str) -> Option<Role> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// The colour of a part of a widget: a palette role, or a colour that stays the same in every
+/// palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Paint {
+    Role(Role),
+    Fixed(Color),
+}
+
+impl Paint {
+    pub fn color(&self, palette: &Palette) -> Color {
+        match self {
+            Paint::Role(role) => palette.color(*role),
+            Paint::Fixed(color) => *color,
+        }
+    }
+}
+
+impl From<Role> for Paint {
+    fn from(role: Role) -> Paint {
+        Paint::Role(role)
+    }
+}
+
+impl From<Color> for Paint {
+    fn from(color: Color) -> Paint {
+        Paint::Fixed(color)
+    }
+}
+
+/// A colour for every [`Role`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub background: Color,
+    pub foreground: Color,
+    pub dim: Color,
+    pub accent: Color,
+    pub warning: Color,
+    pub critical: Color,
+}
+
+impl Palette {
+    pub const DAY: Palette = Palette {
+        background: BLACK_COLOR,
+        foreground: WHITE_COLOR,
+        dim: Color::new(40, 40, 40),
+        accent: Color::new(0, 200, 0),
+        warning: Color::new(255, 170, 0),
+        critical: Color::new(255, 0, 0),
+    };
+
+    pub const NIGHT: Palette = Palette {
+        background: BLACK_COLOR,
+        foreground: Color::new(150, 150, 150),
+        dim: Color::new(24, 24, 24),
+        accent: Color::new(0, 110, 0),
+        warning: Color::new(170, 110, 0),
+        critical: Color::new(190, 0, 0),
+    };
+
+    pub fn color(&self, role: Role) -> Color {
+        match role {
+            Role::Background => self.background,
+            Role::Foreground => self.foreground,
+            Role::Dim => self.dim,
+            Role::Accent => self.accent,
+            Role::Warning => self.warning,
+            Role::Critical => self.critical,
+        }
+    }
+
+    pub fn set_color(&mut self, role: Role, color: Color) {
+        let slot = match role {
+            Role::Background => &mut self.background,
+            Role::Foreground => &mut self.foreground,
+            Role::Dim => &mut self.dim,
+            Role::Accent => &mut self.accent,
+            Role::Warning => &mut self.warning,
+            Role::Critical => &mut self.critical,
+        };
+        *slot = color;
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::DAY
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThemeMode {
+    #[default]
+    Day,
+    Night,
+}
+
+impl ThemeMode {
+    pub fn toggled(&self) -> ThemeMode {
+        match self {
+            ThemeMode::Day => ThemeMode::Night,
+            ThemeMode::Night => ThemeMode::Day,
+        }
+    }
+}
+
+/// The day and the night palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub day: Palette,
+    pub night: Palette,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            day: Palette::DAY,
+            night: Palette::NIGHT,
+        }
+    }
+}
+
+impl Theme {
+    pub fn palette(&self, mode: ThemeMode) -> &Palette {
+        match mode {
+            ThemeMode::Day => &self.day,
+            ThemeMode::Night => &self.night,
+        }
+    }
+}
+
+/// Night from one time of day to another, e.g. from 19:30 to 07:00.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schedule {
+    /// Since midnight.
+    pub night_from: Duration,
+    pub day_from: Duration,
+}
+
+impl Schedule {
+    /// The mode at `time`, since midnight. Times past a day wrap around.
+    pub fn mode_at(&self, time: Duration) -> ThemeMode {
+        let day = 24 * 60 * 60;
+        let (time, night_from, day_from) = (
+            time.as_secs() % day,
+            self.night_from.as_secs() % day,
+            self.day_from.as_secs() % day,
+        );
+        let night = if night_from <= day_from {
+            (night_from..day_from).contains(&time)
+        } else {
+            time >= night_from || time < day_from
+        };
+        if night {
+            ThemeMode::Night
+        } else {
+            ThemeMode::Day
+        }
+    }
+}
+
+/// `HH:MM` as the time since midnight.
+pub fn parse_time_of_day(value: &str) -> Option<Duration> {
+    let (hours, minutes) = value.trim().split_once(':')?;
+    let (hours, minutes): (u64, u64) = (hours.parse().ok()?, minutes.parse().ok()?);
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some(Duration::from_secs((hours * 60 + minutes) * 60))
+}
+
+/// Decides between day and night. A manual switch holds until the automatic source, the
+/// headlights or the clock, changes its mind.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThemeSwitch {
+    mode: ThemeMode,
+    /// What the automatic source said last.
+    automatic: Option<ThemeMode>,
+}
+
+impl ThemeSwitch {
+    pub fn new(mode: ThemeMode) -> ThemeSwitch {
+        ThemeSwitch {
+            mode,
+            automatic: None,
+        }
+    }
+
+    pub fn mode(&self) -> ThemeMode {
+        self.mode
+    }
+
+    /// Switch by hand, e.g. from a button.
+    pub fn toggle(&mut self) -> ThemeMode {
+        self.mode = self.mode.toggled();
+        self.mode
+    }
+
+    /// Follow `automatic`, `None` if there is no automatic source. Returns the mode to show.
+    pub fn update(&mut self, automatic: Option<ThemeMode>) -> ThemeMode {
+        if let Some(mode) = automatic {
+            if self.automatic != Some(mode) {
+                self.mode = mode;
+            }
+        }
+        self.automatic = automatic;
+        self.mode
+    }
+}
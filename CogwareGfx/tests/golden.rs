@@ -0,0 +1,855 @@
+//! Golden image tests: dashboards are rendered into a `SoftFrameBuffer` and compared pixel by
+//! pixel with the PNGs in `tests/golden`.
+//!
+//! Run them with `make test_gfx`. A missing golden image fails the test like a different one.
+//! For a new test, or after an intended change to the drawing code, write them with
+//! `UPDATE_GOLDEN=1 make test_gfx` and look at the new images before committing them.
+
+use std::{
+    env, fs,
+    fmt::Write,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+use cogware_can::{set_e2e, Gauge};
+use embedded_graphics::{
+    mono_font::{ascii::FONT_10X20, MonoTextStyle},
+    pixelcolor::Rgb888,
+    prelude::*,
+    primitives::{Circle, PrimitiveStyle, Rectangle},
+    text::Text,
+};
+use cogware_gfx::animation::{ease, AlarmEffect};
+use cogware_gfx::console::{PanicReport, TextConsole};
+use cogware_gfx::damage::Rect;
+use cogware_gfx::draw_target::Canvas;
+use cogware_gfx::history::History;
+use cogware_gfx::fb_trait::{Color, Coordinates, FrameBufferInterface, BLACK_COLOR, WHITE_COLOR};
+use cogware_gfx::image::{Image, ImageError, MAX_IMAGE_SIZE};
+use cogware_gfx::mask::Shape;
+use cogware_gfx::screenshot::{self, Format, STREAM_BEGIN, STREAM_END};
+use cogware_gfx::soft::SoftFrameBuffer;
+use cogware_gfx::text::{Font, TextRenderer, FALLBACK_GLYPH};
+use cogware_gfx::transform::{Rotation, Transform, Transformed};
+use cogware_gfx::widget::{
+    error_dashboard, parse_layout, parse_theme, parse_time_of_day, Dashboard, LayoutErrorKind,
+    Paint, Palette, Role, Schedule, ThemeMode, ThemeSwitch, Widget,
+};
+
+const WIDTH: u32 = 480;
+const HEIGHT: u32 = 480;
+
+/// Every widget kind with most of its settings.
+const WIDGETS_LAYOUT: &str = r#"
+# All widget kinds
+text           x=16 y=8 label="Track day" size=2 color=yellow
+dial    RPM    x=160 y=150 r=110 min=0 max=8000 ticks=8 label=RPM alarm_above=7000 segments
+dial    MAP    x=380 y=110 r=80 max=250 ticks=5 start=-90 sweep=180 needle=orange label=kPa
+readout CLNT   x=300 y=220 scale=2 offset=-91 label=CLNT unit=C size=2 alarm_above=105
+readout BatVol x=300 y=290 scale=0.1 label=BATT unit=V decimals=1 segments size=2
+bar     TPS    x=16 y=436 w=300 h=24 min=0 max=100 color=#00C800
+bar     IAT    x=440 y=200 w=20 h=200 min=0 max=120 offset=-40 vertical
+lamp    BatVol x=376 y=424 r=14 scale=0.1 alarm_below=12 alarm_color=amber label=BATT
+lamp    CLNT   x=436 y=424 r=14 scale=2 offset=-91 alarm_above=105 label=HOT
+"#;
+
+/// A dashboard for the HyperPixel 2.1 Round.
+const ROUND_LAYOUT: &str = r#"
+screen round
+dial    RPM    x=240 y=200 r=150 min=0 max=8000 ticks=8 label=RPM alarm_above=7000
+readout CLNT   x=170 y=370 scale=2 offset=-91 label=CLNT unit=C size=2
+"#;
+
+/// A dial drawn over a gauge face.
+const FACE_LAYOUT: &str = r#"
+image   FACE.QOI x=90 y=50 w=300 h=300
+dial    RPM    x=240 y=200 r=140 min=0 max=8000 ticks=8 label=RPM
+"#;
+
+/// A dial with needle artwork that reads the raw value in degrees.
+const NEEDLE_LAYOUT: &str = r#"
+dial    RPM    x=240 y=240 r=150 max=270 start=0 sweep=270 needle_image=NEEDLE.QOI pivot_x=2.5 pivot_y=55
+"#;
+
+/// A damped needle and a flashing warning lamp.
+const ANIMATED_LAYOUT: &str = r#"
+dial    RPM    x=240 y=200 r=150 max=8000 damping=0.2
+lamp    CLNT   x=240 y=420 r=20 alarm_above=100 alarm_effect=flash label=HOT
+"#;
+
+/// AFR against its target on a fixed scale, and boost scaled to fit.
+const GRAPH_LAYOUT: &str = r#"
+graph   AfrPri x=20 y=40 w=440 h=182 scale=0.1 trace=AfrTarget min=10 max=20 samples=50 label=AFR
+graph   MAP    x=20 y=260 w=440 h=180 rate=20 label=MAP
+"#;
+
+/// The gauges live in global statics, so tests that set them must not run at the same time.
+static GAUGES: Mutex<()> = Mutex::new(());
+
+/// Gauges read by the layouts above.
+const USED_GAUGES: [Gauge; 9] = [
+    Gauge::RPM,
+    Gauge::MAP,
+    Gauge::AfrPri,
+    Gauge::CLNT,
+    Gauge::IAT,
+    Gauge::BatVol,
+    Gauge::StaTime,
+    Gauge::TPS,
+    Gauge::AfrTarget,
+];
+
+/// Zero every used gauge, then set `values`. Hold the guard while rendering.
+fn set_gauges(values: &[(Gauge, u32)]) -> MutexGuard<'static, ()> {
+    let guard = GAUGES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    for gauge in USED_GAUGES {
+        gauge.set(0);
+        gauge.reset_e2e();
+    }
+    for (gauge, value) in values {
+        gauge.set(*value);
+    }
+    guard
+}
+
+/// A car at speed: 7500 rpm sets off the RPM alarm, about 15 psi of boost, 11.8 V lights BATT.
+const RUNNING: [(Gauge, u32); 8] = [
+    (Gauge::RPM, 7500),
+    (Gauge::MAP, 205),
+    (Gauge::AfrPri, 118),
+    (Gauge::CLNT, 98),
+    (Gauge::IAT, 70),
+    (Gauge::BatVol, 118),
+    (Gauge::StaTime, 42),
+    (Gauge::TPS, 85),
+];
+
+fn render(dashboard: &mut Dashboard) -> SoftFrameBuffer {
+    render_at(dashboard, WIDTH, HEIGHT)
+}
+
+/// Render on a screen of another size, scaling the layout to fit.
+fn render_at(dashboard: &mut Dashboard, width: u32, height: u32) -> SoftFrameBuffer {
+    let mut fb = SoftFrameBuffer::new(width, height);
+    dashboard.fit(width, height);
+    dashboard.draw(&mut fb);
+    fb
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name))
+}
+
+/// Compare with the golden image `name`, saving the rendering next to the build output when
+/// it differs or there is no golden image. `UPDATE_GOLDEN` writes the golden image instead.
+fn check(name: &str, fb: &SoftFrameBuffer) {
+    let golden = golden_path(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        fb.save(&golden).unwrap();
+        eprintln!("wrote {}", golden.display());
+        return;
+    }
+
+    let actual = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.png", name));
+    if !golden.exists() {
+        fb.save(&actual).unwrap();
+        panic!(
+            "there is no golden image {}, the rendering is in {}",
+            golden.display(),
+            actual.display()
+        );
+    }
+    let expected = SoftFrameBuffer::load_png(&golden)
+        .unwrap_or_else(|e| panic!("{}: {}", golden.display(), e));
+    let diff = fb.diff(&expected);
+    if diff != 0 {
+        fb.save(&actual).unwrap();
+        panic!(
+            "{} pixels differ from {}, the rendering is in {}",
+            diff,
+            golden.display(),
+            actual.display()
+        );
+    }
+}
+
+#[test]
+fn default_layout_idle() {
+    let _gauges = set_gauges(&[]);
+    check("default_idle", &render(&mut Dashboard::default_layout()));
+}
+
+#[test]
+fn default_layout_running() {
+    let _gauges = set_gauges(&RUNNING);
+    check("default_running", &render(&mut Dashboard::default_layout()));
+}
+
+#[test]
+fn untrusted_gauges_show_dashes() {
+    let _gauges = set_gauges(&RUNNING);
+    set_e2e(true);
+    // RPM, CLNT, IAT and TPS have not passed a check yet.
+    for gauge in [Gauge::MAP, Gauge::AfrPri, Gauge::BatVol, Gauge::StaTime] {
+        gauge.receive(&gauge.to_frame().unwrap());
+    }
+    let fb = render(&mut Dashboard::default_layout());
+    set_e2e(false);
+    check("default_untrusted", &fb);
+}
+
+#[test]
+fn default_layout_scaled_for_hdmi() {
+    let _gauges = set_gauges(&RUNNING);
+    let mut dashboard = Dashboard::default_layout();
+    check("default_800x480", &render_at(&mut dashboard, 800, 480));
+    check("default_1024x600", &render_at(&mut dashboard, 1024, 600));
+}
+
+#[test]
+fn wide_layout_on_square_screen() {
+    let _gauges = set_gauges(&RUNNING);
+    let text = "screen w=800 h=480\nbar TPS x=0 y=200 w=800 h=80 max=100\n";
+    check("wide_on_square", &render(&mut parse_layout(text).unwrap()));
+}
+
+#[test]
+fn every_widget_kind() {
+    let _gauges = set_gauges(&RUNNING);
+    let mut dashboard = parse_layout(WIDGETS_LAYOUT).unwrap();
+    check("widgets", &render(&mut dashboard));
+}
+
+#[test]
+fn layout_errors() {
+    let text = "dial FOO r=10 max=1\ngadget RPM\nbar TPS w=10 h=10\nreadout RPM y=q colour=red\n\
+                screen w=0 h=480\nlamp BatVol x=500 y=100 r=10 alarm_below=12\n";
+    let errors = parse_layout(text).err().unwrap();
+    assert_eq!(errors.last().unwrap().kind, LayoutErrorKind::NotVisible);
+    check("errors", &render(&mut error_dashboard("LAYOUT.TXT", &errors)));
+}
+
+#[test]
+fn partial_redraw_matches_full_redraw() {
+    let _gauges = set_gauges(&[]);
+    let mut dashboard = Dashboard::default_layout();
+    let mut fb = render(&mut dashboard);
+
+    dashboard.draw(&mut fb);
+    assert_eq!(fb.frames, 1, "nothing changed, nothing to present");
+
+    Gauge::TPS.set(60);
+    Gauge::RPM.set(3000);
+    dashboard.draw(&mut fb);
+    assert_eq!(fb.frames, 2);
+    assert!(!fb.presented.is_full());
+    assert_eq!(fb.diff(&render(&mut Dashboard::default_layout())), 0);
+}
+
+/// Dials too small for their label and value, which keep their size.
+const SMALL_DIALS_LAYOUT: &str = r#"
+dial    RPM    x=60 y=60 r=30 min=0 max=8000 label=RPM
+dial    MAP    x=180 y=60 r=30 max=250 label=kPa segments
+"#;
+
+#[test]
+fn small_dial_text_is_redrawn() {
+    let _gauges = set_gauges(&[(Gauge::RPM, 7500), (Gauge::MAP, 180)]);
+    let mut dashboard = parse_layout(SMALL_DIALS_LAYOUT).unwrap();
+    let mut fb = render(&mut dashboard);
+
+    Gauge::RPM.set(900);
+    Gauge::MAP.set(95);
+    dashboard.draw(&mut fb);
+    assert!(!fb.presented.is_full());
+    assert_eq!(fb.diff(&render(&mut parse_layout(SMALL_DIALS_LAYOUT).unwrap())), 0);
+}
+
+#[test]
+fn round_panel_rotated_and_mirrored() {
+    let _gauges = set_gauges(&RUNNING);
+    let mut dashboard = parse_layout(ROUND_LAYOUT).unwrap();
+    assert_eq!(dashboard.shape, Shape::Round);
+
+    let transform = Transform::new(Rotation::Deg90, true);
+    let panel = SoftFrameBuffer::new(WIDTH, HEIGHT);
+    let mut fb = Transformed::new(panel, transform).set_shape(Shape::Round);
+    dashboard.fit(fb.width_u32(), fb.height_u32());
+    // Something in the corner that the panel cannot show.
+    fb.draw_rect_fill(&Coordinates::new(0, 0), 40, 40, WHITE_COLOR);
+    fb.add_damage(Rect::new(0, 0, 40, 40));
+    dashboard.draw(&mut fb);
+
+    assert_eq!(fb.inner.pixel(0, 0), BLACK_COLOR);
+    assert_eq!(fb.inner.pixel(WIDTH - 1, 0), BLACK_COLOR);
+    check("round_rot90_mirror", &fb.inner);
+}
+
+#[test]
+fn rotation_moves_the_top_left_corner() {
+    // A 4x2 panel, on its side for 90 and 270 degrees.
+    let cases = [
+        (Rotation::Deg0, false, (0, 0)),
+        (Rotation::Deg90, false, (3, 0)),
+        (Rotation::Deg180, false, (3, 1)),
+        (Rotation::Deg270, false, (0, 1)),
+        (Rotation::Deg0, true, (3, 0)),
+        (Rotation::Deg90, true, (3, 1)),
+    ];
+    for (rotation, mirror, (x, y)) in cases {
+        let transform = Transform::new(rotation, mirror);
+        let mut fb = Transformed::new(SoftFrameBuffer::new(4, 2), transform);
+        assert_eq!(
+            (fb.width_u32(), fb.height_u32()),
+            transform.upright_size(4, 2)
+        );
+        fb.use_pixel(0, 0, WHITE_COLOR);
+        fb.update();
+        assert_eq!(fb.inner.pixel(x, y), WHITE_COLOR, "{:?} mirror={}", rotation, mirror);
+        let lit = fb.inner.pixels().iter().filter(|p| **p & 0xFF_FFFF != 0);
+        assert_eq!(lit.count(), 1, "{:?} mirror={}", rotation, mirror);
+    }
+}
+
+#[test]
+fn round_layout_rejects_widgets_in_the_corners() {
+    let text = "screen round\ntext x=4 y=4 label=HELLO\nlamp BatVol x=240 y=240 r=14 alarm_below=12\n\
+                dial RPM x=400 y=400 r=60 max=8000\n";
+    let errors = parse_layout(text).err().unwrap();
+    let lines: Vec<_> = errors.iter().map(|e| (e.line, e.kind.clone())).collect();
+    assert_eq!(
+        lines,
+        [(2, LayoutErrorKind::NotVisible), (4, LayoutErrorKind::NotVisible)]
+    );
+
+    // The same widgets are fine on a square screen.
+    assert!(parse_layout(&text.replace(" round", "")).is_ok());
+}
+
+/// A gauge face: an opaque ring with a see-through centre, half transparent in between.
+fn gauge_face() -> Image {
+    let size = 60;
+    let pixels = (0..size * size)
+        .map(|i| {
+            let (x, y) = ((i % size) as f64 - 29.5, (i / size) as f64 - 29.5);
+            let alpha = match libm::hypot(x, y) {
+                d if d > 30.0 => 0,
+                d if d > 24.0 => 0xFF,
+                d if d > 20.0 => 0x80,
+                _ => 0,
+            };
+            alpha << 24 | 0x2040A0
+        })
+        .collect();
+    Image::from_argb(size, size, pixels).unwrap()
+}
+
+#[test]
+fn image_widget_under_a_dial() {
+    let _gauges = set_gauges(&RUNNING);
+    let mut dashboard = parse_layout(FACE_LAYOUT).unwrap();
+    let face = Rc::new(gauge_face());
+    let mut requested = Vec::new();
+    dashboard.load_images(|file| {
+        requested.push(file.to_string());
+        Some(face.clone())
+    });
+    assert_eq!(requested, ["FACE.QOI"]);
+
+    let fb = render(&mut dashboard);
+    // The ring is opaque, the corners and the middle show the background.
+    let ring = Color::from_rgb(0x2040A0);
+    assert_eq!(fb.pixel(90 + 150, 50 + 5), ring);
+    assert_eq!(fb.pixel(91, 51), BLACK_COLOR);
+    let between = fb.pixel(90 + 150, 50 + 5 * 5 + 9);
+    assert_eq!(between, BLACK_COLOR.blend(ring, 0x80));
+    check("image_under_dial", &fb);
+}
+
+#[test]
+fn images_are_decoded_and_validated() {
+    let rgba = [255, 0, 0, 255, 0, 255, 0, 128, 0, 0, 255, 0, 255, 255, 255, 255];
+    let data = qoi::encode_to_vec(rgba, 2, 2).unwrap();
+    let image = Image::from_qoi(&data).unwrap();
+    assert_eq!((image.width(), image.height()), (2, 2));
+    assert_eq!(image.pixels(), [0xFFFF0000, 0x8000FF00, 0x000000FF, 0xFFFFFFFF]);
+    assert!(!image.is_opaque());
+
+    // Opaque images are copied, transparent pixels keep what was there.
+    let mut fb = SoftFrameBuffer::new(4, 4);
+    fb.draw_rect_fill(&Coordinates::new(0, 0), 4, 4, WHITE_COLOR);
+    image.draw(&mut fb, 3, 3);
+    assert_eq!(fb.pixel(3, 3), Color::new(255, 0, 0));
+    image.draw(&mut fb, -1, 0);
+    assert_eq!(fb.pixel(0, 0), WHITE_COLOR.blend(Color::new(0, 255, 0), 128));
+    assert_eq!(fb.pixel(0, 1), Color::new(255, 255, 255));
+
+    assert!(matches!(Image::from_qoi(b"BM not a qoi"), Err(ImageError::Qoi(_))));
+    let mut huge = data.clone();
+    huge[4..8].copy_from_slice(&(MAX_IMAGE_SIZE + 1).to_be_bytes());
+    assert!(matches!(Image::from_qoi(&huge), Err(ImageError::TooLarge { .. })));
+    assert!(matches!(Image::from_argb(0, 4, vec![]), Err(ImageError::Empty)));
+    assert!(matches!(
+        Image::from_argb(2, 2, vec![0; 3]),
+        Err(ImageError::BadLength { expected: 4, actual: 3 })
+    ));
+
+    let errors = parse_layout("image x=0 y=0").err().unwrap();
+    assert_eq!(errors[0].kind, LayoutErrorKind::MissingImage);
+}
+
+/// A white needle 6 pixels wide, pointing up from a pivot 55 pixels down.
+fn needle_art() -> Rc<Image> {
+    Rc::new(Image::from_argb(6, 60, vec![0xFFFFFFFF; 6 * 60]).unwrap())
+}
+
+fn needle_dashboard() -> Dashboard {
+    let mut dashboard = parse_layout(NEEDLE_LAYOUT).unwrap();
+    let needle = needle_art();
+    dashboard.load_images(|_| Some(needle.clone()));
+    dashboard
+}
+
+#[test]
+fn needle_art_turns_around_its_pivot() {
+    let _gauges = set_gauges(&[(Gauge::RPM, 90)]);
+    let mut dashboard = needle_dashboard();
+    let mut fb = render(&mut dashboard);
+    // Pointing at 3 o'clock, smooth along the edges at any angle.
+    assert_eq!(fb.pixel(270, 240), WHITE_COLOR);
+    assert_eq!(fb.pixel(240, 210), BLACK_COLOR);
+    check("needle_art_90", &fb);
+
+    Gauge::RPM.set(135);
+    dashboard.draw(&mut fb);
+    let diagonal = fb.pixel(240 + 28, 240 + 24);
+    assert!(diagonal != WHITE_COLOR && diagonal != BLACK_COLOR);
+    // Only the old and the new needle were redrawn, and nothing of the old one is left.
+    assert!(!fb.presented.is_full());
+    assert_eq!(fb.diff(&render(&mut needle_dashboard())), 0);
+}
+
+#[test]
+fn images_are_clipped_at_the_screen_edge() {
+    let mut fb = SoftFrameBuffer::new(WIDTH, HEIGHT);
+    let red = Color::new(255, 0, 0);
+    let image = vec![red.rgb(); 10 * 10];
+    fb.display_image(&Coordinates::new(WIDTH - 5, HEIGHT - 5), &image, 10, 10);
+    assert_eq!(fb.pixel(WIDTH - 1, HEIGHT - 1), red);
+    assert_eq!(fb.pixel(WIDTH - 6, HEIGHT - 1), BLACK_COLOR);
+
+    // Fewer pixels than the size says: only the whole lines are drawn.
+    fb.display_image(&Coordinates::new(0, 0), &image[..25], 10, 10);
+    assert_eq!(fb.pixel(9, 1), red);
+    assert_eq!(fb.pixel(0, 2), BLACK_COLOR);
+
+    // Transparent pixels keep what is underneath.
+    fb.display_image(&Coordinates::new(0, 0), &[0x80FFFFFF, 0], 2, 1);
+    assert_eq!(fb.pixel(0, 0), red.blend(WHITE_COLOR, 0x80));
+    assert_eq!(fb.pixel(1, 0), red);
+
+    // Turned and scaled half off the screen.
+    let art = needle_art();
+    art.draw_rotated(&mut fb, (2.5, 55.0), &Coordinates::new(2, 2), 180.0, 2.0);
+    assert_eq!(fb.pixel(2, 40), WHITE_COLOR);
+
+    // Blending reads back what is underneath, off the screen too.
+    assert_eq!(fb.get_pixel(WIDTH as usize, 0), BLACK_COLOR);
+    assert_eq!(fb.get_pixel(0, HEIGHT as usize), BLACK_COLOR);
+}
+
+#[test]
+fn embedded_graphics_drawing_is_presented() {
+    let panel = SoftFrameBuffer::new(WIDTH, HEIGHT);
+    let mut fb = Transformed::new(panel, Transform::new(Rotation::Deg0, false));
+    fb.update();
+    let mut canvas = Canvas::new(&mut fb);
+    let fill = PrimitiveStyle::with_fill(Rgb888::new(0, 200, 0));
+    // fill_solid, fill_contiguous half off the screen and draw_iter.
+    let rectangle = Rectangle::new(Point::new(20, 20), Size::new(100, 60));
+    rectangle.into_styled(fill).draw(&mut canvas).unwrap();
+    let stripes = (0..40 * 40).map(|i| if i % 2 == 0 { Rgb888::RED } else { Rgb888::WHITE });
+    let corner = Rectangle::new(Point::new(WIDTH as i32 - 20, 200), Size::new(40, 40));
+    canvas.fill_contiguous(&corner, stripes).unwrap();
+    let outline = PrimitiveStyle::with_stroke(Rgb888::new(255, 230, 0), 3);
+    Circle::new(Point::new(200, 300), 120).into_styled(outline).draw(&mut canvas).unwrap();
+    let text = MonoTextStyle::new(&FONT_10X20, Rgb888::WHITE);
+    Text::new("embedded-graphics", Point::new(150, 120), text).draw(&mut canvas).unwrap();
+    fb.update();
+    check("embedded_graphics", &fb.inner);
+}
+
+#[test]
+fn unknown_characters_use_the_fallback_glyph() {
+    let draw = |c: char| {
+        let mut fb = SoftFrameBuffer::new(40, 40);
+        fb.draw_glyph(c, 4, 4, &Font::LARGE, WHITE_COLOR);
+        fb
+    };
+    let unknown = draw('\u{4E2D}');
+    assert_eq!(unknown.diff(&draw(FALLBACK_GLYPH)), 0);
+    assert_ne!(unknown.diff(&draw('?')), 0);
+    assert_ne!(unknown.diff(&SoftFrameBuffer::new(40, 40)), 0);
+}
+
+#[test]
+fn widgets_are_clipped_at_the_screen_edge() {
+    let _gauges = set_gauges(&RUNNING);
+    // Made for a bigger screen and drawn unscaled, the bar hangs off the bottom and the graph
+    // off the bottom right corner.
+    let text = "screen w=600 h=600\nbar TPS x=300 y=470 w=100 h=24 max=100\n\
+                graph RPM x=440 y=440 w=100 h=100 max=8000\n";
+    let mut dashboard = parse_layout(text).unwrap();
+    let mut fb = SoftFrameBuffer::new(WIDTH, HEIGHT);
+    for tick in 0..100u64 {
+        Gauge::RPM.set(1000 + tick as u32 * 50);
+        dashboard.animate(Duration::from_millis(tick * 50));
+    }
+    dashboard.draw(&mut fb);
+    assert_eq!(fb.pixel(300, 470), WHITE_COLOR);
+    assert_ne!(fb.pixel(302, HEIGHT - 1), BLACK_COLOR);
+    assert_ne!(fb.pixel(WIDTH - 1, 440), BLACK_COLOR);
+    assert_ne!(fb.pixel(440, HEIGHT - 1), BLACK_COLOR);
+}
+
+#[test]
+fn easing_does_not_depend_on_the_frame_rate() {
+    let _gauges = set_gauges(&[]);
+    let mut fast = parse_layout(ANIMATED_LAYOUT).unwrap();
+    let mut slow = parse_layout(ANIMATED_LAYOUT).unwrap();
+    fast.animate(Duration::ZERO);
+    slow.animate(Duration::ZERO);
+
+    Gauge::RPM.set(6000);
+    for frame in 1..=30 {
+        fast.animate(Duration::from_millis(frame * 1000 / 60));
+    }
+    for frame in 1..=10 {
+        slow.animate(Duration::from_millis(frame * 1000 / 20));
+    }
+    let (fast, slow) = (fast.widgets[0].value().unwrap(), slow.widgets[0].value().unwrap());
+    assert!((fast - slow).abs() < 1e-6, "{} at 60 fps, {} at 20 fps", fast, slow);
+    // Half a second is 2.5 time constants in.
+    let expected = 6000.0 * (1.0 - (-2.5f64).exp());
+    assert!((fast - expected).abs() < 1e-6);
+    assert_eq!(ease(10.0, 20.0, 0.0, Duration::from_millis(1)), 20.0);
+}
+
+#[test]
+fn power_on_sweep_goes_to_the_maximum_and_back() {
+    let _gauges = set_gauges(&RUNNING);
+    let mut dashboard = Dashboard::default_layout().set_sweep(Duration::from_secs(2));
+    let rpm = |dashboard: &Dashboard| dashboard.widgets[0].value().unwrap();
+
+    dashboard.animate(Duration::from_secs(5));
+    assert_eq!(rpm(&dashboard), 0.0);
+    dashboard.animate(Duration::from_millis(5500));
+    assert!(rpm(&dashboard) > 0.0 && rpm(&dashboard) < 8000.0);
+    dashboard.animate(Duration::from_secs(6));
+    assert_eq!(rpm(&dashboard), 8000.0);
+    dashboard.animate(Duration::from_secs(7));
+    assert!(dashboard.sweep.is_none());
+    assert!((rpm(&dashboard) - 7500.0).abs() < 1e-3);
+
+    // Readouts have no range to sweep and show their value throughout.
+    let mut dashboard = Dashboard::default_layout().set_sweep(Duration::from_secs(2));
+    dashboard.animate(Duration::from_millis(500));
+    assert_eq!(dashboard.widgets[3].value(), Some(98.0 * 2.0 - 91.0));
+}
+
+#[test]
+fn flashing_alarm_is_redrawn() {
+    let _gauges = set_gauges(&[(Gauge::CLNT, 120)]);
+    let mut dashboard = parse_layout(ANIMATED_LAYOUT).unwrap();
+    let red = Color::new(255, 0, 0);
+    dashboard.animate(Duration::ZERO);
+    let mut fb = render(&mut dashboard);
+    assert_eq!(fb.pixel(240, 420), red);
+
+    dashboard.animate(Duration::from_millis(400));
+    dashboard.draw(&mut fb);
+    assert_eq!(fb.frames, 2);
+    assert_eq!(fb.pixel(240, 420), Color::new(40, 40, 40));
+    assert!(!fb.presented.is_full());
+
+    // Only the lamp flashes, the dial is left alone.
+    dashboard.animate(Duration::from_millis(650));
+    dashboard.draw(&mut fb);
+    assert_eq!(fb.pixel(240, 420), red);
+    let dial = dashboard.widgets[0].bounds();
+    assert!(!fb.presented.rects().iter().any(|rect| rect.intersects(&dial)));
+
+    assert_eq!(AlarmEffect::Steady.level(Duration::from_millis(400)), 255);
+    assert_eq!(AlarmEffect::Pulse.level(Duration::ZERO), 255);
+    assert!(AlarmEffect::Pulse.level(Duration::from_millis(600)) < 128);
+}
+
+#[test]
+fn history_keeps_the_latest_samples_evenly_spaced() {
+    let mut history = History::new(4, Duration::from_millis(100));
+    assert_eq!(history.record(Duration::ZERO, 1.0), 1);
+    assert_eq!(history.record(Duration::from_millis(50), 2.0), 0);
+    assert_eq!(history.record(Duration::from_millis(100), 3.0), 1);
+    // A late frame fills in the sample it missed.
+    assert_eq!(history.record(Duration::from_millis(350), 4.0), 2);
+    assert_eq!(history.iter().collect::<Vec<_>>(), [1.0, 3.0, 4.0, 4.0]);
+    assert_eq!(history.record(Duration::from_millis(400), f64::NAN), 1);
+    assert_eq!(history.len(), 4);
+    assert_eq!(history.iter().next(), Some(3.0));
+    assert!(history.latest().unwrap().is_nan());
+    assert_eq!(history.min_max(), Some((3.0, 4.0)));
+
+    // After a long pause the whole buffer is the current value, and sampling goes on from now.
+    assert_eq!(history.record(Duration::from_secs(60), 5.0), 4);
+    assert_eq!(history.min_max(), Some((5.0, 5.0)));
+    assert_eq!(history.record(Duration::from_millis(60050), 6.0), 0);
+    assert_eq!(history.record(Duration::from_millis(60100), 6.0), 1);
+}
+
+#[test]
+fn graph_scrolls_with_every_trace() {
+    let _gauges = set_gauges(&[]);
+    let mut dashboard = parse_layout(GRAPH_LAYOUT).unwrap();
+    let mut fb = SoftFrameBuffer::new(WIDTH, HEIGHT);
+    // Five seconds of a lean spike and a boost ramp.
+    for tick in 0..100u64 {
+        let afr = if (40..50).contains(&tick) { 170 } else { 140 };
+        Gauge::AfrPri.set(afr);
+        Gauge::AfrTarget.set(if tick < 60 { 147 } else { 125 });
+        Gauge::MAP.set(100 + tick as u32);
+        dashboard.animate(Duration::from_millis(tick * 50));
+    }
+    dashboard.draw(&mut fb);
+    // The AFR trace ends at 14.0 by the right edge, the target below it at 12.5.
+    let (right, afr_y, target_y) = (20 + 436, 41 + 180 * 6 / 10, 41 + 180 * 3 / 4);
+    assert_eq!(fb.pixel(right, afr_y), Color::new(0, 200, 0));
+    assert_eq!(fb.pixel(right, target_y), Color::new(255, 230, 0));
+    check("graph", &fb);
+
+    // Nothing new to show until the next sample is due.
+    dashboard.animate(Duration::from_millis(4960));
+    dashboard.draw(&mut fb);
+    assert_eq!(fb.frames, 1);
+    dashboard.animate(Duration::from_millis(5000));
+    dashboard.draw(&mut fb);
+    assert_eq!(fb.frames, 2);
+    assert!(!fb.presented.is_full());
+
+    let text = "graph RPM x=0 y=0 w=100 h=50 trace=FOO\ngraph RPM x=0 y=0 w=100 h=50 samples=1\n";
+    let errors = parse_layout(text).err().unwrap();
+    assert_eq!(errors[0].kind, LayoutErrorKind::UnknownGauge("FOO".into()));
+    assert!(matches!(&errors[1].kind, LayoutErrorKind::BadValue { key, .. } if key == "samples"));
+}
+
+#[test]
+fn console_scrolls_and_wraps() {
+    let mut console = TextConsole::new(WIDTH, 100);
+    assert_eq!(console.rows(), 5);
+    for i in 0..6 {
+        writeln!(console, "[  {:>3}.000000] line {}", i, i).unwrap();
+    }
+    write!(console, "[W   6.000000] {}", "x".repeat(console.columns())).unwrap();
+    let lines: Vec<&str> = console.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[0], "[    3.000000] line 3");
+    assert!(lines[3].starts_with("[W   6.000000] x"));
+    assert_eq!(lines[3].chars().count(), console.columns());
+    assert_eq!(lines[4], "x".repeat(15));
+
+    let mut fb = SoftFrameBuffer::new(WIDTH, HEIGHT);
+    let mut console = TextConsole::new(WIDTH, HEIGHT);
+    writeln!(console, "[  0.100000] kernel_init").unwrap();
+    writeln!(console, "[W 1.200000] No layout LAYOUT.TXT, using the built-in one").unwrap();
+    write!(console, "\tindented").unwrap();
+    assert!(console.changed());
+    console.draw(&mut fb);
+    assert!(!console.changed());
+    check("console", &fb);
+
+    // Drawing line by line, scrolling included, ends up the same as drawing it all at once.
+    let mut fb = SoftFrameBuffer::new(WIDTH, 100);
+    let mut console = TextConsole::new(WIDTH, 100);
+    let mut whole = TextConsole::new(WIDTH, 100);
+    console.draw(&mut fb);
+    fb.update();
+    for i in 0..8 {
+        let line = format!("[W  {:>3}.000000] line {}\n", i, "x".repeat(i * 10));
+        console.write_str(&line).unwrap();
+        whole.write_str(&line).unwrap();
+        console.draw(&mut fb);
+        fb.update();
+        assert!(!fb.presented.is_full());
+        if i < 3 {
+            assert_eq!(fb.presented.rects()[0].y, 4 + 16 * i as i32, "only the new line");
+        }
+    }
+    let mut expected = SoftFrameBuffer::new(WIDTH, 100);
+    whole.draw(&mut expected);
+    assert_eq!(fb.diff(&expected), 0);
+}
+
+#[test]
+fn panic_screen() {
+    let mut fb = SoftFrameBuffer::new(WIDTH, HEIGHT);
+    fb.clear_screen();
+    let message = format_args!("Failed to open volume 0: {:?}", "FormatError(\"no FAT\")");
+    PanicReport {
+        message: &message,
+        file: "src/main.rs",
+        line: 187,
+        column: 10,
+        uptime: Duration::from_micros(2_345_678),
+    }
+    .draw(&mut fb);
+    assert_eq!(fb.pixel(WIDTH - 1, 1), Color::new(200, 0, 0));
+    check("panic", &fb);
+}
+
+#[test]
+fn screenshots_encode_what_is_shown() {
+    let _gauges = set_gauges(&RUNNING);
+    let mut dashboard = Dashboard::default_layout();
+    let mut fb = render(&mut dashboard);
+    let shot = screenshot::capture(&mut fb).unwrap();
+    assert_eq!((shot.width(), shot.height()), (WIDTH, HEIGHT));
+    assert!(shot.is_opaque());
+
+    let qoi = Format::Qoi.encode(&shot);
+    assert_eq!(Image::from_qoi(&qoi).unwrap(), shot);
+
+    // Bottom-up, blue first, every line padded to a multiple of four bytes.
+    let bmp = Format::Bmp.encode(&shot);
+    let stride = (WIDTH as usize * 3 + 3) & !3;
+    assert_eq!(&bmp[..2], b"BM");
+    assert_eq!(bmp.len(), 54 + stride * HEIGHT as usize);
+    assert_eq!(u32::from_le_bytes(bmp[2..6].try_into().unwrap()) as usize, bmp.len());
+    assert_eq!(u16::from_le_bytes([bmp[28], bmp[29]]), 24);
+    let (x, y) = (WIDTH / 3, 10);
+    let at = 54 + (HEIGHT - 1 - y) as usize * stride + x as usize * 3;
+    let color = fb.pixel(x, y);
+    assert_eq!(&bmp[at..at + 3], &[color.blue(), color.green(), color.red()]);
+
+    assert_eq!(Format::from_name("Qoi"), Some(Format::Qoi));
+    assert_eq!(Format::from_name("png"), None);
+    assert_eq!(Format::Bmp.file_name(7), "SHOT0007.BMP");
+
+    // The corners a round panel cuts off come out black.
+    let panel = SoftFrameBuffer::new(WIDTH, HEIGHT);
+    let mut fb = Transformed::new(panel, Transform::default()).set_shape(Shape::Round);
+    fb.draw_rect_fill(&Coordinates::new(0, 0), WIDTH, HEIGHT, WHITE_COLOR);
+    let shot = screenshot::capture(&mut fb).unwrap();
+    assert_eq!(shot.pixel(0, 0), (BLACK_COLOR, 255));
+    assert_eq!(shot.pixel(WIDTH / 2, HEIGHT / 2), (WHITE_COLOR, 255));
+}
+
+#[test]
+fn screenshots_stream_as_base64() {
+    let mut text = String::new();
+    screenshot::write_base64(&mut text, b"Man").unwrap();
+    screenshot::write_base64(&mut text, b"Ma").unwrap();
+    screenshot::write_base64(&mut text, b"M").unwrap();
+    assert_eq!(text, "TWFu\nTWE=\nTQ==\n");
+
+    let data: Vec<u8> = (0..=255).collect();
+    let mut text = String::new();
+    screenshot::write_stream(&mut text, "SHOT0001.QOI", &data).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], format!("{} SHOT0001.QOI 256", STREAM_BEGIN));
+    assert_eq!(lines[1].len(), 76);
+    assert_eq!(lines.len(), 2 + 256_usize.div_ceil(57));
+    assert_eq!(*lines.last().unwrap(), STREAM_END);
+}
+
+#[test]
+fn night_palette_redraws_every_widget() {
+    let _gauges = set_gauges(&RUNNING);
+    let mut dashboard = Dashboard::default_layout();
+    let mut fb = render(&mut dashboard);
+    let (bar, lamp) = ((20, 448), (376, 424));
+    assert_eq!(fb.pixel(bar.0, bar.1), Palette::DAY.accent);
+    assert_eq!(fb.pixel(lamp.0, lamp.1), Palette::DAY.warning);
+
+    // The same palette again draws nothing, another one draws everything.
+    dashboard.set_palette(Palette::DAY);
+    dashboard.draw(&mut fb);
+    assert_eq!(fb.frames, 1);
+    dashboard.set_palette(Palette::NIGHT);
+    dashboard.draw(&mut fb);
+    assert_eq!(fb.frames, 2);
+    assert!(fb.presented.is_full());
+    assert_eq!(fb.pixel(bar.0, bar.1), Palette::NIGHT.accent);
+    assert_eq!(fb.pixel(lamp.0, lamp.1), Palette::NIGHT.warning);
+    check("default_night", &fb);
+
+    let mut night = Dashboard::default_layout();
+    night.set_palette(Palette::NIGHT);
+    assert_eq!(render(&mut night).diff(&fb), 0);
+
+    // Fixed colours stay put, the background fills the screen.
+    let text = "text x=0 y=0 label=A color=critical\ntext x=0 y=20 label=B color=#102030\n";
+    let mut dashboard = parse_layout(text).unwrap();
+    let Widget::Text(first) = &dashboard.widgets[0] else { panic!() };
+    assert_eq!(first.color, Paint::Role(Role::Critical));
+    let Widget::Text(second) = &dashboard.widgets[1] else { panic!() };
+    assert_eq!(second.color, Paint::Fixed(Color::new(16, 32, 48)));
+    let mut palette = Palette::NIGHT;
+    palette.background = Color::new(0, 0, 60);
+    dashboard.set_palette(palette);
+    let fb = render(&mut dashboard);
+    assert_eq!(fb.pixel(WIDTH - 1, HEIGHT - 1), Color::new(0, 0, 60));
+}
+
+#[test]
+fn theme_file_changes_the_palettes() {
+    let text = "# colours for the track
+day   accent=#0000FF
+night foreground=#808080 critical=red
+";
+    let theme = parse_theme(text).unwrap();
+    assert_eq!(theme.day.accent, Color::new(0, 0, 255));
+    assert_eq!(theme.day.foreground, Palette::DAY.foreground);
+    assert_eq!(theme.night.foreground, Color::new(128, 128, 128));
+    assert_eq!(theme.night.critical, Color::new(255, 0, 0));
+    assert_eq!(theme.night.accent, Palette::NIGHT.accent);
+
+    let errors = parse_theme("dusk accent=red\nday accent=accent\nnight glow=red\n").unwrap_err();
+    assert_eq!(errors.len(), 3);
+    assert_eq!(errors[0].kind, LayoutErrorKind::UnknownPalette("dusk".into()));
+    assert!(matches!(&errors[1].kind, LayoutErrorKind::BadValue { key, .. } if key == "accent"));
+    assert_eq!(errors[2].kind, LayoutErrorKind::UnknownSetting("glow".into()));
+}
+
+#[test]
+fn theme_switches_by_hand_headlights_and_clock() {
+    let time = |value| parse_time_of_day(value).unwrap();
+    assert_eq!(time("07:05"), Duration::from_secs(7 * 3600 + 5 * 60));
+    assert_eq!(parse_time_of_day("24:00"), None);
+    let schedule = Schedule {
+        night_from: time("19:30"),
+        day_from: time("07:00"),
+    };
+    assert_eq!(schedule.mode_at(time("12:00")), ThemeMode::Day);
+    assert_eq!(schedule.mode_at(time("23:00")), ThemeMode::Night);
+    assert_eq!(schedule.mode_at(time("03:00")), ThemeMode::Night);
+    assert_eq!(schedule.mode_at(time("07:00")), ThemeMode::Day);
+
+    // Without an automatic source only the hand switch counts.
+    let mut switch = ThemeSwitch::new(ThemeMode::Day);
+    assert_eq!(switch.update(None), ThemeMode::Day);
+    assert_eq!(switch.toggle(), ThemeMode::Night);
+    assert_eq!(switch.update(None), ThemeMode::Night);
+
+    // The headlights going on switch to night, switching back by hand holds until they change.
+    let mut switch = ThemeSwitch::new(ThemeMode::Day);
+    assert_eq!(switch.update(Some(ThemeMode::Night)), ThemeMode::Night);
+    switch.toggle();
+    assert_eq!(switch.update(Some(ThemeMode::Night)), ThemeMode::Day);
+    assert_eq!(switch.update(Some(ThemeMode::Day)), ThemeMode::Day);
+    assert_eq!(switch.update(Some(ThemeMode::Night)), ThemeMode::Night);
+}
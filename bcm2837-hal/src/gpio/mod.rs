@@ -87,6 +87,7 @@ impl OutputPin for Pin {
         Ok(())
     }
 }
+
 /*impl OldHalOutputPinnonv2 for Pin{
     fn set_low(&mut self) {
         embedded_hal::digital::OutputPin::set_low(self);
@@ -206,9 +207,7 @@ impl Pin {
         let reg = self.fen_ptr();
         let bank = self.pin_num % 32;
 
-        unsafe {
-            reg.write_with_zero(|w| w.bits(1 << bank));
-        }
+        reg.modify(|r, w| unsafe { w.bits(r.bits() | 1 << bank) });
 
         self
     }
@@ -218,9 +217,7 @@ impl Pin {
         let reg = self.afen_ptr();
         let bank = self.pin_num % 32;
 
-        unsafe {
-            reg.write_with_zero(|w| w.bits(1 << bank));
-        }
+        reg.modify(|r, w| unsafe { w.bits(r.bits() | 1 << bank) });
 
         self
     }
@@ -230,9 +227,7 @@ impl Pin {
         let reg = self.ren_ptr();
         let bank = self.pin_num % 32;
 
-        unsafe {
-            reg.write_with_zero(|w| w.bits(1 << bank));
-        }
+        reg.modify(|r, w| unsafe { w.bits(r.bits() | 1 << bank) });
 
         self
     }
@@ -242,9 +237,7 @@ impl Pin {
         let reg = self.aren_ptr();
         let bank = self.pin_num % 32;
 
-        unsafe {
-            reg.write_with_zero(|w| w.bits(1 << bank));
-        }
+        reg.modify(|r, w| unsafe { w.bits(r.bits() | 1 << bank) });
 
         self
     }
@@ -254,9 +247,7 @@ impl Pin {
         let reg = self.hen_ptr();
         let bank = self.pin_num % 32;
 
-        unsafe {
-            reg.write_with_zero(|w| w.bits(1 << bank));
-        }
+        reg.modify(|r, w| unsafe { w.bits(r.bits() | 1 << bank) });
 
         self
     }
@@ -266,13 +257,12 @@ impl Pin {
         let reg = self.len_ptr();
         let bank = self.pin_num % 32;
 
-        unsafe {
-            reg.write_with_zero(|w| w.bits(1 << bank));
-        }
+        reg.modify(|r, w| unsafe { w.bits(r.bits() | 1 << bank) });
 
         self
     }
 
+    /// Clears a detected event, the status bits are write-1-to-clear.
     pub fn set_eds(&self) -> &Pin {
         // event detect status writer
         let reg = self.eds_ptr();
@@ -285,6 +275,22 @@ impl Pin {
         self
     }
 
+    /// Whether one of the enabled edge or level detectors fired since the last `set_eds`.
+    pub fn event_detected(&self) -> bool {
+        let bank = self.pin_num % 32;
+        self.eds_ptr().read().bits() & (1 << bank) != 0
+    }
+
+    /// Current input level of the pin.
+    pub fn is_high(&self) -> bool {
+        let bank = self.pin_num % 32;
+        self.lvl_ptr().read().bits() & (1 << bank) != 0
+    }
+
+    pub fn is_low(&self) -> bool {
+        !self.is_high()
+    }
+
     // -------------------------------------------
     // Pointers
     // -------------------------------------------
@@ -292,7 +298,11 @@ impl Pin {
     fn fen_ptr(&self) -> &crate::pac::gpio::GPFEN0 {
         // falling edge R/W
         let offset = (self.pin_num / 32) * 0x04;
-        let offset_ptr = unsafe { crate::pac::GPIO::ptr().byte_add(offset as usize) } as usize;
+        let offset_ptr = unsafe {
+            crate::pac::GPIO::ptr()
+                .byte_add(0x58)
+                .byte_add(offset as usize)
+        } as usize;
 
         unsafe { &*(offset_ptr as *const crate::pac::gpio::GPFEN0) }
     }
@@ -300,7 +310,11 @@ impl Pin {
     fn afen_ptr(&self) -> &crate::pac::gpio::GPAFEN0 {
         // async falling edge R/W
         let offset = (self.pin_num / 32) * 0x04;
-        let offset_ptr = unsafe { crate::pac::GPIO::ptr().byte_add(offset as usize) } as usize;
+        let offset_ptr = unsafe {
+            crate::pac::GPIO::ptr()
+                .byte_add(0x88)
+                .byte_add(offset as usize)
+        } as usize;
 
         unsafe { &*(offset_ptr as *const crate::pac::gpio::GPAFEN0) }
     }
@@ -308,7 +322,11 @@ impl Pin {
     fn ren_ptr(&self) -> &crate::pac::gpio::GPREN0 {
         // rising edge R/W
         let offset = (self.pin_num / 32) * 0x04;
-        let offset_ptr = unsafe { crate::pac::GPIO::ptr().byte_add(offset as usize) } as usize;
+        let offset_ptr = unsafe {
+            crate::pac::GPIO::ptr()
+                .byte_add(0x4C)
+                .byte_add(offset as usize)
+        } as usize;
 
         unsafe { &*(offset_ptr as *const crate::pac::gpio::GPREN0) }
     }
@@ -316,7 +334,11 @@ impl Pin {
     fn aren_ptr(&self) -> &crate::pac::gpio::GPAREN0 {
         // async rising edge R/W
         let offset = (self.pin_num / 32) * 0x04;
-        let offset_ptr = unsafe { crate::pac::GPIO::ptr().byte_add(offset as usize) } as usize;
+        let offset_ptr = unsafe {
+            crate::pac::GPIO::ptr()
+                .byte_add(0x7C)
+                .byte_add(offset as usize)
+        } as usize;
 
         unsafe { &*(offset_ptr as *const crate::pac::gpio::GPAREN0) }
     }
@@ -324,7 +346,11 @@ impl Pin {
     fn eds_ptr(&self) -> &crate::pac::gpio::GPEDS0 {
         // event detect status R/W
         let offset = (self.pin_num / 32) * 0x04;
-        let offset_ptr = unsafe { crate::pac::GPIO::ptr().byte_add(offset as usize) } as usize;
+        let offset_ptr = unsafe {
+            crate::pac::GPIO::ptr()
+                .byte_add(0x40)
+                .byte_add(offset as usize)
+        } as usize;
 
         unsafe { &*(offset_ptr as *const crate::pac::gpio::GPEDS0) }
     }
@@ -332,7 +358,11 @@ impl Pin {
     fn hen_ptr(&self) -> &crate::pac::gpio::GPHEN0 {
         // high detect enable R/W
         let offset = (self.pin_num / 32) * 0x04;
-        let offset_ptr = unsafe { crate::pac::GPIO::ptr().byte_add(offset as usize) } as usize;
+        let offset_ptr = unsafe {
+            crate::pac::GPIO::ptr()
+                .byte_add(0x64)
+                .byte_add(offset as usize)
+        } as usize;
 
         unsafe { &*(offset_ptr as *const crate::pac::gpio::GPHEN0) }
     }
@@ -340,7 +370,11 @@ impl Pin {
     fn len_ptr(&self) -> &crate::pac::gpio::GPLEN0 {
         // low detect enable R/W
         let offset = (self.pin_num / 32) * 0x04;
-        let offset_ptr = unsafe { crate::pac::GPIO::ptr().byte_add(offset as usize) } as usize;
+        let offset_ptr = unsafe {
+            crate::pac::GPIO::ptr()
+                .byte_add(0x70)
+                .byte_add(offset as usize)
+        } as usize;
 
         unsafe { &*(offset_ptr as *const crate::pac::gpio::GPLEN0) }
     }
@@ -348,7 +382,11 @@ impl Pin {
     fn lvl_ptr(&self) -> &crate::pac::gpio::GPLEV0 {
         // pin level reader R/O
         let offset = (self.pin_num / 32) * 0x04;
-        let offset_ptr = unsafe { crate::pac::GPIO::ptr().byte_add(offset as usize) } as usize;
+        let offset_ptr = unsafe {
+            crate::pac::GPIO::ptr()
+                .byte_add(0x34)
+                .byte_add(offset as usize)
+        } as usize;
 
         unsafe { &*(offset_ptr as *const crate::pac::gpio::GPLEV0) }
     }
@@ -1,4 +1,5 @@
 use bcm2837_lpa::{Peripherals, SPI0};
+use core::cell::RefCell;
 use embedded_hal::{
     delay::DelayNs,
     digital::OutputPin,
@@ -15,10 +16,25 @@ pub struct SPIZero<'a> {
     spi0: &'a SPI0,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BuiltinCS {
-    Cs0 = 00,
-    Cs1 = 01,
-    Cs2 = 10,
+    Cs0 = 0b00,
+    Cs1 = 0b01,
+    Cs2 = 0b10,
+}
+
+impl BuiltinCS {
+    /// The GPIO behind this chip enable, for devices that need to hold CS across several
+    /// transfers (like the MCP2515) and therefore drive it as a plain output.
+    ///
+    /// CE2 isn't routed to the header.
+    pub fn gpio_pin(&self) -> Option<u8> {
+        match self {
+            BuiltinCS::Cs0 => Some(8),
+            BuiltinCS::Cs1 => Some(7),
+            BuiltinCS::Cs2 => None,
+        }
+    }
 }
 
 impl ErrorType for SPIZero<'_> {
@@ -444,6 +460,26 @@ where
     }
 }
 
+/// One of several handles on a bus shared by devices that drive their own chip select, like the
+/// two MCP2515s of the CAN gateway on SPI0. Each transfer borrows the bus for its duration.
+pub struct SharedSPI<'a, B> {
+    bus: &'a RefCell<B>,
+}
+
+impl<'a, B> SharedSPI<'a, B> {
+    pub fn new(bus: &'a RefCell<B>) -> Self {
+        SharedSPI { bus }
+    }
+}
+
+impl<B: Transfer<u8>> Transfer<u8> for SharedSPI<'_, B> {
+    type Error = B::Error;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
+        self.bus.borrow_mut().transfer(words)
+    }
+}
+
 fn round_clock(freq: Hertz<u32>) -> u32 {
     // Calculate the desired divider
     let divider = (REFERENCE_FREQ / freq.to_Hz()).max(1);
@@ -14,6 +14,10 @@ require 'serialport'
 
 SERIAL_BAUD = 921_600
 
+# Marker lines around a screenshot sent by the kernel, see CogwareGfx/src/screenshot.rs.
+SCREENSHOT_BEGIN = '--- screenshot'
+SCREENSHOT_END = '--- end of screenshot'
+
 class ConnectionError < StandardError; end
 
 # The main class
@@ -23,6 +27,7 @@ class MiniTerm
         @target_serial_name = serial_name
         @target_serial = nil
         @host_console = IO.console
+        @screenshot = nil
     end
 
     private
@@ -63,11 +68,21 @@ class MiniTerm
 
         # Receive from target and print on host console.
         target_to_host = Thread.new do
+            line = +''
             loop do
                 char = @target_serial.getc
 
                 raise ConnectionError if char.nil?
 
+                if char == "\n"
+                    screenshot_line(line)
+                    line = +''
+                else
+                    line << char
+                end
+                # The base64 of a screenshot is not shown.
+                next if @screenshot
+
                 # Translate incoming newline to newline + carriage return.
                 @host_console.putc("\r") if char == "\n"
                 @host_console.putc(char)
@@ -88,9 +103,43 @@ class MiniTerm
         end
     end
 
+    # Collect the lines of a screenshot, from the line after the begin marker to the end marker.
+    def screenshot_line(line)
+        line = line.chomp("\r")
+        if @screenshot.nil?
+            return unless line.start_with?(SCREENSHOT_BEGIN)
+
+            name, size = line.delete_prefix(SCREENSHOT_BEGIN).split
+            @screenshot = { name: File.basename(name.to_s), size: size.to_i, base64: +'' }
+        elsif line == SCREENSHOT_END
+            save_screenshot
+        else
+            @screenshot[:base64] << line
+        end
+    end
+
+    # Save the screenshot in the current directory, the time in front of the name so none is
+    # overwritten.
+    def save_screenshot
+        data = @screenshot[:base64].unpack1('m')
+        name = "#{Time.now.strftime('%Y%m%d-%H%M%S')}-#{@screenshot[:name]}"
+        expected = @screenshot[:size]
+        @screenshot = nil
+
+        if data.bytesize == expected
+            File.binwrite(name, data)
+            message = "📷 Saved #{name}".green
+        else
+            message = "⚡ #{"Screenshot got #{data.bytesize} of #{expected} bytes".light_red}"
+        end
+        # The newline after the end marker finishes the line.
+        @host_console.write("\r\n[#{@name_short}] #{message}")
+    end
+
     def connection_reset
         @target_serial&.close
         @target_serial = nil
+        @screenshot = nil
         @host_console.cooked!
     end
 
@@ -0,0 +1,110 @@
+//! Images on the SD card: splash screens and gauge face artwork.
+//!
+//! Files are QOI or BMP, told apart by their first bytes rather than the name. Each file is
+//! decoded once and kept on the heap, layouts showing the same file share it.
+
+use alloc::{collections::BTreeMap, rc::Rc, string::String, vec::Vec};
+use cogware_gfx::fb_trait::Color;
+use cogware_gfx::image::{Image, ImageError};
+use core::fmt;
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+use embedded_graphics::{prelude::OriginDimensions, Pixel};
+use embedded_sdmmc::{BlockDevice, Directory, Mode, TimeSource};
+use tinybmp::Bmp;
+
+/// Largest file read, a 2048x2048 BMP with alpha and then some.
+const MAX_FILE_SIZE: u32 = 17 * 1024 * 1024;
+
+const QOI_MAGIC: &[u8] = b"qoif";
+const BMP_MAGIC: &[u8] = b"BM";
+
+#[derive(Debug)]
+pub enum AssetError<E: fmt::Debug> {
+    File(embedded_sdmmc::Error<E>),
+    TooLarge(u32),
+    /// Neither a QOI nor a BMP file.
+    UnknownFormat,
+    Bmp(tinybmp::ParseError),
+    Image(ImageError),
+}
+
+impl<E: fmt::Debug> fmt::Display for AssetError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetError::File(e) => write!(f, "cannot read the file: {:?}", e),
+            AssetError::TooLarge(size) => write!(
+                f,
+                "file is {} bytes, at most {} are read",
+                size, MAX_FILE_SIZE
+            ),
+            AssetError::UnknownFormat => write!(f, "not a QOI or BMP image"),
+            AssetError::Bmp(e) => write!(f, "bad BMP image: {:?}", e),
+            AssetError::Image(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Images loaded so far, by file name.
+pub struct Assets {
+    images: BTreeMap<String, Rc<Image>>,
+}
+
+impl Assets {
+    pub fn new() -> Assets {
+        Assets {
+            images: BTreeMap::new(),
+        }
+    }
+
+    /// The image in file `name` of `dir`, decoded on first use.
+    pub fn load<D, T, const MAX_DIRS: usize, const MAX_FILES: usize, const MAX_VOLUMES: usize>(
+        &mut self,
+        dir: &mut Directory<D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+        name: &str,
+    ) -> Result<Rc<Image>, AssetError<D::Error>>
+    where
+        D: BlockDevice,
+        T: TimeSource,
+    {
+        let key = name.to_ascii_uppercase();
+        if let Some(image) = self.images.get(&key) {
+            return Ok(image.clone());
+        }
+        let mut file = dir
+            .open_file_in_dir(name, Mode::ReadOnly)
+            .map_err(AssetError::File)?;
+        if file.length() > MAX_FILE_SIZE {
+            return Err(AssetError::TooLarge(file.length()));
+        }
+        let data = file.read_to_vec().map_err(AssetError::File)?;
+        file.close().map_err(AssetError::File)?;
+
+        let image = Rc::new(decode(&data)?);
+        self.images.insert(key, image.clone());
+        Ok(image)
+    }
+}
+
+fn decode<E: fmt::Debug>(data: &[u8]) -> Result<Image, AssetError<E>> {
+    if data.starts_with(QOI_MAGIC) {
+        Image::from_qoi(data).map_err(AssetError::Image)
+    } else if data.starts_with(BMP_MAGIC) {
+        decode_bmp(data)
+    } else {
+        Err(AssetError::UnknownFormat)
+    }
+}
+
+/// Any BMP that `tinybmp` understands, drawn opaque.
+fn decode_bmp<E: fmt::Debug>(data: &[u8]) -> Result<Image, AssetError<E>> {
+    let bmp = Bmp::<Rgb888>::from_slice(data).map_err(AssetError::Bmp)?;
+    let size = bmp.size();
+    Image::check_size(size.width, size.height).map_err(AssetError::Image)?;
+
+    let mut pixels: Vec<u32> = alloc::vec![0; (size.width * size.height) as usize];
+    for Pixel(point, color) in bmp.pixels() {
+        let index = point.y as usize * size.width as usize + point.x as usize;
+        pixels[index] = Color::new(color.r(), color.g(), color.b()).rgb();
+    }
+    Image::from_argb(size.width, size.height, pixels).map_err(AssetError::Image)
+}
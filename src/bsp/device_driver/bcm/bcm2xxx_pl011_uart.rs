@@ -384,6 +384,11 @@ impl console::interface::Read for PL011Uart {
             .lock(|inner| inner.read_char_converting(BlockingMode::Blocking).unwrap())
     }
 
+    fn try_read_char(&self) -> Option<char> {
+        self.inner
+            .lock(|inner| inner.read_char_converting(BlockingMode::NonBlocking))
+    }
+
     fn clear_rx(&self) {
         // Read from the RX FIFO until it is indicating empty.
         while self
@@ -0,0 +1,103 @@
+//! Debounced push buttons on GPIO pins.
+
+use bcm2837_hal::gpio::{Pin, PinMode};
+use core::time::Duration;
+
+/// How long the level has to stay put before a press or release counts.
+const DEBOUNCE: Duration = Duration::from_millis(30);
+/// Holding a button this long is a long press.
+const LONG_PRESS: Duration = Duration::from_millis(800);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtonEvent {
+    /// Pressed and released before the long press time.
+    Press,
+    /// Held down for the long press time, fired while the button is still held.
+    LongPress,
+}
+
+/// A button between a GPIO pin and ground (or 3V3 when not `active_low`), polled from the
+/// main loop.
+///
+/// Both edge detectors are enabled on the pin. Any edge seen in the event detect status
+/// restarts the debounce timer, so contact bounce between two polls is not missed.
+pub struct Button {
+    pin: Pin,
+    active_low: bool,
+    pressed: bool,
+    settling_since: Option<Duration>,
+    pressed_at: Duration,
+    long_fired: bool,
+}
+
+impl Button {
+    pub fn new(pin_num: u8, active_low: bool) -> Button {
+        let mode = if active_low {
+            PinMode::InputPullUp
+        } else {
+            PinMode::InputPullDown
+        };
+        let pin = Pin::new(pin_num, mode);
+        pin.set_mode(mode);
+        pin.set_fen().set_ren().set_eds();
+
+        Button {
+            pin,
+            active_low,
+            pressed: false,
+            // The first poll reads the level, for an input that is already on at power-on.
+            settling_since: Some(Duration::ZERO),
+            pressed_at: Duration::ZERO,
+            long_fired: false,
+        }
+    }
+
+    /// Whether the button is held down, after debouncing.
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Forget the press going on, releasing the button fires no event. For when it was held
+    /// together with another one.
+    pub fn cancel(&mut self) {
+        if self.pressed {
+            self.long_fired = true;
+        }
+    }
+
+    fn level_pressed(&self) -> bool {
+        self.pin.is_high() != self.active_low
+    }
+
+    /// Sample the button, `now` comes from the system timer.
+    pub fn poll(&mut self, now: Duration) -> Option<ButtonEvent> {
+        if self.pin.event_detected() {
+            self.pin.set_eds();
+            self.settling_since = Some(now);
+        }
+
+        if let Some(since) = self.settling_since {
+            if now - since < DEBOUNCE {
+                return None;
+            }
+            self.settling_since = None;
+
+            let pressed = self.level_pressed();
+            if pressed != self.pressed {
+                self.pressed = pressed;
+                if pressed {
+                    self.pressed_at = now;
+                    self.long_fired = false;
+                } else if !self.long_fired {
+                    return Some(ButtonEvent::Press);
+                }
+            }
+        }
+
+        if self.pressed && !self.long_fired && now - self.pressed_at >= LONG_PRESS {
+            self.long_fired = true;
+            return Some(ButtonEvent::LongPress);
+        }
+        None
+    }
+}
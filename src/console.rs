@@ -4,6 +4,7 @@
 
 //! System console.
 
+pub mod fb_console;
 mod null_console;
 
 use crate::synchronization::{self, NullLock};
@@ -35,6 +36,11 @@ pub mod interface {
             ' '
         }
 
+        /// Read a character if one came in, without waiting.
+        fn try_read_char(&self) -> Option<char> {
+            None
+        }
+
         /// Clear RX buffers, if any.
         fn clear_rx(&self);
     }
@@ -63,6 +69,10 @@ pub mod interface {
 static CUR_CONSOLE: NullLock<&'static (dyn interface::All + Sync)> =
     NullLock::new(&null_console::NULL_CONSOLE);
 
+/// Gets a copy of everything written to the console, e.g. the screen next to the UART.
+static SECONDARY_CONSOLE: NullLock<Option<&'static (dyn interface::Write + Sync)>> =
+    NullLock::new(None);
+
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
@@ -79,3 +89,13 @@ pub fn register_console(new_console: &'static (dyn interface::All + Sync)) {
 pub fn console() -> &'static dyn interface::All {
     CUR_CONSOLE.lock(|con| *con)
 }
+
+/// Register a console that gets a copy of all output, or none with `None`.
+pub fn register_secondary_console(new_console: Option<&'static (dyn interface::Write + Sync)>) {
+    SECONDARY_CONSOLE.lock(|con| *con = new_console);
+}
+
+/// Return a reference to the secondary console, if one is registered.
+pub fn secondary_console() -> Option<&'static dyn interface::Write> {
+    SECONDARY_CONSOLE.lock(|con| con.map(|con| con as &'static dyn interface::Write))
+}
@@ -0,0 +1,150 @@
+//! Console on the display, for the boot log and the panic screen.
+//!
+//! The console owns the framebuffer while the kernel boots and hands it over to the dashboard
+//! with [`FbConsole::detach`]. It remembers where the framebuffer is, so a panic can still take
+//! the screen back from the dashboard.
+
+use super::interface;
+use crate::framebuffer::{FrameBuffer, Location, Vsync};
+use crate::hvs;
+use crate::synchronization::{interface::Mutex, NullLock};
+use cogware_gfx::console::{PanicReport, TextConsole};
+use cogware_gfx::fb_trait::FrameBufferInterface;
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+pub struct FbConsole {
+    inner: NullLock<FbConsoleInner>,
+    /// Set while a write is drawing, so output from within the framebuffer code, like a vsync
+    /// warning, does not draw into the screen it is in the middle of presenting.
+    busy: AtomicBool,
+}
+
+struct FbConsoleInner {
+    fb: Option<FrameBuffer>,
+    text: Option<TextConsole>,
+    location: Option<Location>,
+    /// Pixels for the panic screen, reserved up front as the panic may be running out of
+    /// memory.
+    panic_pixels: Option<Vec<u32>>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+pub static FB_CONSOLE: FbConsole = FbConsole::new();
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl FbConsole {
+    pub const fn new() -> Self {
+        FbConsole {
+            inner: NullLock::new(FbConsoleInner {
+                fb: None,
+                text: None,
+                location: None,
+                panic_pixels: None,
+            }),
+            busy: AtomicBool::new(false),
+        }
+    }
+
+    /// Show everything written from now on on `fb`. Flipping does not wait for vsync, the log
+    /// would only come in slower.
+    pub fn attach(&self, fb: FrameBuffer) {
+        let fb = fb.set_vsync(Vsync::Off);
+        self.inner.lock(|inner| {
+            inner.text = Some(TextConsole::new(fb.width_u32(), fb.height_u32()));
+            let location = fb.location();
+            inner.location = Some(location);
+            inner.panic_pixels = Some(location.reserve());
+            inner.fb = Some(fb);
+        });
+    }
+
+    /// Take the framebuffer back, e.g. for the dashboard. Nothing is shown from then on, except
+    /// for the panic screen.
+    pub fn detach(&self) -> Option<FrameBuffer> {
+        self.inner.lock(|inner| {
+            inner.text = None;
+            inner.fb.take()
+        })
+    }
+
+    /// Fill the screen with `report`, on the attached framebuffer or on a new one over the same
+    /// memory. Nothing is allocated. Whatever the HVS was showing makes way for it.
+    pub fn panic(&self, report: &PanicReport) {
+        // A panic in the middle of a write leaves the attached framebuffer half way through
+        // drawing or presenting, it is not touched again.
+        let busy = self.busy.load(Ordering::Relaxed);
+        self.inner.lock(|inner| {
+            let mut opened;
+            let attached = inner.fb.as_mut().filter(|_| !busy);
+            let fb = match (attached, inner.location) {
+                (Some(fb), _) => fb,
+                // Whoever has the framebuffer now is not coming back.
+                (None, Some(location)) => {
+                    let Some(pixels) = inner.panic_pixels.take() else {
+                        return;
+                    };
+                    opened = unsafe { location.open(pixels) }.set_vsync(Vsync::Off);
+                    &mut opened
+                }
+                (None, None) => return,
+            };
+            report.draw(fb);
+            fb.update();
+        });
+        hvs::show_firmware_list();
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+
+impl FbConsoleInner {
+    fn show(&mut self) {
+        if let (Some(fb), Some(text)) = (self.fb.as_mut(), self.text.as_mut()) {
+            if text.changed() {
+                text.draw(fb);
+                fb.update();
+            }
+        }
+    }
+}
+
+impl interface::Write for FbConsole {
+    fn write_char(&self, c: char) {
+        // Writing into a `TextConsole` cannot fail.
+        let _ = self.write_fmt(format_args!("{}", c));
+    }
+
+    fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result {
+        // Plain loads and stores, the kernel runs on one core with the MMU off.
+        if self.busy.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        self.busy.store(true, Ordering::Relaxed);
+        let result = self.inner.lock(|inner| {
+            let Some(text) = inner.text.as_mut() else {
+                return Ok(());
+            };
+            fmt::Write::write_fmt(text, args)?;
+            inner.show();
+            Ok(())
+        });
+        self.busy.store(false, Ordering::Relaxed);
+        result
+    }
+
+    fn flush(&self) {}
+}
@@ -1,68 +1,406 @@
-use crate::{
-    fb_trait::{Color, FrameBufferInterface},
-    mailbox::set_virtual_framebuffer_offset,
+use crate::hvs::Channel;
+use crate::mailbox::{set_virtual_framebuffer_offset, wait_for_vsync};
+use crate::{time, warn};
+use alloc::{vec, vec::Vec};
+use core::time::Duration;
+use cogware_gfx::{
+    damage::{Damage, Rect},
+    fb_trait::{Color, Coordinates, FrameBufferInterface},
 };
+
+/// Byte order of a pixel in the firmware's framebuffer, as reported by the pixel order tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelOrder {
+    /// Red in the lowest bits, the firmware's `RGB`.
+    Rgb,
+    /// Red in the highest bits, the firmware's `BGR`. Same layout as [`Color`].
+    Bgr,
+}
+
+/// How the firmware lays out a pixel in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelFormat {
+    /// 16 (RGB565), 24 or 32.
+    pub depth_bits: u32,
+    pub order: PixelOrder,
+}
+
+impl PixelFormat {
+    /// The layout of [`Color`], copied without conversion.
+    pub const NATIVE: PixelFormat = PixelFormat {
+        depth_bits: 32,
+        order: PixelOrder::Bgr,
+    };
+
+    /// `None` for depths the framebuffer cannot draw in, like 8-bit palettes.
+    pub fn new(depth_bits: u32, order: PixelOrder) -> Option<Self> {
+        match depth_bits {
+            16 | 24 | 32 => Some(PixelFormat { depth_bits, order }),
+            _ => None,
+        }
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.depth_bits as usize / 8
+    }
+
+    /// The little endian pixel value for `color`, in the low `bytes_per_pixel` bytes.
+    #[inline(always)]
+    fn encode(&self, color: Color) -> u32 {
+        let green = color.green() as u32;
+        let (high, low) = match self.order {
+            PixelOrder::Bgr => (color.red() as u32, color.blue() as u32),
+            PixelOrder::Rgb => (color.blue() as u32, color.red() as u32),
+        };
+        match self.depth_bits {
+            16 => (high >> 3) << 11 | (green >> 2) << 5 | low >> 3,
+            24 => high << 16 | green << 8 | low,
+            _ => 0xFF << 24 | high << 16 | green << 8 | low,
+        }
+    }
+}
+
+/// Most screens the framebuffer flips between.
+pub const MAX_BUFFERS: usize = 3;
+/// Give up on a frame counter that stopped, e.g. with the display off.
+const VSYNC_TIMEOUT: Duration = Duration::from_millis(100);
+/// The HVS frame counter is 6 bits.
+const FRAME_COUNT_MASK: u32 = 0x3F;
+
+/// How `update` waits for a screen to leave the display before drawing into it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Vsync {
+    /// Don't wait, the screen may tear.
+    Off,
+    /// Block on the firmware's wait-for-vsync tag.
+    #[default]
+    Firmware,
+    /// Spin on the HVS frame counter, without the mailbox round trip.
+    Hvs,
+}
+
+/// How well frames keep up with the display.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    /// Frames presented.
+    pub frames: u32,
+    /// Refreshes that showed an old frame while a new one was being presented, beyond the one
+    /// `update` may wait for.
+    pub missed: u32,
+    /// Time from `update` being called until the frame was handed to the display, for the
+    /// last frame and the slowest one.
+    pub latency: Duration,
+    pub max_latency: Duration,
+}
+
+impl FrameStats {
+    fn record(&mut self, latency: Duration, refreshes: u32) {
+        self.frames += 1;
+        self.missed += refreshes.saturating_sub(1);
+        self.latency = latency;
+        self.max_latency = self.max_latency.max(latency);
+    }
+}
+
+/// Where the firmware put a framebuffer, to open it again when the [`FrameBuffer`] itself cannot
+/// be reached, like for the panic screen.
+#[derive(Debug, Clone, Copy)]
+pub struct Location {
+    address: usize,
+    size: usize,
+    width: u32,
+    height: u32,
+    pitch: u32,
+    fb_virtual_width: u32,
+    screens: u8,
+    format: PixelFormat,
+}
+
+impl Location {
+    /// Room for the pixels of a framebuffer opened with [`Location::open`], taken ahead of time
+    /// so that opening one does not allocate.
+    pub fn reserve(&self) -> Vec<u32> {
+        vec![0; (self.width * self.height) as usize]
+    }
+
+    /// A new black [`FrameBuffer`] over the same memory, drawing into `pixels` from
+    /// [`Location::reserve`] and showing the next screen on `update`.
+    ///
+    /// # Safety
+    ///
+    /// The memory is shared with the framebuffer this came from, which must not be drawn to
+    /// anymore.
+    pub unsafe fn open(&self, pixels: Vec<u32>) -> FrameBuffer {
+        let framebuff = core::slice::from_raw_parts_mut(self.address as *mut u8, self.size);
+        FrameBuffer::with_pixels(framebuff, self, pixels)
+    }
+}
+
+/// The firmware framebuffer, flipping between two or three screens through the virtual offset.
+///
+/// Drawing goes to a full-screen copy in normal memory in [`Color`] layout. `update` converts
+/// the damaged parts to the firmware's depth, pixel order and pitch, so drawing code never has
+/// to care about them and blending reads cached memory.
+///
+/// A screen is only written once the display has moved on from it, see [`Vsync`]. With three
+/// screens there is usually one that is already free, so `update` does not have to wait.
 pub struct FrameBuffer {
-    // this could be an array.
-    pub framebuff: &'static mut [u32],
+    /// All screens as the firmware sees them, `pitch` bytes per line.
+    pub framebuff: &'static mut [u8],
+    /// The screen being drawn, `width` pixels per line.
+    pub pixels: Vec<u32>,
     pub width: u32,
     pub height: u32,
+    /// Bytes per line in `framebuff`, at least `width` pixels.
     pub pitch: u32,
-    pub is_rgb: bool,
-    pub is_brg: bool,
-    /// crate::mailbox::FB_VIRTUAL_WIDTH
+    pub format: PixelFormat,
+    /// Width of the virtual buffer as allocated by the firmware.
     pub fb_virtual_width: u32,
-    /// Bits used by each pixel
-    pub depth_bits: u32,
-    pub current_index: u8,
+    /// Screens allocated in `framebuff`.
+    screens: u8,
+    /// Screens flipped between, 2 or 3.
+    buffers: u8,
+    /// The screen on the display, or about to be.
+    front: u8,
+    pub vsync: Vsync,
+    /// The HVS channel whose frame counter tells when a screen is off the display.
+    pub channel: Channel,
+    /// The frame count when each screen was replaced on the display, `None` if it never was
+    /// on it.
+    retired: [Option<u32>; MAX_BUFFERS],
+    /// What each screen is missing from `pixels`.
+    stale: [Damage; MAX_BUFFERS],
+    /// Regions drawn to since the last `update`.
+    pub damage: Damage,
+    pub stats: FrameStats,
 }
 
 impl FrameBufferInterface for FrameBuffer {
     fn raw_buffer(&mut self) -> &mut [u32] {
-        let start = self.width() * self.current_height_offset();
-        let end_of_buffer = start + self.single_screen_len();
-        &mut self.framebuff[start..end_of_buffer]
+        &mut self.pixels
     }
 
     fn width(&self) -> usize {
         self.width as usize
     }
 
-    fn use_pixel(&mut self, x_usize: usize, y_usize: usize, color: Color) {
-        let width = self.width();
-        let slice_ptr = (&mut self.raw_buffer()[width * y_usize + x_usize..]).as_mut_ptr();
-        unsafe {
-            core::ptr::write_volatile(slice_ptr, color.rgb());
-        }
+    fn width_u32(&self) -> u32 {
+        self.width
+    }
+
+    fn height_u32(&self) -> u32 {
+        self.height
     }
 
     fn clear_screen(&mut self) {
-        let slice_ptr = (&mut self.raw_buffer()).as_mut_ptr();
-        for i in 0..self.single_screen_len() {
-            unsafe {
-                core::ptr::write_volatile(slice_ptr.add(i), 0);
-            }
+        self.pixels.fill(0);
+        self.damage.add_all();
+    }
+
+    /// Row by row instead of pixel by pixel, clipped to the screen.
+    fn draw_rect_fill(&mut self, point: &Coordinates, width: u32, height: u32, color: Color) {
+        let rect = Rect::new(point.x() as i32, point.y() as i32, width, height)
+            .clip(self.width, self.height);
+        let stride = self.width();
+        let (x, w) = (rect.x as usize, rect.width as usize);
+        for y in rect.y as usize..rect.y as usize + rect.height as usize {
+            self.pixels[y * stride + x..y * stride + x + w].fill(color.rgb());
         }
     }
 
+    fn add_damage(&mut self, rect: Rect) {
+        self.damage.add(rect);
+    }
+
+    /// Wait until the next screen is off the display, write everything it is missing into it
+    /// and show it. The other screens catch up on this frame's damage when it is their turn.
     fn update(&mut self) {
-        set_virtual_framebuffer_offset(self.current_index as u32 * self.height);
-        self.current_index = Self::inverse(self.current_index);
+        let start = time::time_manager().uptime();
+        let started = self.channel.frame_count();
+        for index in 0..self.buffers as usize {
+            if self.damage.is_full() {
+                self.stale[index].add_all();
+            } else {
+                for rect in self.damage.rects() {
+                    self.stale[index].add(*rect);
+                }
+            }
+        }
+        self.damage.clear();
+
+        let back = (self.front + 1) % self.buffers;
+        self.wait_until_free(back);
+        self.write_out(back);
+        set_virtual_framebuffer_offset(back as u32 * self.height);
+        let frame = self.channel.frame_count();
+        self.retired[self.front as usize] = Some(frame);
+        self.front = back;
+
+        let latency = time::time_manager().uptime().saturating_sub(start);
+        self.stats.record(latency, frame.wrapping_sub(started) & FRAME_COUNT_MASK);
     }
 }
 
 impl FrameBuffer {
-    fn single_screen_len(&self) -> usize {
-        (self.height * self.width) as usize
+    /// A black screen over the firmware's `framebuff`, which holds `screens` screens. Two of
+    /// them are used until [`FrameBuffer::set_triple_buffered`].
+    pub fn new(
+        framebuff: &'static mut [u8],
+        width: u32,
+        height: u32,
+        pitch: u32,
+        fb_virtual_width: u32,
+        screens: u8,
+        format: PixelFormat,
+    ) -> Self {
+        let location = Location {
+            address: framebuff.as_ptr() as usize,
+            size: framebuff.len(),
+            width,
+            height,
+            pitch,
+            fb_virtual_width,
+            screens,
+            format,
+        };
+        let pixels = location.reserve();
+        FrameBuffer::with_pixels(framebuff, &location, pixels)
+    }
+
+    /// [`FrameBuffer::new`] drawing into `pixels`, one for each pixel of the screen.
+    fn with_pixels(framebuff: &'static mut [u8], location: &Location, pixels: Vec<u32>) -> Self {
+        let Location {
+            width,
+            height,
+            pitch,
+            fb_virtual_width,
+            screens,
+            format,
+            ..
+        } = *location;
+        // Nothing is known about what the firmware left in the screens.
+        let stale = core::array::from_fn(|_| {
+            let mut damage = Damage::new();
+            damage.add_all();
+            damage
+        });
+        FrameBuffer {
+            framebuff,
+            pixels,
+            width,
+            height,
+            pitch,
+            format,
+            fb_virtual_width,
+            screens,
+            buffers: 2,
+            front: 0,
+            vsync: Vsync::default(),
+            channel: Channel::Dpi,
+            retired: [None; MAX_BUFFERS],
+            stale,
+            damage: Damage::new(),
+            stats: FrameStats::default(),
+        }
+    }
+
+    pub fn set_vsync(self, vsync: Vsync) -> Self {
+        let mut fb = self;
+        fb.vsync = vsync;
+        fb
+    }
+
+    pub fn set_channel(self, channel: Channel) -> Self {
+        let mut fb = self;
+        fb.channel = channel;
+        fb
     }
-    fn current_height_offset(&self) -> usize {
-        self.height as usize * self.current_index as usize
+
+    /// Flip between three screens if the firmware made room for them, otherwise stay with two.
+    pub fn set_triple_buffered(self, triple: bool) -> Self {
+        let mut fb = self;
+        fb.buffers = if triple { fb.screens.min(MAX_BUFFERS as u8) } else { 2 };
+        fb
+    }
+
+    /// The number of screens flipped between.
+    pub fn buffers(&self) -> u8 {
+        self.buffers
     }
-    fn inverse(index: u8) -> u8 {
-        if index == 1 {
-            0
-        } else {
-            1
+
+    pub fn location(&self) -> Location {
+        Location {
+            address: self.framebuff.as_ptr() as usize,
+            size: self.framebuff.len(),
+            width: self.width,
+            height: self.height,
+            pitch: self.pitch,
+            fb_virtual_width: self.fb_virtual_width,
+            screens: self.screens,
+            format: self.format,
+        }
+    }
+
+    /// Block until screen `index` has been replaced on the display by a later one.
+    fn wait_until_free(&mut self, index: u8) {
+        let Some(retired) = self.retired[index as usize] else {
+            return;
+        };
+        match self.vsync {
+            Vsync::Off => {}
+            Vsync::Firmware => {
+                if self.channel.frame_count() == retired && !wait_for_vsync() {
+                    warn!("The firmware cannot wait for vsync, flipping without it");
+                    self.vsync = Vsync::Off;
+                }
+            }
+            Vsync::Hvs => {
+                let timeout = time::time_manager().uptime() + VSYNC_TIMEOUT;
+                while self.channel.frame_count() == retired {
+                    if time::time_manager().uptime() > timeout {
+                        warn!("The HVS frame counter stopped, flipping without vsync");
+                        self.vsync = Vsync::Off;
+                        break;
+                    }
+                    core::hint::spin_loop();
+                }
+            }
+        }
+    }
+
+    /// Convert what screen `index` is missing into it.
+    fn write_out(&mut self, index: u8) {
+        let stale = self.stale[index as usize].clone();
+        self.stale[index as usize].clear();
+        if stale.is_full() {
+            self.write_rect(index, Rect::new(0, 0, self.width, self.height));
+            return;
+        }
+        for rect in stale.rects() {
+            let rect = rect.clip(self.width, self.height);
+            self.write_rect(index, rect);
+        }
+    }
+
+    fn write_rect(&mut self, index: u8, rect: Rect) {
+        let pitch = self.pitch as usize;
+        let bytes = self.format.bytes_per_pixel();
+        let screen = index as usize * self.height as usize * pitch;
+        let (x, w) = (rect.x as usize, rect.width as usize);
+        for y in rect.y as usize..rect.y as usize + rect.height as usize {
+            let source = &self.pixels[y * self.width as usize + x..][..w];
+            let line = screen + y * pitch + x * bytes;
+            let target = &mut self.framebuff[line..line + w * bytes];
+            if self.format == PixelFormat::NATIVE {
+                for (out, pixel) in target.chunks_exact_mut(4).zip(source) {
+                    out.copy_from_slice(&pixel.to_le_bytes());
+                }
+            } else {
+                for (out, pixel) in target.chunks_exact_mut(bytes).zip(source) {
+                    let value = self.format.encode(Color::from_rgb(*pixel));
+                    out.copy_from_slice(&value.to_le_bytes()[..bytes]);
+                }
+            }
         }
     }
 }
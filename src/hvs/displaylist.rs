@@ -1,28 +1,180 @@
-use core::ptr::addr_of;
+use core::ptr::{read_volatile, write_volatile};
 
-use alloc::vec::Vec;
-
-use crate::info;
-
-use super::Plane;
+use super::{Channel, Plane};
 
 #[derive(Clone, Copy)]
+#[repr(u32)]
 enum Control {
-    Valid = 1 << 30,
     Unity = 1 << 4,
+    Valid = 1 << 30,
     End = 1 << 31,
 }
 
-const SCALER_DISPLIST0: *mut u32 = 0x3F400020 as *mut u32;
-const SCALER_DISPLIST1: *mut u32 = 0x3F400024 as *mut u32;
-const SCALER_DISPLIST2: *mut u32 = 0x3F400028 as *mut u32;
+const CONTROL_SCALING_SHIFT: [u32; 2] = [5, 8];
+const CONTROL_ORDER_SHIFT: u32 = 13;
+const CONTROL_SIZE_SHIFT: u32 = 24;
+
+const POSITION_ALPHA_SHIFT: u32 = 24;
+const POSITION_Y_SHIFT: u32 = 12;
+const SIZE_HEIGHT_SHIFT: u32 = 16;
+
+/// Alpha modes of position word 2.
+const ALPHA_MODE_PIPELINE: u32 = 0 << 30;
+const ALPHA_MODE_FIXED: u32 = 1 << 30;
+/// Multiply the per pixel alpha with the fixed one.
+const ALPHA_MIX: u32 = 1 << 28;
+
+/// Marks words the HVS writes its own state into.
+const CONTEXT: u32 = 0xDEADBEEF;
+
+const PPF_AGC: u32 = 1 << 30;
+const SCALE_SHIFT: u32 = 8;
+const KERNEL_OFFSET_MASK: u32 = 0x3FFF;
+
+const SCALER_DISPLIST: [*mut u32; 3] = [
+    0x3F400020 as *mut u32,
+    0x3F400024 as *mut u32,
+    0x3F400028 as *mut u32,
+];
 
 /// DisplayList is 16KiB in size.
-type DisplayListMem = [u32; 4096];
+const MEM_WORDS: usize = 4096;
+type DisplayListMem = [u32; MEM_WORDS];
+
+/// The most words one plane can take, scaled on both axes and one of them polyphase.
+const MAX_PLANE_WORDS: usize = 17;
+/// The smallest list worth building, a few planes and the end.
+const MIN_LIST_WORDS: usize = 4 * MAX_PLANE_WORDS + 1;
+
+/// Line buffer memory for vertical scaling, in the HVS's units of two pixels. Each list gets
+/// half of it.
+const LBM_WORDS: usize = 48 * 1024;
+
+/// Mitchell-Netravali with B = C = 1/3, half of a symmetric 16 tap kernel in 9 bit
+/// coefficients, the filter Linux uses.
+const KERNEL_HALF: [u32; 6] = [
+    kernel_word(0, -2, -6),
+    kernel_word(-8, -10, -8),
+    kernel_word(-3, 2, 18),
+    kernel_word(50, 82, 119),
+    kernel_word(155, 187, 213),
+    kernel_word(227, 227, 0),
+];
+const KERNEL_WORDS: usize = KERNEL_HALF.len() * 2 - 1;
+
+const fn kernel_word(c0: i32, c1: i32, c2: i32) -> u32 {
+    (c0 as u32 & 0x1FF) | (c1 as u32 & 0x1FF) << 9 | (c2 as u32 & 0x1FF) << 18
+}
+
+/// How the HVS resizes one axis of a plane.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scaling {
+    None,
+    /// Polyphase filter, for enlarging and mild shrinking.
+    Ppf,
+    /// Trapezoidal, for shrinking to less than two thirds.
+    Tpz,
+}
+
+impl Scaling {
+    fn of(source: u32, scaled: u32) -> Scaling {
+        if source == scaled {
+            Scaling::None
+        } else if 3 * scaled >= 2 * source {
+            Scaling::Ppf
+        } else {
+            Scaling::Tpz
+        }
+    }
+
+    /// The scaler field of the control word, for a plane scaled on at least one axis.
+    fn control(x: Scaling, y: Scaling) -> u32 {
+        match (x, y) {
+            (Scaling::Ppf, Scaling::Ppf) => 0,
+            (Scaling::Tpz, Scaling::Ppf) => 1,
+            (Scaling::Ppf, Scaling::Tpz) => 2,
+            (Scaling::Tpz, Scaling::Tpz) => 3,
+            (Scaling::Ppf, Scaling::None) => 4,
+            (Scaling::None, Scaling::Ppf) => 5,
+            (Scaling::None, Scaling::Tpz) => 6,
+            (Scaling::Tpz, Scaling::None) => 7,
+            (Scaling::None, Scaling::None) => 0,
+        }
+    }
+}
+
+/// The part of a plane that is on screen.
+struct Visible {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    source_x: u32,
+    source_y: u32,
+    source_width: u32,
+    source_height: u32,
+}
+
+impl Visible {
+    /// `None` if the plane is entirely off the `width` x `height` screen.
+    fn of(plane: &Plane, width: u32, height: u32) -> Option<Visible> {
+        let (scaled_w, scaled_h) = plane.scaled_size.unwrap_or((plane.width, plane.height));
+        let (x, source_x, source_width) =
+            Self::clip(plane.start_x, scaled_w as u32, plane.width as u32, width)?;
+        let (y, source_y, source_height) =
+            Self::clip(plane.start_y, scaled_h as u32, plane.height as u32, height)?;
+        Some(Visible {
+            x: x.0,
+            y: y.0,
+            width: x.1,
+            height: y.1,
+            source_x,
+            source_y,
+            source_width,
+            source_height,
+        })
+    }
+
+    /// Clip one axis: `start` and `scaled` on a `screen` long axis, `source` long in the image.
+    /// Gives the visible start and length on screen and the matching start and length in the
+    /// image.
+    fn clip(start: i32, scaled: u32, source: u32, screen: u32) -> Option<((u32, u32), u32, u32)> {
+        let from = start.max(0) as i64;
+        let to = (start as i64 + scaled as i64).min(screen as i64);
+        if to <= from || scaled == 0 || source == 0 {
+            return None;
+        }
+        let (skip, end) = ((from - start as i64) as u64, (to - start as i64) as u64);
+        let source_from = skip * source as u64 / scaled as u64;
+        let source_to = (end * source as u64).div_ceil(scaled as u64).min(source as u64);
+        Some((
+            (from as u32, (to - from) as u32),
+            source_from as u32,
+            (source_to - source_from).max(1) as u32,
+        ))
+    }
+}
+
+/// Where our part of the display list memory is, next to the firmware's lists so they stay
+/// intact for [`super::Hvs::restore`].
+#[derive(Clone, Copy)]
+struct Area {
+    /// The filter the polyphase scaler interpolates with, uploaded once.
+    kernel: usize,
+    /// Two lists, one being built while the HVS reads the other.
+    lists: [usize; 2],
+    list_words: usize,
+}
 
 pub struct DisplayList {
     mem: *mut DisplayListMem,
     offset: usize,
+    /// Which of the two lists in `Area::lists` is built next.
+    back: usize,
+    /// Next free line buffer word in the back list's half.
+    lbm_next: usize,
+    /// Found on the first [`DisplayList::write_planes`], with the kernel uploaded.
+    area: Option<Area>,
 }
 
 impl Default for DisplayList {
@@ -36,52 +188,230 @@ impl DisplayList {
         DisplayList {
             mem: 0x3F402000 as *mut DisplayListMem,
             offset: 0,
+            back: 0,
+            lbm_next: 0,
+            area: None,
         }
     }
 
-    pub fn write_planes(&mut self, planes: &[Plane]) {
+    /// The display list the `channel` shows right now, as a word offset.
+    pub fn current(channel: Channel) -> u32 {
+        unsafe { read_volatile(SCALER_DISPLIST[channel as usize]) }
+    }
+
+    /// Point `channel` at the list starting at word `start`, from the next frame on.
+    pub fn show(channel: Channel, start: u32) {
+        unsafe { write_volatile(SCALER_DISPLIST[channel as usize], start) }
+    }
+
+    /// Build a list from `planes`, bottom first, for a `width` x `height` screen and show it on
+    /// `channel` from the next frame on. Returns `false` if the firmware's lists leave no room
+    /// for ours, the firmware's screen stays then.
+    pub fn write_planes(
+        &mut self,
+        planes: &[&Plane],
+        channel: Channel,
+        width: u32,
+        height: u32,
+    ) -> bool {
+        let area = match self.area {
+            Some(area) => area,
+            None => match self.find_area() {
+                Some(area) => {
+                    self.upload_kernel(area.kernel);
+                    self.area = Some(area);
+                    area
+                }
+                None => return false,
+            },
+        };
+        self.offset = area.lists[self.back];
+        self.lbm_next = self.back * LBM_WORDS / 2;
+        let end = area.lists[self.back] + area.list_words - 1;
         for p in planes {
-            self.write_plane(p);
+            if self.offset + MAX_PLANE_WORDS > end {
+                break;
+            }
+            if let Some(visible) = Visible::of(p, width, height) {
+                self.write_plane(p, &visible, area.kernel);
+            }
         }
 
         self.write_word(Control::End as u32);
-        self.finish();
+        Self::show(channel, area.lists[self.back] as u32);
+        self.back = 1 - self.back;
+        true
+    }
+
+    /// The larger free stretch before or after the lists the firmware shows on the enabled
+    /// channels, `None` if it cannot hold the kernel and two lists.
+    fn find_area(&self) -> Option<Area> {
+        let (mut used_start, mut used_end) = (MEM_WORDS, 0);
+        for channel in Channel::ALL.into_iter().filter(|channel| channel.is_enabled()) {
+            let start = Self::current(channel) as usize;
+            let end = (start..MEM_WORDS).find(|&i| self.word(i) & Control::End as u32 != 0)?;
+            used_start = used_start.min(start);
+            used_end = used_end.max(end + 1);
+        }
+        let (start, end) = if used_start > used_end {
+            (0, MEM_WORDS)
+        } else if MEM_WORDS - used_end >= used_start {
+            (used_end, MEM_WORDS)
+        } else {
+            (0, used_start)
+        };
+        let list_words = (end - start).checked_sub(KERNEL_WORDS)? / 2;
+        if list_words < MIN_LIST_WORDS {
+            return None;
+        }
+        let lists = start + KERNEL_WORDS;
+        Some(Area {
+            kernel: start,
+            lists: [lists, lists + list_words],
+            list_words,
+        })
     }
 
-    pub fn write_plane(&mut self, plane: &Plane) {
-        let num_words: u32 = 7; // TODO: do we need more words than this sometimes?
-        let ctl: u32 = Control::Valid as u32
-            | Control::Unity as u32
-            | (plane.order as u32) << 13
-            | num_words << 24
+    fn write_plane(&mut self, plane: &Plane, visible: &Visible, kernel: usize) {
+        let x_scaling = Scaling::of(visible.source_width, visible.width);
+        let y_scaling = Scaling::of(visible.source_height, visible.height);
+        let unity = x_scaling == Scaling::None && y_scaling == Scaling::None;
+        let lbm = self.allocate_lbm(visible, x_scaling, y_scaling);
+        if lbm.is_none() && y_scaling != Scaling::None {
+            // Out of line buffer, the plane cannot be scaled this frame.
+            return;
+        }
+
+        let start = self.offset;
+        let scaling = Scaling::control(x_scaling, y_scaling);
+        let mut control = Control::Valid as u32
+            | (plane.order as u32) << CONTROL_ORDER_SHIFT
+            | scaling << CONTROL_SCALING_SHIFT[0]
+            | scaling << CONTROL_SCALING_SHIFT[1]
             | (plane.format as u32);
-        self.write_word(ctl);
+        if unity {
+            control |= Control::Unity as u32;
+        }
+        // The size goes in once the plane's words are counted.
+        self.write_word(control);
 
-        let pos0 = plane.start_x as u32 | (plane.start_y as u32) << 16;
-        self.write_word(pos0);
+        self.write_word(
+            (plane.alpha as u32) << POSITION_ALPHA_SHIFT
+                | visible.y << POSITION_Y_SHIFT
+                | visible.x,
+        );
+        if !unity {
+            self.write_word(visible.height << SIZE_HEIGHT_SHIFT | visible.width);
+        }
+        let alpha_mode = match (plane.pixel_alpha, plane.alpha) {
+            (true, 255) => ALPHA_MODE_PIPELINE,
+            (true, _) => ALPHA_MODE_PIPELINE | ALPHA_MIX,
+            (false, _) => ALPHA_MODE_FIXED,
+        };
+        self.write_word(
+            alpha_mode | visible.source_height << SIZE_HEIGHT_SHIFT | visible.source_width,
+        );
+        self.write_word(CONTEXT);
 
-        let pos2 = plane.width as u32 | (plane.height as u32) << 16;
-        self.write_word(pos2);
+        // Bus address through the uncached alias. The MMU is off, so the CPU never holds the
+        // plane in a cache the HVS cannot see.
+        let bytes = plane.format.bytes_per_pixel() as u32;
+        let address = plane.framebuffer.as_ptr() as u32
+            + visible.source_y * plane.pitch as u32
+            + visible.source_x * bytes;
+        self.write_word((address & 0x3FFFFFFF) | 0xC0000000);
+        self.write_word(CONTEXT);
+        self.write_word(plane.pitch as u32);
 
-        self.write_word(0xDEADBEEF);
+        if !unity {
+            if let Some(lbm) = lbm {
+                self.write_word(lbm);
+            }
+            // The HVS reads them in the order H-PPF, V-PPF, H-TPZ, V-TPZ, like Linux writes
+            // them in vc4_write_scaling.
+            let axes = [
+                (x_scaling, visible.source_width, visible.width, false),
+                (y_scaling, visible.source_height, visible.height, true),
+            ];
+            for kind in [Scaling::Ppf, Scaling::Tpz] {
+                for (scaling, source, scaled, vertical) in axes {
+                    if scaling == kind {
+                        self.write_scaling(scaling, source, scaled, vertical);
+                    }
+                }
+            }
+            if x_scaling == Scaling::Ppf || y_scaling == Scaling::Ppf {
+                // Horizontal and vertical kernel for each of the two channels.
+                for _ in 0..4 {
+                    self.write_word(kernel as u32 & KERNEL_OFFSET_MASK);
+                }
+            }
+        }
 
-        let fb_ptr = plane.framebuffer.as_ptr();
-        info!("fb_ptr: {:08x}", fb_ptr as u32);
-        self.write_word((fb_ptr as u32) & 0x3FFFFFFF);
+        let size = (self.offset - start) as u32;
+        self.set_word(start, control | size << CONTROL_SIZE_SHIFT);
+    }
 
-        self.write_word(0xDEADBEEF);
-        self.write_word(plane.pitch as u32);
+    /// The scaling parameter words for one axis, shrinking or growing `source` to `scaled`.
+    fn write_scaling(&mut self, scaling: Scaling, source: u32, scaled: u32, vertical: bool) {
+        // 16.16 fixed point source pixels per screen pixel.
+        let scale = (source << 16) / scaled;
+        match scaling {
+            Scaling::Ppf => self.write_word(PPF_AGC | scale << SCALE_SHIFT),
+            Scaling::Tpz => {
+                self.write_word(scale << SCALE_SHIFT);
+                self.write_word(u32::MAX / scale);
+            }
+            Scaling::None => return,
+        }
+        if vertical {
+            self.write_word(CONTEXT);
+        }
     }
 
-    fn write_word(&mut self, word: u32) {
-        unsafe {
-            (*self.mem)[self.offset] = word;
+    /// Line buffer for a plane scaled vertically, `None` if it needs none or there is no room.
+    fn allocate_lbm(&mut self, visible: &Visible, x: Scaling, y: Scaling) -> Option<u32> {
+        if y == Scaling::None {
+            return None;
+        }
+        let pixels_per_line = if x == Scaling::Tpz {
+            visible.width
+        } else {
+            visible.source_width
+        } as usize;
+        let bytes = pixels_per_line * if y == Scaling::Tpz { 8 } else { 16 };
+        let words = bytes.next_multiple_of(64) / 2;
+
+        let start = self.lbm_next.next_multiple_of(32);
+        if start + words > (self.back + 1) * LBM_WORDS / 2 {
+            return None;
         }
+        self.lbm_next = start + words;
+        Some(start as u32)
+    }
+
+    /// Store the scaler's filter kernel at word `start`, mirrored to all 16 taps.
+    fn upload_kernel(&mut self, start: usize) {
+        for i in 0..KERNEL_WORDS {
+            let word = if i < KERNEL_HALF.len() {
+                KERNEL_HALF[i]
+            } else {
+                KERNEL_HALF[KERNEL_WORDS - i - 1]
+            };
+            self.set_word(start + i, word);
+        }
+    }
+
+    fn write_word(&mut self, word: u32) {
+        self.set_word(self.offset, word);
         self.offset += 1;
     }
 
-    fn finish(&mut self) {
-        self.offset = 0;
-        unsafe { *SCALER_DISPLIST1 = 0 }
+    fn set_word(&mut self, index: usize, word: u32) {
+        unsafe { write_volatile(&mut (*self.mem)[index], word) }
+    }
+
+    fn word(&self, index: usize) -> u32 {
+        unsafe { read_volatile(&(*self.mem)[index]) }
     }
 }
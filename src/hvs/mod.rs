@@ -1,165 +1,137 @@
+//! The Hardware Video Scaler, which composites planes onto the screen every frame.
+//!
+//! Planes are positioned, scaled, blended and stacked by the hardware, so a static background
+//! like a gauge face can be drawn once and only the overlays above it are redrawn by the CPU.
+//!
+//! The firmware drives the HVS too: it shows the mailbox framebuffer with a display list of
+//! its own, and may rewrite it when the framebuffer's virtual offset changes. While planes are
+//! shown here the framebuffer should not be flipped, [`Hvs::restore`] hands the screen back.
+
 mod displaylist;
 mod plane;
 
-use core::mem;
-
+use crate::synchronization::{interface::Mutex, NullLock};
 use alloc::vec::Vec;
+use core::ptr::read_volatile;
 use displaylist::DisplayList;
 pub use plane::*;
 
-pub const SCREEN_HEIGHT: u32 = 480;
-pub const SCREEN_WIDTH: u32 = 480;
-
-const LFB_MESSAGE_SIZE: usize = 35;
-/// Set physical (display) width/height
-const FB_PHYSICAL_WH_TAG: u32 = 0x00048003;
-/// Width of the requested frame buffer
-const FB_PHYSICAL_WIDTH: u32 = SCREEN_WIDTH;
-/// Height of the requested frame buffer
-const FB_PHYSICAL_HEIGHT: u32 = SCREEN_HEIGHT;
-
-pub const FB_BUFFER_LEN: usize = FB_PHYSICAL_HEIGHT as usize * FB_PHYSICAL_WIDTH as usize;
+/// Control of each channel, 0x10 apart.
+const SCALER_DISPCTRL: usize = 0x3F400040;
+const DISPCTRL_ENABLE: u32 = 1 << 31;
+/// Status of each channel, 0x10 apart.
+const SCALER_DISPSTAT: usize = 0x3F400048;
+const DISPSTAT_FRAME_COUNT_SHIFT: u32 = 12;
+const DISPSTAT_FRAME_COUNT_MASK: u32 = 0x3F;
+
+/// The firmware's list on the channel planes were last shown on, for [`show_firmware_list`].
+static FIRMWARE_LIST: NullLock<Option<(Channel, u32)>> = NullLock::new(None);
+
+/// The HVS output feeding a display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    /// DPI, like the HyperPixel, and DSI0.
+    Dpi = 0,
+    /// HDMI and composite.
+    Hdmi = 1,
+    /// DSI1, like the official touchscreen.
+    Dsi1 = 2,
+}
 
-/// Set virtual (buffer) width/height
-const FB_VIRTUAL_WH_TAG: u32 = 0x00048004;
-const FB_VIRTUAL_WIDTH: u32 = SCREEN_WIDTH;
-const FB_VIRTUAL_HEIGHT: u32 = SCREEN_HEIGHT * 2;
+impl Channel {
+    pub const ALL: [Channel; 3] = [Channel::Dpi, Channel::Hdmi, Channel::Dsi1];
 
-pub const TOTAL_FB_BUFFER_LEN: usize = FB_VIRTUAL_HEIGHT as usize * FB_VIRTUAL_WIDTH as usize;
+    /// The channel feeding the display the firmware set up, the first enabled one.
+    pub fn active() -> Option<Channel> {
+        Channel::ALL.into_iter().find(|channel| channel.is_enabled())
+    }
 
-const FB_VIRTUAL_OFFSET_TAG: u32 = 0x48009;
-const FB_VIRTUAL_OFFSET_X: u32 = 0;
-const FB_VIRTUAL_OFFSET_Y: u32 = 0;
+    /// Whether the channel feeds a display.
+    pub fn is_enabled(self) -> bool {
+        let control = SCALER_DISPCTRL + 0x10 * self as usize;
+        unsafe { read_volatile(control as *const u32) & DISPCTRL_ENABLE != 0 }
+    }
 
-pub struct FrameBufferData {
-    width: u16,
-    height: u16,
-    pitch: u16,
+    /// Frames the channel has started, counting up to 63 and wrapping to 0.
+    pub fn frame_count(self) -> u32 {
+        let status = SCALER_DISPSTAT + 0x10 * self as usize;
+        let status = unsafe { read_volatile(status as *const u32) };
+        (status >> DISPSTAT_FRAME_COUNT_SHIFT) & DISPSTAT_FRAME_COUNT_MASK
+    }
 }
 
-#[derive(Default)]
 pub struct Hvs {
     planes: Vec<Plane>,
     display_list: DisplayList,
+    channel: Channel,
+    width: u16,
+    height: u16,
+    /// The firmware's list, shown again by `restore`.
+    firmware_list: u32,
 }
 
 impl Hvs {
-    pub fn new() -> Hvs {
-        Hvs::default()
+    /// Composite onto a `width` x `height` screen on the DPI output.
+    pub fn new(width: u16, height: u16) -> Hvs {
+        Hvs {
+            planes: Vec::new(),
+            display_list: DisplayList::new(),
+            channel: Channel::Dpi,
+            width,
+            height,
+            firmware_list: DisplayList::current(Channel::Dpi),
+        }
+    }
+
+    pub fn set_channel(self, channel: Channel) -> Hvs {
+        let mut hvs = self;
+        hvs.channel = channel;
+        hvs.firmware_list = DisplayList::current(channel);
+        hvs
     }
 
     pub fn reset(&mut self) {
         self.planes.clear();
     }
 
-    // fn init() {
-    //     let msg = lfb_message();
-    //
-    //     info!("send msg: {:?}", msg);
-    //     while mailbox.status0().read().full().bit() {
-    //         core::hint::spin_loop();
-    //     }
-    //
-    //     let raw_ptr = msg.as_ptr();
-    //     unsafe {
-    //         mailbox.write().write_with_zero(|w| w.bits(raw_ptr as u32));
-    //     }
-    //
-    //     loop {
-    //         while mailbox.status0().read().empty().bit() {
-    //             core::hint::spin_loop();
-    //         }
-    //
-    //         if mailbox.read().read().bits() == raw_ptr as u32 {
-    //             info!("recv msg: {:?}", msg);
-    //             break;
-    //         }
-    //     }
-    //
-    //     // convert GPU address to ARM address
-    //     let fb_ptr = (msg[28] & 0x3FFFFFFF) as usize;
-    //
-    //     // get actual physical width
-    //     let width = msg[5];
-    //     // get actual physical height
-    //     let height = msg[6];
-    //     // get number of bytes per line:
-    //     let pitch = msg[33];
-    //     // get the pixel depth TODO: is this correct? Missin from: https://github.com/bztsrc/raspi3-tutorial/blob/master/09_framebuffer/lfb.c
-    //     let depth = msg[20];
-    //     // get the actual channel order. brg = 0, rgb > 0
-    //     let is_rgb = msg[24] != 0;
-    //
-    //     let fb_ptr = unsafe { &mut *(fb_ptr as *const u32 as *mut u32) };
-    //     let fb: &mut [u32] = unsafe {
-    //         core::slice::from_raw_parts_mut(fb_ptr, TOTAL_FB_BUFFER_LEN)
-    //     };
-    //
-    //     Hvs::default()
-    //
-    // }
-
     /// Add a new plane to the display list.
     ///
-    /// NOTE: The order in which planes are added will determine the order they are drawn to the
-    /// display. Planes added later will be drawn on top of planes added before.
+    /// NOTE: Planes are stacked by their `z`. Planes with the same `z` are drawn in the order
+    /// they were added, later ones on top.
     pub fn add_plane(&mut self, plane: Plane) {
         self.planes.push(plane);
     }
 
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    /// Show the planes from the next frame on.
     pub fn draw(&mut self) {
-        self.display_list.write_planes(&self.planes)
+        FIRMWARE_LIST.lock(|list| *list = Some((self.channel, self.firmware_list)));
+        let mut planes: Vec<&Plane> = self.planes.iter().collect();
+        planes.sort_by_key(|plane| plane.z);
+        let shown = self.display_list.write_planes(
+            &planes,
+            self.channel,
+            self.width as u32,
+            self.height as u32,
+        );
+        if !shown {
+            warn!("No room for the planes next to the firmware's display list");
+        }
+    }
+
+    /// Show the firmware's framebuffer again.
+    pub fn restore(&self) {
+        DisplayList::show(self.channel, self.firmware_list);
     }
 }
 
-const fn lfb_message() -> [u32; LFB_MESSAGE_SIZE] {
-    let mut msg = [0u32; LFB_MESSAGE_SIZE];
-    msg[0] = (LFB_MESSAGE_SIZE * mem::size_of::<u32>()) as u32;
-    msg[1] = 0; // says it's a request?
-    msg[2] = FB_PHYSICAL_WH_TAG;
-    msg[3] = 8;
-    msg[4] = 8;
-    msg[5] = FB_PHYSICAL_WIDTH;
-    msg[6] = FB_PHYSICAL_HEIGHT;
-
-    // set virt wh
-    msg[7] = FB_VIRTUAL_WH_TAG;
-    msg[8] = 8;
-    msg[9] = 8;
-    // FrameBufferInfo.virtual_width
-    msg[10] = FB_VIRTUAL_WIDTH;
-    // FrameBufferInfo.virtual_height
-    msg[11] = FB_VIRTUAL_HEIGHT;
-
-    // set virt offset
-    msg[12] = FB_VIRTUAL_OFFSET_TAG;
-    msg[13] = 8;
-    msg[14] = 8;
-    msg[15] = FB_VIRTUAL_OFFSET_X;
-    msg[16] = FB_VIRTUAL_OFFSET_Y;
-
-    msg[17] = 0x48005; // set depth
-    msg[18] = 4;
-    msg[19] = 4;
-    msg[20] = 32; // FrameBufferInfo.depth
-
-    msg[21] = 0x48006; // set pixel order
-    msg[22] = 4;
-    msg[23] = 4;
-    msg[24] = 1; // RGB, not BGR preferably
-
-    msg[25] = 0x40001; // Allocate buffer
-    msg[26] = 8;
-    msg[27] = 8;
-    msg[28] = 4096; // FrameBufferInfo.pointer
-    msg[29] = 0; // FrameBufferInfo.size
-
-    msg[30] = 0x40008; // get pitch
-    msg[31] = 4;
-    msg[32] = 4;
-    msg[33] = 0; // FrameBufferInfo.pitch
-
-    msg[34] = 0;
-
-    msg
+/// Show the firmware's framebuffer again, whichever [`Hvs`] showed its planes last. For when
+/// that one cannot be reached, like on a panic.
+pub fn show_firmware_list() {
+    if let Some((channel, list)) = FIRMWARE_LIST.lock(|list| *list) {
+        DisplayList::show(channel, list);
+    }
 }
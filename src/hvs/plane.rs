@@ -1,129 +1,158 @@
 use alloc::vec;
 use alloc::vec::Vec;
-use crate::info;
-#[repr(C)]
+use cogware_gfx::fb_trait::FrameBufferInterface;
+use cogware_gfx::image::Image;
+
+/// An image the HVS composites onto the screen.
+///
+/// The pixels live in normal memory and are read by the HVS while it scans out. Position,
+/// alpha, scale and z are read by [`super::Hvs::draw`].
 #[derive(Clone)]
 pub struct Plane {
     pub(super) format: PixelFormat,
     pub(super) order: PixelOrder,
-    pub(super) start_x: u16,
-    pub(super) start_y: u16,
+    /// Blend with the alpha in each pixel. Without it only `alpha` is used.
+    pub pixel_alpha: bool,
+    /// Alpha of the whole plane, 255 is opaque.
+    pub alpha: u8,
+    /// Top left corner on screen. Planes hanging off the screen are clipped.
+    pub start_x: i32,
+    pub start_y: i32,
+    /// Size of the image in pixels.
     pub(super) width: u16,
     pub(super) height: u16,
+    /// Size on screen if the HVS should scale the image, `None` to show it pixel for pixel.
+    pub scaled_size: Option<(u16, u16)>,
+    /// Planes with a higher z are drawn on top, equal ones in the order they were added.
+    pub z: i16,
+    /// Bytes from the start of one line to the next.
     pub(super) pitch: u16,
     pub(super) framebuffer: Vec<u32>,
 }
 
 impl Plane {
-    pub fn from_qoi(header: qoi::Header, image: Vec<u8>) -> Plane {
-        let format = match header.channels {
-            qoi::Channels::Rgb => PixelFormat::Rgb888,
-            qoi::Channels::Rgba => PixelFormat::Rgba8888,
-        };
-        let img_pitch = match header.channels {
-            qoi::Channels::Rgb => header.width * 3,
-            qoi::Channels::Rgba => header.width * 4,
-        };
-        info!("IMAGE PITCH: {:?}", img_pitch);
-        let framebuffer = image
-            .chunks(4)
-            .map(|p| u32::from_be_bytes([p[3], p[2], p[1], p[0]]))
-            .collect::<Vec<_>>();
-
+    /// A transparent `width` x `height` plane, lines padded to whole words.
+    pub fn new(format: PixelFormat, order: PixelOrder, width: u16, height: u16) -> Plane {
+        let pitch = (width as usize * format.bytes_per_pixel()).next_multiple_of(4);
         Plane {
             format,
-            order: PixelOrder::ARGB,
+            order,
+            pixel_alpha: false,
+            alpha: 255,
             start_x: 0,
             start_y: 0,
-            width: 480,
-            height: 480,
-            pitch: img_pitch as u16,
-            framebuffer,
+            width,
+            height,
+            scaled_size: None,
+            z: 0,
+            pitch: pitch as u16,
+            framebuffer: vec![0; pitch / 4 * height as usize],
         }
-        
     }
 
-    pub fn white() -> Plane {
-        let framebuffer = vec![0xFFFFFFFF; 480 * 480];
-
-        Plane {
-            format: PixelFormat::Rgba8888,
-            order: PixelOrder::ARGB,
-            start_x: 0,
-            start_y: 0,
-            width: 480,
-            height: 480,
-            pitch: 32,
-            framebuffer,
-        }
+    /// A plane in [`Color`] layout that widgets can draw into, see the
+    /// [`FrameBufferInterface`] impl. Opaque unless `pixel_alpha` is set, then pixels that
+    /// were never drawn stay transparent.
+    ///
+    /// [`Color`]: cogware_gfx::fb_trait::Color
+    pub fn canvas(width: u16, height: u16) -> Plane {
+        Plane::new(PixelFormat::Rgba8888, PixelOrder::ABGR, width, height)
     }
 
-    pub fn green_half_alpha() -> Plane {
-        let framebuffer = vec![0x3300FF00; 480 * 480];
-
-        Plane {
-            format: PixelFormat::Rgba8888,
-            order: PixelOrder::ARGB,
-            start_x: 0,
-            start_y: 0,
-            width: 480,
-            height: 480,
-            pitch: 32,
-            framebuffer,
+    /// A decoded QOI image, with per pixel alpha if the image has it.
+    pub fn from_qoi(header: qoi::Header, image: Vec<u8>) -> Plane {
+        let (format, order, pixel_alpha) = match header.channels {
+            // R, G, B bytes, red in the lowest.
+            qoi::Channels::Rgb => (PixelFormat::Rgb888, PixelOrder::ABGR, false),
+            // R, G, B, A bytes, red in the lowest.
+            qoi::Channels::Rgba => (PixelFormat::Rgba8888, PixelOrder::ARGB, true),
+        };
+        let mut plane = Plane::new(format, order, header.width as u16, header.height as u16)
+            .set_pixel_alpha(pixel_alpha);
+
+        let line = header.width as usize * format.bytes_per_pixel();
+        let pitch = plane.pitch as usize;
+        let mut bytes = vec![0u8; pitch * header.height as usize];
+        for (row, pixels) in image.chunks_exact(line).enumerate() {
+            bytes[row * pitch..row * pitch + line].copy_from_slice(pixels);
+        }
+        for (word, chunk) in plane.framebuffer.iter_mut().zip(bytes.as_chunks().0) {
+            *word = u32::from_le_bytes(*chunk);
         }
+        plane
     }
 
-    pub fn set_pixel_format(self, format: PixelFormat) -> Plane {
-        let mut plane = self;
-        plane.format = format;
+    /// A copy of an image loaded from the SD card, blended with its alpha unless it is opaque.
+    pub fn from_image(image: &Image) -> Plane {
+        let mut plane = Plane::canvas(image.width() as u16, image.height() as u16)
+            .set_pixel_alpha(!image.is_opaque());
+        plane.framebuffer.copy_from_slice(image.pixels());
         plane
     }
 
-    pub fn set_pixel_order(self, order: PixelOrder) -> Plane {
+    pub fn set_pixel_alpha(self, pixel_alpha: bool) -> Plane {
         let mut plane = self;
-        plane.order = order;
+        plane.pixel_alpha = pixel_alpha;
         plane
     }
 
-    pub fn set_start_x(self, start_x: u16) -> Plane {
+    pub fn set_start_x(self, start_x: i32) -> Plane {
         let mut plane = self;
         plane.start_x = start_x;
         plane
     }
 
-    pub fn set_start_y(self, start_y: u16) -> Plane {
+    pub fn set_start_y(self, start_y: i32) -> Plane {
         let mut plane = self;
         plane.start_y = start_y;
         plane
     }
 
-    pub fn set_width(self, width: u16) -> Plane {
+    pub fn set_scaled_size(self, width: u16, height: u16) -> Plane {
         let mut plane = self;
-        plane.width = width;
+        plane.scaled_size = Some((width, height));
         plane
     }
 
-    pub fn set_height(self, height: u16) -> Plane {
-        let mut plane = self;
-        plane.height = height;
-        plane
+    /// Scale the plane up or down to fit a `width` x `height` screen, keeping its aspect
+    /// ratio, and centre it.
+    pub fn fit_to(self, width: u16, height: u16) -> Plane {
+        let (w, h) = (self.width as u32, self.height as u32);
+        let (to_w, to_h) = (width as u32, height as u32);
+        let (scaled_w, scaled_h) = if to_w * h <= to_h * w {
+            (to_w, (h * to_w / w).max(1))
+        } else {
+            ((w * to_h / h).max(1), to_h)
+        };
+        self.set_scaled_size(scaled_w as u16, scaled_h as u16)
+            .set_start_x((to_w - scaled_w) as i32 / 2)
+            .set_start_y((to_h - scaled_h) as i32 / 2)
     }
+}
 
-    pub fn set_pitch(self, pitch: u16) -> Plane {
-        let mut plane = self;
-        plane.pitch = pitch;
-        plane
+/// Drawing into a plane made by [`Plane::canvas`]. Other formats do not hold one [`Color`]
+/// per word, drawing into them gives garbage.
+///
+/// [`Color`]: cogware_gfx::fb_trait::Color
+impl FrameBufferInterface for Plane {
+    fn raw_buffer(&mut self) -> &mut [u32] {
+        &mut self.framebuffer
     }
 
-    pub fn set_framebuffer(self, framebuffer: Vec<u32>) -> Plane {
-        let mut plane = self;
-        plane.framebuffer = framebuffer;
-        plane
+    fn width_u32(&self) -> u32 {
+        self.width as u32
+    }
+
+    fn height_u32(&self) -> u32 {
+        self.height as u32
     }
+
+    /// Nothing to present, the HVS reads the plane every frame.
+    fn update(&mut self) {}
 }
 
 /// The format of the pixels stored in the framebuffer.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub enum PixelFormat {
     /* 8bpp */
     Rgb332 = 0,
@@ -151,6 +180,10 @@ impl PixelFormat {
             Self::Rgba8888 => 32,
         }
     }
+
+    pub(super) fn bytes_per_pixel(&self) -> usize {
+        self.depth() as usize / 8
+    }
 }
 
 impl Default for PixelFormat {
@@ -159,11 +192,15 @@ impl Default for PixelFormat {
     }
 }
 
-/// The order of the pixels in the framebuffer.
+/// The order of the components in a pixel, as the HVS names them.
+///
+/// The names do not read like memory or DRM formats. With [`PixelFormat::Rgba8888`], `ABGR`
+/// is the [`Color`] layout (alpha, red, green, blue from the highest byte down) and `ARGB` has
+/// red in the lowest byte, like the bytes of an RGBA image. With the 16 and 24 bit formats
+/// `ARGB` has red in the highest bits and `ABGR` in the lowest.
 ///
-/// NOTE: It seems the HVS needs the Alpha component to always come first, so either ARGB or AGBR
-/// should be used.
-#[derive(Copy, Clone)]
+/// [`Color`]: cogware_gfx::fb_trait::Color
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub enum PixelOrder {
     RGBA = 0,
     BGRA = 1,
@@ -173,6 +210,6 @@ pub enum PixelOrder {
 
 impl Default for PixelOrder {
     fn default() -> Self {
-        Self::ARGB
+        Self::ABGR
     }
 }
@@ -1,12 +1,11 @@
-use crate::framebuffer::FrameBuffer; // videocoremboxbase: 3F00B880 resp-successful: 0
+use crate::framebuffer::{FrameBuffer, PixelFormat, PixelOrder}; // videocoremboxbase: 3F00B880 resp-successful: 0
 use crate::{info, mailbox::ReqResp::ResponseSuccessful};
 use core::{arch::aarch64::float32x2_t, mem, ops::BitAnd};
 // use log::info;
-// use space_invaders::{SCREEN_HEIGHT, SCREEN_WIDTH}; // we hard set these here for now, should
-// really ask the HVS for the screen H and W
 
-pub const SCREEN_HEIGHT: u32 = 480;
-pub const SCREEN_WIDTH: u32 = 480;
+/// Used when the firmware does not report a display size, the HyperPixel's 480x480.
+pub const DEFAULT_SCREEN_WIDTH: u32 = 480;
+pub const DEFAULT_SCREEN_HEIGHT: u32 = 480;
 // const ResponseSuccessful: u32 = 0;
 const VIDEOCORE_MBOX_BASE: u32 = 0x3F00B880;
 
@@ -16,21 +15,17 @@ use tock_registers::{
 };
 
 const LFB_MESSAGE_SIZE: usize = 35;
+/// Get physical (display) width/height
+const GET_PHYSICAL_WH_TAG: u32 = 0x00040003;
 /// Set physical (display) width/height
 const FB_PHYSICAL_WH_TAG: u32 = 0x00048003;
-/// Width of the requested frame buffer
-const FB_PHYSICAL_WIDTH: u32 = SCREEN_WIDTH;
-/// Height of the requested frame buffer
-const FB_PHYSICAL_HEIGHT: u32 = SCREEN_HEIGHT;
-
-pub const FB_BUFFER_LEN: usize = FB_PHYSICAL_HEIGHT as usize * FB_PHYSICAL_WIDTH as usize;
 
 /// Set virtual (buffer) width/height
 const FB_VIRTUAL_WH_TAG: u32 = 0x00048004;
-const FB_VIRTUAL_WIDTH: u32 = SCREEN_WIDTH;
-const FB_VIRTUAL_HEIGHT: u32 = SCREEN_HEIGHT * 2;
-
-pub const TOTAL_FB_BUFFER_LEN: usize = FB_VIRTUAL_HEIGHT as usize * FB_VIRTUAL_WIDTH as usize;
+/// Screens stacked in the virtual buffer. Room for three is asked for so triple buffering
+/// can be switched on later, two are enough if the firmware cannot spare the memory.
+const FB_MAX_BUFFERS: u32 = 3;
+const FB_MIN_BUFFERS: u32 = 2;
 
 const FB_VIRTUAL_OFFSET_TAG: u32 = 0x48009;
 const FB_VIRTUAL_OFFSET_X: u32 = 0;
@@ -123,6 +118,8 @@ const SET_CLOCK_RATE: u32 = 0x00038002;
 const GET_CURRENT_CLOCK_RATE: u32 = 0x00030002;
 const SET_VIRTUAL_BUFFER_OFFSET_TAG: u32 = 0x00048009;
 const TEST_SET_VIRTUAL_BUFFER_OFFSET_TAG: u32 = 0x00044009;
+/// Blocks until the next vertical sync of the display.
+const WAIT_FOR_VSYNC_TAG: u32 = 0x0004800e;
 const LAST_TAG: u32 = 0;
 
 #[repr(align(16))]
@@ -159,7 +156,37 @@ pub fn query_board_serial() -> Option<u64> {
     };
 }
 
-const fn lfb_message() -> Message<LFB_MESSAGE_SIZE> {
+const DISPLAY_SIZE_MESSAGE_SIZE: usize = 8;
+const fn display_size_message() -> Message<DISPLAY_SIZE_MESSAGE_SIZE> {
+    let mut ret = [0u32; DISPLAY_SIZE_MESSAGE_SIZE];
+    ret[0] = (DISPLAY_SIZE_MESSAGE_SIZE * mem::size_of::<u32>()) as u32;
+    ret[1] = MBOX_REQUEST;
+    ret[2] = GET_PHYSICAL_WH_TAG;
+    ret[3] = 8; // value buffer size in bytes
+    ret[4] = 0; // request
+    ret[5] = 0; // width
+    ret[6] = 0; // height
+    ret[7] = LAST_TAG;
+    Message(ret)
+}
+
+/// The size of the attached display as set up by the firmware from `config.txt` or the
+/// HDMI EDID, `None` if it does not know of one.
+pub fn display_size() -> Option<(u32, u32)> {
+    let message = display_size_message();
+    if !send_message_sync(Channel::PROP, &message) {
+        info!("Failed to query the display size.");
+        return None;
+    }
+    let (width, height) = (message.0[5], message.0[6]);
+    if width == 0 || height == 0 {
+        None
+    } else {
+        Some((width, height))
+    }
+}
+
+const fn lfb_message(width: u32, height: u32, buffers: u32) -> Message<LFB_MESSAGE_SIZE> {
     let mut ret = [0u32; LFB_MESSAGE_SIZE];
     ret[0] = (LFB_MESSAGE_SIZE * mem::size_of::<u32>()) as u32;
     ret[1] = MBOX_REQUEST;
@@ -168,18 +195,18 @@ const fn lfb_message() -> Message<LFB_MESSAGE_SIZE> {
     ret[3] = 8;
     ret[4] = 8;
     // FrameBufferInfo.width
-    ret[5] = FB_PHYSICAL_WIDTH;
+    ret[5] = width;
     // FrameBufferInfo.height
-    ret[6] = FB_PHYSICAL_HEIGHT;
+    ret[6] = height;
 
     // set virt wh
     ret[7] = FB_VIRTUAL_WH_TAG;
     ret[8] = 8;
     ret[9] = 8;
     // FrameBufferInfo.virtual_width
-    ret[10] = FB_VIRTUAL_WIDTH;
+    ret[10] = width;
     // FrameBufferInfo.virtual_height
-    ret[11] = FB_VIRTUAL_HEIGHT;
+    ret[11] = height * buffers;
 
     // set virt offset
     ret[12] = FB_VIRTUAL_OFFSET_TAG;
@@ -213,49 +240,75 @@ const fn lfb_message() -> Message<LFB_MESSAGE_SIZE> {
     Message(ret)
 }
 
+/// Allocate a framebuffer the size of the display with room for three screens, or two on the
+/// second try.
+///
+/// The firmware may not give exactly what was asked for, so the size, pitch, depth and number
+/// of screens are taken from its response.
 pub fn lfb_init<'a: 'static>(tentative: usize) -> Option<FrameBuffer> {
-    let message = lfb_message();
+    let (width, height) = display_size().unwrap_or_else(|| {
+        info!(
+            "No display size reported, using {}x{}",
+            DEFAULT_SCREEN_WIDTH, DEFAULT_SCREEN_HEIGHT
+        );
+        (DEFAULT_SCREEN_WIDTH, DEFAULT_SCREEN_HEIGHT)
+    });
+    let requested = if tentative == 1 {
+        FB_MIN_BUFFERS
+    } else {
+        FB_MAX_BUFFERS
+    };
+    let message = lfb_message(width, height, requested);
     let res = send_message_sync(Channel::PROP, &message);
-    return if res && message.0[28] != 0 {
+    // get actual physical width
+    let width = message.0[5];
+    // get actual physical height
+    let height = message.0[6];
+    let virtual_width = message.0[10];
+    let virtual_height = message.0[11];
+    // size of the whole virtual buffer in bytes
+    let size = message.0[29];
+    // get number of bytes per line:
+    let pitch = message.0[33];
+    // get the pixel depth TODO: is this correct? Missin from: https://github.com/bztsrc/raspi3-tutorial/blob/master/09_framebuffer/lfb.c
+    let depth = message.0[20];
+    // get the actual channel order. brg = 0, rgb > 0
+    let order = if message.0[24] != 0 {
+        PixelOrder::Rgb
+    } else {
+        PixelOrder::Bgr
+    };
+    let format = PixelFormat::new(depth, order);
+    let buffers = (virtual_height / height.max(1)).min(FB_MAX_BUFFERS);
+    let fits = virtual_width >= width
+        && buffers >= FB_MIN_BUFFERS
+        && format.is_some_and(|f| pitch as usize >= width as usize * f.bytes_per_pixel())
+        && size as u64 >= pitch as u64 * (height * buffers) as u64;
+    return if res && message.0[28] != 0 && fits {
         // convert GPU address to ARM address
         let fb_ptr_raw = (message.0[28] & 0x3FFFFFFF) as usize;
         info!("fb_ptr_raw: {}", fb_ptr_raw);
 
-        // get actual physical width
-        let width = message.0[5];
-        // get actual physical height
-        let height = message.0[6];
-        // get number of bytes per line:
-        let pitch = message.0[33];
-        // get the pixel depth TODO: is this correct? Missin from: https://github.com/bztsrc/raspi3-tutorial/blob/master/09_framebuffer/lfb.c
-        let depth = message.0[20];
-        // get the actual channel order. brg = 0, rgb > 0
-        let is_rgb = message.0[24] != 0;
-
-        let casted = fb_ptr_raw as *const u32 as *mut u32;
-        let casted = unsafe { &mut *casted };
-        let framebuff: &mut [u32] =
-            unsafe { core::slice::from_raw_parts_mut(casted, TOTAL_FB_BUFFER_LEN) };
-        let fb = FrameBuffer {
+        let framebuff: &mut [u8] =
+            unsafe { core::slice::from_raw_parts_mut(fb_ptr_raw as *mut u8, size as usize) };
+        let fb = FrameBuffer::new(
             framebuff,
             width,
             height,
             pitch,
-            depth_bits: depth,
-            is_rgb,
-            is_brg: !is_rgb,
-            fb_virtual_width: FB_VIRTUAL_WIDTH,
-            current_index: 0,
-        };
+            virtual_width,
+            buffers as u8,
+            format.unwrap(),
+        );
         info!(
-            "All good, setting up the frame buffer now: {}, height: {}, pitch: {}, depth:{}, is_rgb: {}",
-            width, height, pitch, depth, is_rgb
+            "All good, setting up the frame buffer now: {}, height: {}, pitch: {}, depth:{}, order: {:?}, screens: {}",
+            width, height, pitch, depth, order, buffers
         );
         Some(fb)
     } else {
         info!(
-            "Something went wrong setting up lfb. Send message: {}, lfb address: {}",
-            res, message.0[28]
+            "Something went wrong setting up lfb. Send message: {}, lfb address: {}, virtual size: {}x{}, pitch: {}, depth: {}",
+            res, message.0[28], virtual_width, virtual_height, pitch, depth
         );
         if tentative == 1 {
             None
@@ -315,6 +368,12 @@ pub fn set_virtual_framebuffer_offset(offset: u32) {
     }
 }
 
+/// Block until the display starts its next frame. `false` if the firmware does not support it.
+pub fn wait_for_vsync() -> bool {
+    let message = wait_for_vsync_message();
+    send_message_sync(Channel::PROP, &message)
+}
+
 pub fn test_set_virtual_framebuffer_offset(offset: u32) {
     let message = get_test_virtual_fb_offset_message(offset);
 
@@ -387,6 +446,19 @@ fn get_set_virtual_framebuffer_offset_message(
     Message(ret)
 }
 
+const WAIT_FOR_VSYNC_MESSAGE_SIZE: usize = 7;
+fn wait_for_vsync_message() -> Message<WAIT_FOR_VSYNC_MESSAGE_SIZE> {
+    let mut ret = [0u32; WAIT_FOR_VSYNC_MESSAGE_SIZE];
+    ret[0] = (WAIT_FOR_VSYNC_MESSAGE_SIZE * mem::size_of::<u32>()) as u32;
+    ret[1] = MBOX_REQUEST;
+    ret[2] = WAIT_FOR_VSYNC_TAG;
+    ret[3] = mem::size_of::<u32>() as u32; // value buffer size in bytes
+    ret[4] = 0; // :b 31 clear: request, | b31 set: response b30-b0: value length in bytes
+    ret[5] = 0; // unused
+    ret[6] = LAST_TAG;
+    Message(ret)
+}
+
 const TEST_SET_VIRTUAL_FRAMEBUFFER_OFFSET_MESSAGE_SIZE: usize = 8;
 fn get_test_virtual_fb_offset_message(
     offset_y: u32,
@@ -9,43 +9,64 @@
 
 extern crate alloc;
 
+mod assets;
 mod bsp;
+mod button;
 mod console;
 mod cpu;
+mod draw_target;
 mod driver;
-mod fb_trait;
 mod framebuffer;
 mod hvs;
 mod hyperpixel;
 mod mailbox;
 mod panic_wait;
 mod print;
+mod screenshot;
 mod synchronization;
 mod time;
-use alloc::{string::String, vec};
+use alloc::vec;
 
 use crate::mailbox::{max_clock_speed, set_clock_speed};
 use alloc::{format, vec::Vec};
+use assets::Assets;
 use bcm2837_hal::*;
 use bsp::memory::initialize_heap;
+use button::{Button, ButtonEvent};
+use core::cell::RefCell;
 use core::time::Duration;
 use delay::Timer;
 use embedded_hal::spi::*;
 use embedded_sdmmc::{sdcard::EMMCController, time::DummyTimesource, Mode, VolumeManager};
-use fb_trait::FrameBufferInterface;
+use cogware_gfx::animation::PowerOnSweep;
+use cogware_gfx::fb_trait::{FrameBufferInterface, BOOT_IMAGE_QOI};
+use console::fb_console::FB_CONSOLE;
+use framebuffer::Vsync;
 use fugit::RateExtU32;
 use gpio::{pin, GpioExt};
-use hvs::{Hvs, Plane};
+use hvs::{Channel, Hvs, Plane};
 use hyperpixel::HyperPixel;
+use screenshot::Screenshots;
+use cogware_gfx::mask::Shape;
+use cogware_gfx::screenshot::Format;
+use cogware_gfx::transform::{Rotation, Transform, Transformed};
+use cogware_gfx::widget::{
+    self, parse_time_of_day, PageAction, Schedule, Theme, ThemeMode, ThemeSwitch,
+};
 use pac::{bsc0::a::W, Peripherals};
-use spi::spi::{SPI0Device, SPIZero};
+use spi::spi::{BuiltinCS, SPI0Device, SPIZero, SharedSPI};
 use mcp2515::{error::Error, frame::CanFrame, regs::OpMode, CanSpeed, McpSpeed, MCP2515};
-use embedded_hal_0_2::{can::{Frame, Id, StandardId}, digital::v2::OutputPin, prelude::{_embedded_hal_blocking_delay_DelayMs, _embedded_hal_blocking_spi_Transfer}};
+use embedded_hal_0_2::{can::Frame, digital::v2::OutputPin, prelude::{_embedded_hal_blocking_delay_DelayMs, _embedded_hal_blocking_spi_Transfer}};
 use cogware_can::{cli_wri, Gauge, *};
-// use fb_trait::FrameBufferInterface;
-// use framebuffer::FrameBuffer;
 static CONFIGGAUGES: [u8; 9] = [0x20, 0x24, 0x25, 0x26, 0x28, 0x29, 0x2D, 0x35, 0x70];
-const BOOT_IMAGE_QOI: &[u8] = include_bytes!("CogWare.qoi");
+/// Remembers the dashboard page shown last, across reboots.
+const CURRENT_PAGE_FILE: &str = "CURPAGE.TXT";
+/// How often the frame pacing statistics are logged.
+const FRAME_STATS_INTERVAL: Duration = Duration::from_secs(60);
+/// How long the gauges take to swing to their maximum and back at power-on.
+const DEFAULT_GAUGE_SWEEP: Duration = Duration::from_millis(1500);
+/// How long a gauge stays trusted without a frame passing the E2E checks.
+const DEFAULT_E2E_TIMEOUT: Duration = Duration::from_millis(500);
 
 /// Early init code.
 ///
@@ -55,6 +76,7 @@ const BOOT_IMAGE_QOI: &[u8] = include_bytes!("CogWare.qoi");
 /// - The init calls in this function must appear in the correct order.
 
 unsafe fn kernel_init() -> ! {
+    let mut splash;
     // Initialize the BSP driver subsystem.
     if let Err(x) = bsp::driver::init() {
         panic!("Error initializing BSP driver subsystem: {}", x);
@@ -67,47 +89,69 @@ unsafe fn kernel_init() -> ! {
         let max_clock_speed = max_clock_speed();
         set_clock_speed(max_clock_speed.unwrap());
 
+        let boot_fb = mailbox::lfb_init(0).expect("Failed to init framebuffer");
+        // HVS vsync counts the frames of the channel the firmware shows the framebuffer on.
+        let channel = Channel::active().unwrap_or_else(|| {
+            warn!("No HVS channel is enabled, assuming DPI");
+            Channel::Dpi
+        });
+        info!("Display on HVS channel {:?}", channel);
+        let mut boot_fb = boot_fb.set_channel(channel);
+        boot_fb.display_boot_image();
+        boot_fb.update();
+
+        // The HVS shows the logo scaled to the whole screen until the dashboard is ready.
         info!("initializing hvs");
-        /*let (header, image) =
+        let (width, height) = (boot_fb.width_u32() as u16, boot_fb.height_u32() as u16);
+        let (header, image) =
             qoi::decode_to_vec(BOOT_IMAGE_QOI).expect("Failed to decode boot image (wtf?)");
-
-        let mut hvs = Hvs::new();
-        // this doesn't work
-        // why
-        // hvs.add_plane(Plane::from_qoi(header, image));
-        hvs.add_plane(Plane::white());
-        hvs.draw();
-
-        timer.delay_ms(1000);
-
-        hvs.add_plane(Plane::green_half_alpha());
-        hvs.draw();
-
-        timer.delay_ms(1000);
-        hvs.add_plane(Plane::from_qoi(header, image));
-        hvs.draw();
-
-        timer.delay_ms(1000);
-        info!("SUGMA");
-
-        // loop {
-        //     timer.delay_ns(500_000_000);
-        //     hvs.reset();
-        //     hvs.add_plane(Plane::from_qoi(header, image.clone()));
-        //     hvs.draw();
-        //     timer.delay_ns(500_000_000);
-        // }*/
-        let mut fb = mailbox::lfb_init(0).expect("Failed to init framebuffer");
-        fb.display_boot_image();
+        splash = Hvs::new(width, height).set_channel(channel);
+        splash.add_plane(Plane::from_qoi(header, image).fit_to(width, height));
+        splash.draw();
+        // The log goes to the screen as well until the dashboard takes it over, hidden behind
+        // the splash unless CONSOLE=1.
+        FB_CONSOLE.attach(boot_fb);
+        console::register_secondary_console(Some(&FB_CONSOLE));
         // let u = u.assume_init();
     }
 
     // Transition from unsafe to safe.
-    kernel_main()
+    kernel_main(splash)
+}
+
+/// Look up `key=value` in the contents of `CONFIG.TXT`.
+///
+/// Keys are matched case-insensitively, lines starting with `#` are comments.
+fn config_value<'a>(config: &'a str, key: &'a str) -> Option<&'a str> {
+    config_values(config, key).next()
+}
+
+/// All values of a key that may appear several times in `CONFIG.TXT`.
+fn config_values<'a>(config: &'a str, key: &'a str) -> impl Iterator<Item = &'a str> {
+    config
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.starts_with('#'))
+        .filter_map(|l| l.split_once('='))
+        .filter(move |(k, _)| k.trim().eq_ignore_ascii_case(key))
+        .map(|(_, v)| v.trim())
+}
+
+/// Whether a boolean config key is switched on (`1` or `true`).
+fn config_flag(config: &str, key: &str) -> bool {
+    config_value(config, key).is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+/// Parse a decimal or `0x` prefixed hexadecimal config value.
+fn parse_number(value: &str) -> Option<u32> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
 }
 
 /// The main function running after the early init.
-fn kernel_main() -> ! {
+fn kernel_main(mut splash: Hvs) -> ! {
     info!(
         "{} version {}",
         env!("CARGO_PKG_NAME"),
@@ -156,8 +200,158 @@ fn kernel_main() -> ! {
         Err(e) => format!("{:?}", e),
     };
 
+    drop(cfg_file);
+
     info!("CONFIG.TXT:\n{}", out);
 
+    // SPLASH=LOGO.QOI replaces the built-in logo while the dashboard starts, a QOI or BMP.
+    // CONSOLE=1 shows the boot log instead.
+    let mut assets = Assets::new();
+    if config_flag(&out, "CONSOLE") {
+        splash.restore();
+    } else if let Some(name) = config_value(&out, "SPLASH") {
+        match assets.load(&mut root_dir, name) {
+            Ok(image) => {
+                let (width, height) = splash.size();
+                splash.reset();
+                splash.add_plane(Plane::from_image(&image).fit_to(width, height));
+                splash.draw();
+            }
+            Err(e) => warn!("Splash {}: {}", name, e),
+        }
+    }
+
+    let protocol = match config_value(&out, "ECU") {
+        Some(name) => EcuProtocol::from_name(name).unwrap_or_else(|| {
+            warn!("Unknown ECU protocol {:?}, falling back to cogware", name);
+            EcuProtocol::Cogware
+        }),
+        None => EcuProtocol::Cogware,
+    };
+    info!("ECU protocol: {:?}", protocol);
+
+    // CAN_BASE_ID moves the protocol's ids, from 0 for Cogware, 0x5F0 for MegaSquirt and 0x600
+    // for Speeduino. CAN_EXTENDED=1 makes them 29-bit.
+    let can_ids = IdConfig::new(
+        config_value(&out, "CAN_BASE_ID")
+            .and_then(parse_number)
+            .unwrap_or(protocol.ids().base),
+        config_flag(&out, "CAN_EXTENDED"),
+    );
+    let protocol = protocol.set_ids(can_ids);
+    info!("CAN id space: {:?}", can_ids);
+
+    // Only Cogware senders add the E2E trailer. CAN_E2E_TIMEOUT is in milliseconds.
+    let e2e = config_flag(&out, "CAN_E2E");
+    if e2e && protocol != EcuProtocol::Cogware {
+        warn!("CAN_E2E needs the cogware protocol, not checking {:?} frames", protocol);
+    }
+    set_e2e(e2e && protocol == EcuProtocol::Cogware);
+    let e2e_timeout = config_value(&out, "CAN_E2E_TIMEOUT")
+        .and_then(parse_number)
+        .map_or(DEFAULT_E2E_TIMEOUT, |ms| Duration::from_millis(ms as u64));
+    info!("CAN E2E protection: {}, timeout {:?}", e2e_enabled(), e2e_timeout);
+
+    // How the panel is mounted: ROTATE=90 turns the picture clockwise, MIRROR=1 flips it for a
+    // reflection and ROUND=1 is for round panels like the HyperPixel 2.1 Round.
+    let rotation = config_value(&out, "ROTATE")
+        .map_or(Some(Rotation::Deg0), |degrees| {
+            degrees.parse().ok().and_then(Rotation::from_degrees)
+        })
+        .unwrap_or_else(|| {
+            warn!("ROTATE must be 0, 90, 180 or 270, not rotating");
+            Rotation::Deg0
+        });
+    let transform = Transform::new(rotation, config_flag(&out, "MIRROR"));
+    let shape = if config_flag(&out, "ROUND") {
+        Shape::Round
+    } else {
+        Shape::Rectangle
+    };
+    info!(
+        "Display rotated {} degrees, mirrored: {}, {:?}",
+        rotation.degrees(),
+        transform.mirror,
+        shape
+    );
+    // VSYNC=off flips straight away, VSYNC=hvs waits on the HVS instead of the firmware.
+    // TRIPLE_BUFFER=1 lets drawing go on while a finished frame waits for the display.
+    let vsync = match config_value(&out, "VSYNC") {
+        None => Vsync::Firmware,
+        Some(v) if v.eq_ignore_ascii_case("firmware") => Vsync::Firmware,
+        Some(v) if v.eq_ignore_ascii_case("hvs") => Vsync::Hvs,
+        Some(v) if v == "0" || v.eq_ignore_ascii_case("off") => Vsync::Off,
+        Some(v) => {
+            warn!("VSYNC must be off, firmware or hvs, not {}, using the firmware", v);
+            Vsync::Firmware
+        }
+    };
+    let fb = FB_CONSOLE.detach().expect("The boot console lost the framebuffer");
+    let fb = fb
+        .set_vsync(vsync)
+        .set_triple_buffered(config_flag(&out, "TRIPLE_BUFFER"));
+    info!("Vsync: {:?}, flipping between {} screens", vsync, fb.buffers());
+    let mut fb = Transformed::new(fb, transform).set_shape(shape);
+
+    let (screen_width, screen_height) = (fb.width_u32(), fb.height_u32());
+    let mut load_layout = |name: &str| {
+        let layout = root_dir
+            .open_file_in_dir(name, Mode::ReadOnly)
+            .and_then(|mut file| file.read_to_string());
+        let mut dashboard = match layout {
+            Ok(text) => match widget::parse_layout(&text) {
+                Ok(dashboard) => {
+                    if shape == Shape::Round && dashboard.shape != Shape::Round {
+                        warn!("{} is not a round layout, its corners will be cut off", name);
+                    }
+                    dashboard
+                }
+                Err(errors) => {
+                    for error in &errors {
+                        warn!("{}: {}", name, error);
+                    }
+                    widget::error_dashboard(name, &errors)
+                }
+            },
+            Err(e) => {
+                info!("No layout {} ({:?}), using the built-in one", name, e);
+                widget::Dashboard::default_layout()
+            }
+        };
+        dashboard.load_images(|file| match assets.load(&mut root_dir, file) {
+            Ok(image) => Some(image),
+            Err(e) => {
+                warn!("{}: image {}: {}", name, file, e);
+                None
+            }
+        });
+        dashboard.fit(screen_width, screen_height);
+        dashboard
+    };
+    // Every PAGE= line is a layout of its own, without any the single LAYOUT= file is used.
+    let mut dashboards: Vec<widget::Dashboard> =
+        config_values(&out, "PAGE").map(&mut load_layout).collect();
+    if dashboards.is_empty() {
+        dashboards.push(load_layout(
+            config_value(&out, "LAYOUT").unwrap_or("LAYOUT.TXT"),
+        ));
+    }
+    let saved_page = root_dir
+        .open_file_in_dir(CURRENT_PAGE_FILE, Mode::ReadOnly)
+        .and_then(|mut file| file.read_to_string())
+        .ok()
+        .and_then(|text| text.trim().parse().ok())
+        .unwrap_or(0);
+    let mut pages = widget::Pages::new(dashboards, saved_page);
+    info!("Dashboard pages: {}, showing page {}", pages.count(), pages.index());
+    // GAUGE_SWEEP=0 turns the power-on sweep off, otherwise it is its length in milliseconds.
+    let sweep = config_value(&out, "GAUGE_SWEEP")
+        .and_then(parse_number)
+        .map_or(DEFAULT_GAUGE_SWEEP, |ms| Duration::from_millis(ms as u64));
+    if !sweep.is_zero() {
+        pages.current_mut().sweep = Some(PowerOnSweep::new(sweep));
+    }
+
     info!("Drivers loaded:");
     driver::driver_manager().enumerate();
 
@@ -169,17 +363,105 @@ fn kernel_main() -> ! {
     gpio.pins[9..=11].iter().for_each(|p| {
         p.set_mode(gpio::PinMode::AF0);
     });
-    let mut cs = &mut gpio.pins[27];
+    // The dash controller keeps its GPIO27 chip select, a gateway controller sits on CE1.
+    let (low_pins, high_pins) = gpio.pins.split_at_mut(27);
+    let cs = &mut high_pins[0];
     cs.set_mode(gpio::PinMode::Output);
 
      let mut timer = Timer::new();
+
+    // Page buttons, wired to ground unless BUTTON_ACTIVE_HIGH is set.
+    let button_active_low = !config_flag(&out, "BUTTON_ACTIVE_HIGH");
+    let input = |key: &str, active_low: bool| {
+        let pin = config_value(&out, key).and_then(parse_number)?;
+        if pin > 27 {
+            warn!("{}={} is not a header GPIO, ignoring it", key, pin);
+            return None;
+        }
+        info!("{} on GPIO{}", key, pin);
+        Some(Button::new(pin as u8, active_low))
+    };
+    let button = |key: &str| input(key, button_active_low);
+    let mut next_button = button("BUTTON_NEXT");
+    let mut prev_button = button("BUTTON_PREV");
+
+    // THEME=NIGHT.TXT changes the day and night palettes, THEME.TXT if not given. NIGHT=1
+    // starts with the night palette, BUTTON_THEME or `n` on the UART switch by hand.
+    let theme_file = config_value(&out, "THEME").unwrap_or("THEME.TXT");
+    let theme = match root_dir
+        .open_file_in_dir(theme_file, Mode::ReadOnly)
+        .and_then(|mut file| file.read_to_string())
+    {
+        Ok(text) => widget::parse_theme(&text).unwrap_or_else(|errors| {
+            for error in &errors {
+                warn!("{}: {}", theme_file, error);
+            }
+            Theme::default()
+        }),
+        Err(e) => {
+            info!("No theme {} ({:?}), using the built-in one", theme_file, e);
+            Theme::default()
+        }
+    };
+    let mut theme_switch = ThemeSwitch::new(if config_flag(&out, "NIGHT") {
+        ThemeMode::Night
+    } else {
+        ThemeMode::Day
+    });
+    let mut theme_button = button("BUTTON_THEME");
+    // HEADLIGHTS=GPIO switches to night while the headlights are on, the input is high then
+    // unless HEADLIGHTS_ACTIVE_LOW is set.
+    let mut headlights = input("HEADLIGHTS", config_flag(&out, "HEADLIGHTS_ACTIVE_LOW"));
+    // Without headlights NIGHT_FROM=19:30 and DAY_FROM=07:00 switch by the time of day. The Pi
+    // has no clock, CLOCK=GAUGE names the gauge the ECU sends the minutes after midnight in.
+    let schedule = match (config_value(&out, "NIGHT_FROM"), config_value(&out, "DAY_FROM")) {
+        (Some(night), Some(day)) => match (parse_time_of_day(night), parse_time_of_day(day)) {
+            (Some(night_from), Some(day_from)) => Some(Schedule {
+                night_from,
+                day_from,
+            }),
+            _ => {
+                warn!("NIGHT_FROM and DAY_FROM must be HH:MM, not switching by time");
+                None
+            }
+        },
+        _ => None,
+    };
+    let clock = match config_value(&out, "CLOCK") {
+        Some(name) => Gauge::from_name(name).or_else(|| {
+            warn!("CLOCK={} is not a gauge, not switching by time", name);
+            None
+        }),
+        None if schedule.is_some() => {
+            warn!("NIGHT_FROM and DAY_FROM need a CLOCK gauge, not switching by time");
+            None
+        }
+        None => None,
+    };
+    let timetable = schedule.zip(clock);
+    let mut shown_mode = theme_switch.mode();
+    pages.set_palette(*theme.palette(shown_mode));
+    info!("Theme: {:?}", shown_mode);
+    // SCREENSHOT=qoi saves screenshots as QOI, a fraction of the size of the default BMP and
+    // much quicker over the UART.
+    let screenshot_format = config_value(&out, "SCREENSHOT").map_or(Format::Bmp, |name| {
+        Format::from_name(name).unwrap_or_else(|| {
+            warn!("SCREENSHOT must be bmp or qoi, not {}, using bmp", name);
+            Format::Bmp
+        })
+    });
+    let mut screenshots = Screenshots::new(screenshot_format);
+    let mut buttons_held = false;
     // HyperPixel::new(peripherals.GPIO, &mut timer).set_gpio_mode();
 
     let mut spi = SPIZero::new(&peripherals.SPI0);
     spi.init(embedded_hal::spi::MODE_0, 10.MHz());
     info!("in theory SPI inited");
+    // The dash controller and the gateway controller take turns on SPI0, each with its own
+    // chip select.
+    let spi = RefCell::new(spi);
 
-    let mut can = MCP2515::new(spi, cs);
+    let mut can = MCP2515::new(SharedSPI::new(&spi), cs);
     info!("initing CAN");
     can.init(
         &mut timer,
@@ -191,49 +473,105 @@ fn kernel_main() -> ! {
         },
     ).unwrap();
 
-    let masterack = Id::Standard(StandardId::ZERO);
-    let clirequest = Id::Standard(StandardId::new(0x015).expect("bad address"));
+    let gateway_routes: Vec<Route> = config_values(&out, "ROUTE")
+        .filter_map(|line| {
+            let route = Route::parse(line);
+            if route.is_none() {
+                warn!("Ignoring bad gateway route {:?}", line);
+            }
+            route
+        })
+        .collect();
+    let mut gateway = if config_flag(&out, "GATEWAY") {
+        // CE1 is left alone unless there is a gateway controller on it.
+        let gateway_cs = &mut low_pins[BuiltinCS::Cs1.gpio_pin().unwrap() as usize];
+        gateway_cs.set_mode(gpio::PinMode::Output);
+        let mut can2 = MCP2515::new(SharedSPI::new(&spi), gateway_cs);
+        info!("initing gateway CAN");
+        can2.init(
+            &mut timer,
+            mcp2515::Settings {
+                mode: OpMode::Normal,
+                // OEM vehicle buses run at 500k.
+                can_speed: CanSpeed::Kbps500,
+                mcp_speed: McpSpeed::MHz16,
+                clkout_en: false,
+            },
+        )
+        .unwrap();
+
+        let default = match config_value(&out, "GATEWAY_DEFAULT") {
+            Some(d) if d.eq_ignore_ascii_case("drop") => RouteAction::Drop,
+            _ => RouteAction::Forward,
+        };
+        info!("CAN gateway: {} routes, default {:?}", gateway_routes.len(), default);
+        Some((can2, Gateway::new(&gateway_routes, default)))
+    } else {
+        None
+    };
+
     let mut gaugelisten = Vec::new();
     for i in CONFIGGAUGES {
         gaugelisten.push(i);
     }
 
-    for val in &gaugelisten {
+    // Only the Cogware protocol needs to subscribe, broadcast protocols just talk.
+    let subscriptions: &[u8] = match protocol {
+        EcuProtocol::Cogware => &gaugelisten,
+        _ => &[],
+    };
+    for val in subscriptions {
         'read: loop {
             match can.read_message() {
                 Ok(frame) => {
-                    if frame.id() == masterack && frame.data()[0] == *val {
+                    if parse_master_ack(&frame) == Some(*val) {
                         break 'read;
                     }
                 }
                 Err(Error::NoMessage) => {}
                 Err(_) => {}
             }
-            let frame = CanFrame::new(clirequest, &[*val]).unwrap();
+            let frame = client_request_frame(*val).expect("CAN base id out of range");
             can.send_message(frame).ok();
         }
     }
-    let mut dispgauge0: String;
-    let mut dispgauge1: String;
-    let mut dispgauge2: String;
-    let mut dispgauge3: String;
-    let mut dispgauge4: String;
-    let mut dispgauge5: String;
-    let mut dispgauge6: String;
-    let mut dispgauge7: String;
-    let mut dispgauge8: String;
-    let mut dispgauge9: String;
-    let mut bingus: u8 = 0;
+    // The dashboard takes over the screen from the boot logo.
+    splash.restore();
+    let mut next_stats = timer.now() + FRAME_STATS_INTERVAL;
+    let mut next_e2e_check = timer.now() + e2e_timeout;
     loop {
         let timeout = timer.now() + Duration::from_millis(15);
         while timer.now() <= timeout {
+            if let Some((can2, gw)) = gateway.as_mut() {
+                match can2.read_message() {
+                    Ok(frame) => {
+                        if let Some(forwarded) = gw.route(Port::Secondary, &frame) {
+                            can.send_message(forwarded).ok();
+                        }
+                    }
+                    Err(Error::NoMessage) => {}
+                    Err(_) => {}
+                }
+            }
             match can.read_message() {
                 Ok(frame) => {
-                    // bingles = format!("{:?} {:?}", frame.id(), frame.data());
-                    if let Id::Standard(standard_id) = frame.id() {
-                        let primitive_id: u16 = standard_id.as_raw();
-                        if gaugelisten.contains(&primitive_id.try_into().unwrap()) {
-                            cli_wri(frame, primitive_id);
+                    if let Some((can2, gw)) = gateway.as_mut() {
+                        if let Some(forwarded) = gw.route(Port::Primary, &frame) {
+                            can2.send_message(forwarded).ok();
+                        }
+                    }
+                    match protocol {
+                        EcuProtocol::Cogware => {
+                            // bingles = format!("{:?} {:?}", frame.id(), frame.data());
+                            if let Some(gauge) = Gauge::from_id(frame.id()) {
+                                let primitive_id = gauge as u16;
+                                if gaugelisten.contains(&primitive_id.try_into().unwrap()) {
+                                    cli_wri(frame, primitive_id);
+                                }
+                            }
+                        }
+                        _ => {
+                            protocol.decode(&frame);
                         }
                     }
                 }
@@ -241,28 +579,102 @@ fn kernel_main() -> ! {
                 Err(_) => panic!("Oh no!"),
             }
         }
-        let boost = (MAP.get() as f64 * 0.145038) - 14.5038;
-        dispgauge0 = format!("STA: {:?}", STA_TIME.get());
-        dispgauge1 = format!("BOOST: {:.1}", boost);
-        dispgauge2 = format!("IAT: {:?}", ((IAT.get() * 2) -91));
-        dispgauge3 = format!("CLNT: {:?}", ((CLNT.get() * 2) -91));
-        dispgauge4 = format!("BATVOL: {:?}", BAT_VOL.get());
-        dispgauge5 = format!("AFR: {:?}", (AFR_PRI.get() as f64 / 10.00));
-        dispgauge6 = format!("RPM: {:?}", RPM.get());
-        dispgauge7 = format!("TPS: {:?}", TPS.get());
-        dispgauge8 = format!("CliAlive: {:?}", bingus);
-        dispgauge9 = format!("ServAli: {:?}", MASTERALIVE.get());
-        bingus = bingus.wrapping_add(1);
-        info!("{:?}", dispgauge0);
-        info!("{:?}", dispgauge1);
-        info!("{:?}", dispgauge2);
-        info!("{:?}", dispgauge3);
-        info!("{:?}", dispgauge4);
-        info!("{:?}", dispgauge5);
-        info!("{:?}", dispgauge6);
-        info!("{:?}", dispgauge7);
-        info!("{:?}", dispgauge8);
-        info!("{:?}", dispgauge9);
+        let now = timer.now();
+        if e2e_enabled() && now >= next_e2e_check {
+            next_e2e_check = now + e2e_timeout;
+            check_e2e_timeouts();
+        }
+        let next = next_button.as_mut().and_then(|b| b.poll(now)).map(|event| match event {
+            ButtonEvent::Press => PageAction::Next,
+            ButtonEvent::LongPress => PageAction::First,
+        });
+        let prev = prev_button.as_mut().and_then(|b| b.poll(now)).map(|event| match event {
+            ButtonEvent::Press => PageAction::Previous,
+            ButtonEvent::LongPress => PageAction::First,
+        });
+        let command = console::console().try_read_char();
+
+        // The headlights decide over the clock, a switch by hand holds until either changes.
+        let automatic = match (headlights.as_mut(), timetable) {
+            (Some(headlights), _) => {
+                headlights.poll(now);
+                Some(if headlights.is_pressed() {
+                    ThemeMode::Night
+                } else {
+                    ThemeMode::Day
+                })
+            }
+            // Until the clock gauge arrives it reads midnight, the theme stays as it is.
+            (None, Some((schedule, clock))) if clock.received() => {
+                Some(schedule.mode_at(Duration::from_secs(clock.get() as u64 * 60)))
+            }
+            (None, _) => None,
+        };
+        let toggled = theme_button.as_mut().and_then(|b| b.poll(now)) == Some(ButtonEvent::Press);
+        if toggled || command == Some('n') {
+            theme_switch.toggle();
+        }
+        let mode = theme_switch.update(automatic);
+        if mode != shown_mode {
+            shown_mode = mode;
+            info!("Theme: {:?}", mode);
+            pages.set_palette(*theme.palette(mode));
+        }
+
+        if let Some(action) = next.or(prev) {
+            if pages.apply(action) {
+                info!("Showing page {}", pages.index());
+                let saved = root_dir
+                    .open_file_in_dir(CURRENT_PAGE_FILE, Mode::ReadWriteCreateOrTruncate)
+                    .and_then(|mut file| {
+                        file.write(format!("{}", pages.index()).as_bytes())?;
+                        file.close()
+                    });
+                if let Err(e) = saved {
+                    warn!("Failed to save the current page: {:?}", e);
+                }
+            }
+        }
+        pages.animate(timer.now());
+        pages.current_mut().draw(&mut fb);
+
+        // Both page buttons together, or `s` on the UART, save a screenshot on the SD card,
+        // `d` on the UART sends one to the host.
+        let both_held = next_button
+            .as_ref()
+            .zip(prev_button.as_ref())
+            .is_some_and(|(next, prev)| next.is_pressed() && prev.is_pressed());
+        if (both_held && !buttons_held) || command == Some('s') {
+            // Letting go of the buttons must not turn the page.
+            next_button.iter_mut().chain(prev_button.iter_mut()).for_each(Button::cancel);
+            match screenshots.save(&mut fb, &mut root_dir) {
+                Ok(name) => info!("Screenshot saved as {}", name),
+                Err(e) => warn!("Screenshot: {}", e),
+            }
+        }
+        buttons_held = both_held;
+        if command == Some('d') {
+            match screenshots.stream(&mut fb) {
+                Ok(name) => info!("Screenshot sent as {}", name),
+                Err(e) => warn!("Screenshot: {}", e),
+            }
+        }
+        if timer.now() >= next_stats {
+            next_stats = timer.now() + FRAME_STATS_INTERVAL;
+            let stats = fb.inner.stats;
+            info!(
+                "Frames: {}, missed: {}, latency: {:?}, max: {:?}",
+                stats.frames, stats.missed, stats.latency, stats.max_latency
+            );
+            if e2e_enabled() {
+                let e2e = e2e_totals();
+                info!(
+                    "E2E ok: {}, CRC errors: {}, length errors: {}, repeated: {}, lost: {}, \
+                     timeouts: {}",
+                    e2e.ok, e2e.crc_errors, e2e.length_errors, e2e.repeated, e2e.lost, e2e.timeouts
+                );
+            }
+        }
         //info!("Spinning for 1 second");
         //time::time_manager().spin_for(Duration::from_secs(1));
     }
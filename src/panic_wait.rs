@@ -2,9 +2,11 @@
 //
 // Copyright (c) 2018-2023 Andre Richter <andre.o.richter@gmail.com>
 
-//! A panic handler that infinitely waits.
+//! A panic handler that shows the panic on the display and infinitely waits.
 
+use crate::console::fb_console::FB_CONSOLE;
 use crate::{cpu, println};
+use cogware_gfx::console::PanicReport;
 use core::panic::PanicInfo;
 
 //--------------------------------------------------------------------------------------------------
@@ -64,5 +66,14 @@ fn panic(info: &PanicInfo) -> ! {
         column,
     );
 
+    // Nobody in the car has a serial cable attached.
+    FB_CONSOLE.panic(&PanicReport {
+        message: &info.message(),
+        file: location,
+        line,
+        column,
+        uptime: timestamp,
+    });
+
     cpu::wait_forever()
 }
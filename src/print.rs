@@ -14,6 +14,9 @@ use core::fmt;
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
     console::console().write_fmt(args).unwrap();
+    if let Some(secondary) = console::secondary_console() {
+        secondary.write_fmt(args).unwrap();
+    }
 }
 
 /// Prints without a newline.
@@ -0,0 +1,105 @@
+//! Screenshots of the dashboard, saved on the SD card or sent to the host over the UART.
+//!
+//! Files are named `SHOT0000` to `SHOT9999` and never overwritten. Over the UART the file goes
+//! out as base64 between two marker lines, `miniterm` picks it up and saves it on the host.
+
+use crate::console;
+use alloc::string::String;
+use cogware_gfx::fb_trait::FrameBufferInterface;
+use cogware_gfx::image::ImageError;
+use cogware_gfx::screenshot::{self, Format};
+use core::fmt;
+use embedded_sdmmc::{BlockDevice, Directory, Mode, TimeSource};
+
+/// One more than the last file number.
+const MAX_SHOTS: u32 = 10_000;
+
+#[derive(Debug)]
+pub enum ScreenshotError<E: fmt::Debug> {
+    Capture(ImageError),
+    File(embedded_sdmmc::Error<E>),
+    /// Every file name is taken.
+    Full,
+}
+
+impl<E: fmt::Debug> fmt::Display for ScreenshotError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScreenshotError::Capture(e) => write!(f, "cannot capture the screen: {}", e),
+            ScreenshotError::File(e) => write!(f, "cannot write the file: {:?}", e),
+            ScreenshotError::Full => write!(f, "all {} file names are taken", MAX_SHOTS),
+        }
+    }
+}
+
+/// Takes screenshots in one format, numbering the files.
+pub struct Screenshots {
+    pub format: Format,
+    /// The first file number that may be free.
+    next: u32,
+    /// Screenshots sent over the UART, for their names.
+    streamed: u32,
+}
+
+impl Screenshots {
+    pub fn new(format: Format) -> Screenshots {
+        Screenshots {
+            format,
+            next: 0,
+            streamed: 0,
+        }
+    }
+
+    /// Save what `fb` shows in the next free file of `dir`. Returns the file name.
+    pub fn save<F, D, T, const MAX_DIRS: usize, const MAX_FILES: usize, const MAX_VOLUMES: usize>(
+        &mut self,
+        fb: &mut F,
+        dir: &mut Directory<D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+    ) -> Result<String, ScreenshotError<D::Error>>
+    where
+        F: FrameBufferInterface + ?Sized,
+        D: BlockDevice,
+        T: TimeSource,
+    {
+        let image = screenshot::capture(fb).map_err(ScreenshotError::Capture)?;
+        let data = self.format.encode(&image);
+        for number in self.next..MAX_SHOTS {
+            let name = self.format.file_name(number);
+            let mut file = match dir.open_file_in_dir(name.as_str(), Mode::ReadWriteCreate) {
+                Ok(file) => file,
+                Err(embedded_sdmmc::Error::FileAlreadyExists) => continue,
+                Err(e) => return Err(ScreenshotError::File(e)),
+            };
+            self.next = number + 1;
+            file.write(&data).map_err(ScreenshotError::File)?;
+            file.close().map_err(ScreenshotError::File)?;
+            return Ok(name);
+        }
+        Err(ScreenshotError::Full)
+    }
+
+    /// Send what `fb` shows over the UART, and only there. Returns the file name the host is
+    /// given.
+    pub fn stream<F: FrameBufferInterface + ?Sized>(
+        &mut self,
+        fb: &mut F,
+    ) -> Result<String, ImageError> {
+        let image = screenshot::capture(fb)?;
+        let data = self.format.encode(&image);
+        let name = self.format.file_name(self.streamed);
+        self.streamed += 1;
+        // The UART cannot fail.
+        let _ = screenshot::write_stream(&mut Uart, &name, &data);
+        console::console().flush();
+        Ok(name)
+    }
+}
+
+/// The primary console, without the secondary one `print!` writes to as well.
+struct Uart;
+
+impl fmt::Write for Uart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        console::console().write_fmt(format_args!("{}", s))
+    }
+}